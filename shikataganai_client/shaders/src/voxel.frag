@@ -5,6 +5,7 @@ layout(location = 1) flat in int cube_selected;
 layout(location = 2) flat in int face_selected;
 layout(location = 3) in vec3 brightness;
 layout(location = 4) in float occlusion;
+layout(location = 5) in vec3 tint;
 
 layout(location = 0) out vec4 out_color;
 
@@ -13,6 +14,7 @@ layout(set = 1, binding = 1) uniform sampler s_diffuse;
 
 void main() {
   out_color = texture(sampler2DArray(t_diffuse, s_diffuse), vec3(uv, 0));
+  out_color.rgb *= tint;
 //  out_color = vec4(occlusion, occlusion, occlusion, 1.0);
   out_color = vec4(occlusion * vec3(out_color.r * brightness.r, out_color.g * brightness.g, out_color.b * brightness.b), out_color.a);
   if (out_color.a <= 0.01) {
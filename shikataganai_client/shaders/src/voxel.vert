@@ -4,12 +4,14 @@ layout(set = 0, location = 0) in vec3 position;
 layout(set = 0, location = 1) in vec2 uv;
 layout(set = 0, location = 2) in ivec4 tile;
 layout(set = 0, location = 3) in uvec4 meta;
+layout(set = 0, location = 4) in vec4 tint;
 
 layout(location = 0) out vec2 uv_out;
 layout(location = 1) out int cube_selected;
 layout(location = 2) out int face_selected;
 layout(location = 3) out vec3 brightness;
 layout(location = 4) out float occlusion;
+layout(location = 5) out vec3 tint_out;
 
 layout (set = 2, binding = 0) uniform Selection {
   ivec3 cube;
@@ -56,4 +58,5 @@ void main() {
 
   brightness = brightness_mod * texture(sampler2D(light_texture, light_sampler), vec2(meta[1] / 16.0 + 0.5 / 16.0, meta[0] / 16.0 + 0.5 / 16.0)).rgb;
   occlusion = 1.0 - float(meta[2]) / 4.0;
+  tint_out = tint.rgb;
 }
\ No newline at end of file
@@ -1,37 +1,28 @@
-use crate::ecs::components::blocks::{
-  animate, AnimationTrait, BlockRenderInfo, BlockTraitExt, ChestAnimations, Skeletons,
-};
+use crate::ecs::components::blocks::{animate, AnimationTrait, BlockInterface, ChestAnimations};
+use crate::ecs::plugins::client::{send_message, Requested};
 use crate::ecs::plugins::game::ShikataganaiGameState;
-use crate::ecs::plugins::rendering::mesh_pipeline::loader::Meshes;
 use bevy::prelude::{Commands, Entity};
 use bevy_renet::renet::RenetClient;
 use bincode::serialize;
 use iyes_loopless::prelude::NextState;
-use shikataganai_common::networking::{ClientChannel, PlayerCommand};
+use shikataganai_common::networking::{ClientChannel, FunctorType, PlayerCommand};
 use shikataganai_common::util::array::DDD;
 use crate::ecs::systems::user_interface::{InventoryItemMovementStatus, InventoryOpened};
 
-pub struct Stair;
 pub struct Chest;
 
-impl BlockTraitExt for Stair {
-  fn render_info(&self) -> BlockRenderInfo {
-    BlockRenderInfo::AsMesh(Meshes::Stair)
-  }
-}
-
-impl BlockTraitExt for Chest {
-  fn render_info(&self) -> BlockRenderInfo {
-    BlockRenderInfo::AsSkeleton(Skeletons::Chest)
-  }
-  fn right_click_interface(
-    &self,
-    entity: Entity,
-    location: DDD,
-    commands: &mut Commands,
-    client: &mut RenetClient,
-  ) -> Option<()> {
+impl BlockInterface for Chest {
+  fn open(&self, entity: Entity, location: DDD, commands: &mut Commands, client: &mut RenetClient) {
     commands.insert_resource(InventoryOpened(entity));
+    // Always ask for a fresh copy, this also subscribes us to changes made by other players
+    send_message(
+      client,
+      PlayerCommand::RequestFunctor {
+        location,
+        functor: FunctorType::InternalInventory,
+      },
+    );
+    commands.entity(entity).insert(Requested);
     commands.insert_resource(InventoryItemMovementStatus::Nothing);
     commands.insert_resource(NextState(ShikataganaiGameState::InterfaceOpened));
 
@@ -44,6 +35,5 @@ impl BlockTraitExt for Chest {
       })
       .unwrap(),
     );
-    Some(())
   }
 }
@@ -1,20 +1,25 @@
 use crate::ecs::plugins::rendering::mesh_pipeline::loader::Meshes;
 use bevy::prelude::*;
+use bevy::render::extract_resource::ExtractResource;
 use bevy::utils::hashbrown::HashMap;
 use bevy_renet::renet::RenetClient;
 use num_traits::FloatConst;
 use shikataganai_common::ecs::components::blocks::animation::{Animation, AnimationType};
 use shikataganai_common::ecs::components::blocks::block_id::BlockId;
-use shikataganai_common::ecs::components::blocks::Block;
+use shikataganai_common::ecs::components::blocks::registry::{BlockDefinition, BlockRegistry, RenderDefinition};
+use shikataganai_common::ecs::resources::registries::Registries;
 use shikataganai_common::util::array::DDD;
+use std::str::FromStr;
+use std::sync::Arc;
+use strum_macros::EnumString;
 
-pub mod regular_blocks;
-pub mod regular_meshes;
+pub mod interfaces;
 
 const BLOCK_SPRITE_SHEET_WIDTH: usize = 8;
 
 #[allow(dead_code)]
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, EnumString)]
+#[strum(serialize_all = "snake_case")]
 pub enum BlockSprite {
   Empty,
   Dirt,
@@ -60,6 +65,8 @@ impl BlockSprite {
   }
 }
 
+#[derive(Copy, Clone, EnumString)]
+#[strum(serialize_all = "snake_case")]
 pub enum Skeletons {
   Chest,
 }
@@ -162,6 +169,7 @@ impl AnimationTrait for ChestAnimations {
   }
 }
 
+#[derive(Copy, Clone)]
 pub enum BlockRenderInfo {
   Nothing,
   AsBlock([BlockSprite; 6]),
@@ -182,31 +190,90 @@ pub trait BlockTraitExt {
   }
 }
 
-pub trait DerefExt {
-  fn deref_ext(&self) -> &'static dyn BlockTraitExt;
+// What a block opens when right clicked, picked by the interface name of its definition
+pub trait BlockInterface {
+  fn open(&self, entity: Entity, location: DDD, commands: &mut Commands, client: &mut RenetClient);
 }
 
-static BLOCK_TRAITS_EXT: [&(dyn BlockTraitExt + Sync); 8] = [
-  &regular_blocks::Air,
-  &regular_blocks::Dirt,
-  &regular_blocks::Grass,
-  &regular_blocks::Cobblestone,
-  &regular_blocks::Iron,
-  &regular_meshes::Stair,
-  &regular_meshes::Chest,
-  &regular_blocks::Furnace,
-];
+fn interface_by_name(name: &str) -> Option<&'static (dyn BlockInterface + Sync)> {
+  match name {
+    "chest" => Some(&interfaces::Chest),
+    _ => None,
+  }
+}
+
+// A block definition with its names resolved against what this client can draw
+pub struct ClientBlock {
+  render: BlockRenderInfo,
+  interface: Option<&'static (dyn BlockInterface + Sync)>,
+}
+
+impl ClientBlock {
+  fn new(definition: &BlockDefinition) -> Result<Self, String> {
+    let unknown = |kind: &str, name: &str| format!("Block {} uses {} {}, which this client doesn't have", definition.name, kind, name);
+    let render = match &definition.render {
+      RenderDefinition::Nothing => BlockRenderInfo::Nothing,
+      RenderDefinition::Block(sprites) => {
+        let mut resolved = [BlockSprite::Empty; 6];
+        for (resolved, sprite) in resolved.iter_mut().zip(sprites.iter()) {
+          *resolved = BlockSprite::from_str(sprite).map_err(|_| unknown("sprite", sprite))?;
+        }
+        BlockRenderInfo::AsBlock(resolved)
+      }
+      RenderDefinition::Mesh(mesh) => BlockRenderInfo::AsMesh(Meshes::from_str(mesh).map_err(|_| unknown("mesh", mesh))?),
+      RenderDefinition::Skeleton(skeleton) => {
+        BlockRenderInfo::AsSkeleton(Skeletons::from_str(skeleton).map_err(|_| unknown("skeleton", skeleton))?)
+      }
+    };
+    let interface = definition
+      .interface
+      .as_deref()
+      .map(|interface| interface_by_name(interface).ok_or_else(|| unknown("interface", interface)))
+      .transpose()?;
+    Ok(Self { render, interface })
+  }
+}
+
+impl BlockTraitExt for ClientBlock {
+  fn render_info(&self) -> BlockRenderInfo {
+    self.render
+  }
+  fn right_click_interface(
+    &self,
+    entity: Entity,
+    location: DDD,
+    commands: &mut Commands,
+    client: &mut RenetClient,
+  ) -> Option<()> {
+    self.interface?.open(entity, location, commands, client);
+    Some(())
+  }
+}
+
+// What this client draws for the blocks of the Registries, replaced along with them when joining a server. Extracted
+// into the render world for the inventory icons.
+#[derive(Resource, ExtractResource, Clone)]
+pub struct ClientBlocks(Arc<Vec<ClientBlock>>);
+
+impl ClientBlocks {
+  // Fails if this client can't draw one of the blocks
+  pub fn new(registry: &BlockRegistry) -> Result<Self, String> {
+    let blocks = registry
+      .blocks()
+      .iter()
+      .map(|block| ClientBlock::new(&block.definition))
+      .collect::<Result<_, String>>()?;
+    Ok(Self(Arc::new(blocks)))
+  }
 
-impl DerefExt for BlockId {
-  #[inline]
-  fn deref_ext(&self) -> &'static dyn BlockTraitExt {
-    BLOCK_TRAITS_EXT[*self as usize]
+  // Ids outside of the registry draw as air
+  pub fn block(&self, id: BlockId) -> &dyn BlockTraitExt {
+    self.0.get(id.0 as usize).unwrap_or(&self.0[0])
   }
 }
 
-impl DerefExt for Block {
-  #[inline]
-  fn deref_ext(&self) -> &'static dyn BlockTraitExt {
-    self.block.deref_ext()
+impl Default for ClientBlocks {
+  fn default() -> Self {
+    Self::new(&Registries::default().blocks).expect("The built-in blocks can't be drawn")
   }
 }
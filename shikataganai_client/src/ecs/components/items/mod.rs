@@ -1,10 +1,17 @@
+use bevy::prelude::*;
+use bevy::render::extract_resource::ExtractResource;
+use shikataganai_common::ecs::components::item::registry::{ItemDefinition, ItemRegistry};
 use shikataganai_common::ecs::components::item::ItemId;
-
-pub mod regular_items;
+use shikataganai_common::ecs::resources::registries::Registries;
+use std::str::FromStr;
+use std::sync::Arc;
+use strum_macros::EnumString;
 
 const ITEM_SPRITE_SHEET_WIDTH: usize = 8;
 
 #[allow(dead_code)]
+#[derive(Copy, Clone, EnumString)]
+#[strum(serialize_all = "snake_case")]
 pub enum ItemSprite {
   Nothing,
   Coal,
@@ -34,16 +41,53 @@ pub trait ItemTraitExt {
   fn render_info(&self) -> ItemSprite;
 }
 
-pub trait ItemDerefExt {
-  fn deref_ext(&self) -> &dyn ItemTraitExt;
+// An item definition with its sprite resolved against this client's item sheet
+pub struct ClientItem {
+  sprite: ItemSprite,
+}
+
+impl ClientItem {
+  fn new(definition: &ItemDefinition) -> Result<Self, String> {
+    let sprite = ItemSprite::from_str(&definition.sprite).map_err(|_| {
+      format!(
+        "Item {} uses sprite {}, which this client doesn't have",
+        definition.name, definition.sprite
+      )
+    })?;
+    Ok(Self { sprite })
+  }
+}
+
+impl ItemTraitExt for ClientItem {
+  fn render_info(&self) -> ItemSprite {
+    self.sprite
+  }
 }
 
-static ITEM_TRAITS_EXT: [&(dyn ItemTraitExt + Sync); 3] =
-  [&regular_items::Coal, &regular_items::Wand, &regular_items::Iron];
+// What this client draws for the items of the Registries, replaced along with them when joining a server. Extracted
+// into the render world for the inventory icons.
+#[derive(Resource, ExtractResource, Clone)]
+pub struct ClientItems(Arc<Vec<ClientItem>>);
+
+impl ClientItems {
+  // Fails if this client can't draw one of the items
+  pub fn new(registry: &ItemRegistry) -> Result<Self, String> {
+    let items = registry
+      .items()
+      .iter()
+      .map(|item| ClientItem::new(&item.definition))
+      .collect::<Result<_, String>>()?;
+    Ok(Self(Arc::new(items)))
+  }
+
+  // Ids outside of the registry draw as the first item
+  pub fn item(&self, id: ItemId) -> &dyn ItemTraitExt {
+    self.0.get(id.0 as usize).unwrap_or(&self.0[0])
+  }
+}
 
-impl ItemDerefExt for ItemId {
-  #[inline]
-  fn deref_ext(&self) -> &'static dyn ItemTraitExt {
-    ITEM_TRAITS_EXT[*self as usize]
+impl Default for ClientItems {
+  fn default() -> Self {
+    Self::new(&Registries::default().items).expect("The built-in items can't be drawn")
   }
 }
@@ -1,5 +1,5 @@
-use crate::ecs::components::blocks::BlockRenderInfo;
-use crate::ecs::components::blocks::DerefExt;
+use crate::ecs::components::blocks::{BlockRenderInfo, ClientBlocks};
+use crate::ecs::plugins::console::{AddConsoleCommand, ArgKind, ConsoleCommand};
 use crate::ecs::plugins::game::{in_game_input_enabled, ShikataganaiGameState};
 use crate::ecs::plugins::rendering::mesh_pipeline::loader::GltfMeshStorageHandle;
 use crate::ecs::plugins::settings::MouseSensitivity;
@@ -17,7 +17,9 @@ use bevy_rapier3d::rapier::prelude::Group as GroupRapier;
 use iyes_loopless::prelude::{ConditionSet, CurrentState, IntoConditionalSystem};
 use iyes_loopless::state::NextState;
 use num_traits::float::FloatConst;
+use shikataganai_common::ecs::resources::registries::Registries;
 use shikataganai_common::ecs::resources::world::GameWorld;
+use shikataganai_common::networking::{EYE_HEIGHT, MAX_PLAYER_SPEED, REACH_DISTANCE};
 use shikataganai_common::util::array::{to_ddd, DDD};
 
 pub struct CameraPlugin;
@@ -66,11 +68,85 @@ pub fn raycast_to_block(
 #[derive(Default, Resource)]
 pub struct PlayerPreviousPosition(pub DDD);
 
+// Set from the console, the server still refuses moves faster than MAX_PLAYER_SPEED
+#[derive(Resource)]
+pub struct PlayerMovement {
+  pub speed: f32,
+  // No gravity and no collisions
+  pub noclip: bool,
+}
+
+impl Default for PlayerMovement {
+  fn default() -> Self {
+    Self {
+      speed: 5.0,
+      noclip: false,
+    }
+  }
+}
+
 impl Plugin for CameraPlugin {
   fn build(&self, app: &mut App) {
     app
       .init_resource::<Recollide>()
-      .init_resource::<PlayerPreviousPosition>();
+      .init_resource::<PlayerPreviousPosition>()
+      .init_resource::<PlayerMovement>()
+      .add_console_command(ConsoleCommand {
+        name: "sensitivity",
+        args: &[("value", ArgKind::Float)],
+        optional: 0,
+        help: "Set how fast the mouse turns the camera, between 0 and 2",
+        run: |world, args| {
+          let sensitivity = args[0].float();
+          if !(0.0..=2.0).contains(&sensitivity) {
+            return Err(format!("Sensitivity has to be between 0 and 2, got {}", sensitivity));
+          }
+          world.resource_mut::<MouseSensitivity>().0 = sensitivity;
+          Ok(format!("Sensitivity set to {}", sensitivity))
+        },
+      })
+      .add_console_command(ConsoleCommand {
+        name: "player_speed",
+        args: &[("value", ArgKind::Float)],
+        optional: 0,
+        help: "Set how fast the player walks in blocks per second",
+        run: |world, args| {
+          let speed = args[0].float();
+          // The server corrects anything faster
+          if !(speed > 0.0 && speed <= MAX_PLAYER_SPEED) {
+            return Err(format!("Speed has to be above 0 and at most {}, got {}", MAX_PLAYER_SPEED, speed));
+          }
+          world.resource_mut::<PlayerMovement>().speed = speed;
+          Ok(format!("Changed player speed to {}", speed))
+        },
+      })
+      .add_console_command(ConsoleCommand {
+        name: "noclip",
+        args: &[("on", ArgKind::Bool)],
+        optional: 1,
+        help: "Fly through blocks with space and shift, toggles without an argument",
+        run: |world, args| {
+          let mut movement = world.resource_mut::<PlayerMovement>();
+          let noclip = args.first().map(|arg| arg.bool()).unwrap_or(!movement.noclip);
+          movement.noclip = noclip;
+          Ok(format!("noclip {}", if noclip { "on" } else { "off" }))
+        },
+      })
+      .add_console_command(ConsoleCommand {
+        name: "position",
+        args: &[],
+        optional: 0,
+        help: "Print where the player stands",
+        run: |world, _| {
+          let translation = world
+            .query_filtered::<&Transform, With<Player>>()
+            .get_single(world)
+            .map_err(|_| "There is no player".to_string())?
+            .translation;
+          // Where the server has the player, the camera is at eye height
+          Ok(format!("{:.2} {:.2} {:.2}", translation.x, translation.y - EYE_HEIGHT, translation.z))
+        },
+      });
 
     let on_game_simulation_continuous = ConditionSet::new()
       .run_in_state(ShikataganaiGameState::Simulation)
@@ -137,6 +213,7 @@ fn movement_input_system(
   camera_transform: Query<&Transform, With<Camera>>,
   mut mouse_events: EventReader<MouseMotion>,
   mouse_sensitivity: Res<MouseSensitivity>,
+  player_movement: Res<PlayerMovement>,
   key_events: Res<Input<KeyCode>>,
   mut windows: ResMut<Windows>,
   time: Res<Time>,
@@ -178,7 +255,14 @@ fn movement_input_system(
       movement += back;
     }
 
-    if key_events.pressed(KeyCode::Space) && *stationary_frames > 2 {
+    if player_movement.noclip {
+      if key_events.pressed(KeyCode::Space) {
+        movement += Vec3::Y;
+      }
+      if key_events.pressed(KeyCode::LShift) {
+        movement -= Vec3::Y;
+      }
+    } else if key_events.pressed(KeyCode::Space) && *stationary_frames > 2 {
       *stationary_frames = 0;
       fps_camera.velocity.y = 7.0;
     }
@@ -186,6 +270,11 @@ fn movement_input_system(
 
   movement = movement.normalize_or_zero();
 
+  if player_movement.noclip {
+    fps_camera.velocity = movement * player_movement.speed;
+    return;
+  }
+
   if fps_camera.velocity.y.abs() < 0.001 {
     *stationary_frames += 1;
   } else {
@@ -195,7 +284,7 @@ fn movement_input_system(
   let y = fps_camera.velocity.y;
   fps_camera.velocity.y = 0.0;
   fps_camera.velocity = movement;
-  fps_camera.velocity *= 5.0;
+  fps_camera.velocity *= player_movement.speed;
   fps_camera.velocity.y = y;
 
   if game_world.get(to_ddd(translation)).is_none() {
@@ -245,6 +334,8 @@ fn update_colliders(
   mesh_assets: Res<Assets<Mesh>>,
   storage: Res<GltfMeshStorageHandle>,
   mesh_storage_assets: Res<Assets<GltfMeshStorage>>,
+  registries: Res<Registries>,
+  client_blocks: Res<ClientBlocks>,
 ) {
   let player_new_position_translation = player_transform.single().translation;
   let player_new_position = to_ddd(player_new_position_translation);
@@ -256,8 +347,8 @@ fn update_colliders(
           let c = player_new_position_translation + Vec3::new(ix as f32, iy as f32, iz as f32);
           let c = to_ddd(c);
           if let Some(block) = game_world.get(c) {
-            if !block.passable() {
-              match block.deref_ext().render_info() {
+            if !registries.block(block.block).passable() {
+              match client_blocks.block(block.block).render_info() {
                 BlockRenderInfo::AsBlock(_) => {
                   commands.spawn(ProximityColliderBundle::proximity_collider(
                     Collider::cuboid(0.5, 0.5, 0.5),
@@ -307,6 +398,7 @@ fn collision_movement_system(
   mut transforms: Query<&mut Transform>,
   time: Res<Time>,
   rapier_context: Res<RapierContext>,
+  player_movement: Res<PlayerMovement>,
 ) {
   let (entity_camera, mut fps_camera): (Entity, Mut<FPSCamera>) = camera.single_mut();
   let entity_player = player.single();
@@ -324,6 +416,10 @@ fn collision_movement_system(
   let feet_shape = Collider::cylinder(0.05, 0.2);
 
   let mut movement_left = fps_camera.velocity * time.delta().as_secs_f32();
+  if player_movement.noclip {
+    transforms.get_mut(entity_player).unwrap().translation += movement_left;
+    return;
+  }
   let leg_height = 0.26;
 
   let filter = QueryFilter {
@@ -415,7 +511,7 @@ fn block_pick(
   let transform = camera.single();
 
   if let Some((entity, intersection)) =
-    raycast_to_block(&rapier_context, transform.translation(), transform.forward(), 5.0)
+    raycast_to_block(&rapier_context, transform.translation(), transform.forward(), REACH_DISTANCE)
   {
     // TODO: generalise it. Make it possible to right click on custom meshes
     let transform = transforms.get(entity).unwrap();
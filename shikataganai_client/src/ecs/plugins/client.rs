@@ -1,45 +1,53 @@
 use bevy::prelude::*;
 use bevy::utils::hashbrown::HashMap;
-use bevy_renet::renet::{ClientAuthentication, RenetClient, RenetError};
+use bevy_renet::renet::{ClientAuthentication, ConnectToken, RenetClient, RenetError};
 use bevy_renet::RenetClientPlugin;
 use bincode::*;
 use flate2::read::ZlibDecoder;
 use iyes_loopless::prelude::ConditionSet;
 use num_traits::{Float, FloatConst};
 use shikataganai_common::ecs::components::blocks::block_id::BlockId;
-use shikataganai_common::ecs::components::blocks::Block;
+use shikataganai_common::ecs::components::blocks::registry::BlockDefinition;
+use shikataganai_common::ecs::components::item::registry::ItemDefinition;
 use shikataganai_common::ecs::components::chunk::Chunk;
 use shikataganai_common::ecs::components::functors::InternalInventory;
 use shikataganai_common::ecs::resources::light::RelightEvent;
-use shikataganai_common::ecs::resources::player::PlayerNickname;
+use shikataganai_common::ecs::resources::player::{PlayerInventory, PlayerNickname};
+use shikataganai_common::ecs::resources::registries::Registries;
 use shikataganai_common::ecs::resources::world::GameWorld;
 use shikataganai_common::networking::{
-  client_connection_config, ClientChannel, FunctorType, NetworkFrame, PlayerCommand, PolarRotation, ServerChannel,
-  ServerMessage, PROTOCOL_ID,
+  client_connection_config, read_disconnect_reason, ChunkReassembly, ChunkStreamMessage, ClientChannel, FrameAck,
+  FunctorType, NetworkFrame, PlayerCommand, PolarRotation, ServerChannel, ServerMessage, Snapshot, EYE_HEIGHT,
+  DEFAULT_TICK_RATE, FRAME_HISTORY, PROTOCOL_ID,
 };
+use shikataganai_common::util::array::{DD, DDD};
+use std::collections::VecDeque;
+use std::fs::File;
 use std::io::Read;
 use std::net::UdpSocket;
 use std::time::SystemTime;
 use tracing::Level;
 
-use crate::ecs::components::blocks::animate;
+use crate::ecs::components::blocks::{animate, ClientBlocks};
+use crate::ecs::components::items::ClientItems;
 use crate::ecs::plugins::camera::{FPSCamera, Player, Recollide};
 use crate::ecs::plugins::console::ConsoleText;
 use crate::ecs::plugins::game::{in_game, LocalTick};
 use crate::ecs::plugins::rendering::mesh_pipeline::loader::{get_mesh_from_storage, GltfMeshStorageHandle, Meshes};
 use crate::ecs::plugins::rendering::mesh_pipeline::systems::MeshMarker;
+use crate::ecs::plugins::rendering::inventory_pipeline::inventory_cache::ExtractedItems;
 use crate::ecs::plugins::rendering::mesh_pipeline::AmongerTextureHandle;
 use crate::ecs::plugins::rendering::voxel_pipeline::meshing::RemeshEvent;
-use crate::ecs::resources::player::PlayerInventory;
-use crate::ecs::systems::input::add_item_inventory;
 use crate::GltfMeshStorage;
 
+// Server entities of the players by their id
 #[derive(Default, Resource)]
-struct NetworkMapping(HashMap<Entity, Entity>);
+struct NetworkMapping(HashMap<Entity, u64>);
 
 #[derive(Debug, Resource)]
 struct PlayerInfo {
-  client_entity: Entity,
+  // Only there while the newest frame has the player, see interpolate_players
+  client_entity: Option<Entity>,
 }
 
 #[derive(Debug, Default, Resource)]
@@ -60,6 +68,102 @@ pub struct LegAnimationFrame(f32, u32);
 #[derive(Component)]
 pub struct Requested;
 
+// Sent along with the nickname, reserves it on servers that don't hand out connect tokens
+#[derive(Default, Resource)]
+pub struct PlayerPassword(pub Option<String>);
+
+// Chunks and game events travel on different channels, so an event can overtake the chunk it belongs to. Events for
+// chunks that haven't arrived yet wait here and are replayed once the chunk is in.
+#[derive(Default, Resource)]
+pub struct PendingChunkEvents(HashMap<DD, Vec<ServerMessage>>);
+
+// Definitions sent ahead of AuthConfirmed, installed when it arrives
+#[derive(Default, Resource)]
+pub struct ReceivedDefinitions {
+  items: Vec<ItemDefinition>,
+  blocks: Vec<BlockDefinition>,
+}
+
+// Ticks remote players are drawn behind the newest frame, enough to ride out a lost frame or two
+const INTERPOLATION_DELAY: f64 = 3.0;
+
+// Frames received from the server, oldest first. They double as baselines for the frames that follow.
+#[derive(Resource)]
+pub struct SnapshotBuffer {
+  snapshots: VecDeque<(u32, Snapshot)>,
+  // Server tick remote players are currently drawn at
+  render_tick: Option<f64>,
+  // Server ticks per second, as told by the server
+  tick_rate: f64,
+}
+
+impl Default for SnapshotBuffer {
+  fn default() -> Self {
+    Self {
+      snapshots: VecDeque::new(),
+      render_tick: None,
+      tick_rate: DEFAULT_TICK_RATE,
+    }
+  }
+}
+
+impl SnapshotBuffer {
+  fn get(&self, tick: u32) -> Option<&Snapshot> {
+    self.snapshots.iter().find(|(t, _)| *t == tick).map(|(_, snapshot)| snapshot)
+  }
+
+  fn newest(&self) -> Option<&Snapshot> {
+    self.snapshots.back().map(|(_, snapshot)| snapshot)
+  }
+
+  // Frames may come out of order, anything older than FRAME_HISTORY ticks behind the newest one is forgotten
+  fn insert(&mut self, tick: u32, snapshot: Snapshot) {
+    let position = self.snapshots.iter().position(|(t, _)| *t > tick).unwrap_or(self.snapshots.len());
+    self.snapshots.insert(position, (tick, snapshot));
+    let newest = self.snapshots.back().unwrap().0;
+    while let Some((oldest, _)) = self.snapshots.front() && newest - oldest >= FRAME_HISTORY {
+      self.snapshots.pop_front();
+    }
+  }
+
+  // Moves the render tick along with the clock, speeding it up or slowing it down a little to keep the delay
+  fn advance(&mut self, delta: f64) -> Option<f64> {
+    let target = self.snapshots.back()?.0 as f64 - INTERPOLATION_DELAY;
+    let render_tick = match self.render_tick {
+      Some(render_tick) if (target - render_tick).abs() < INTERPOLATION_DELAY * 4.0 => {
+        let scale = 1.0 + ((target - render_tick) * 0.05).clamp(-0.2, 0.2);
+        render_tick + delta * self.tick_rate * scale
+      }
+      _ => target,
+    };
+    self.render_tick = Some(render_tick);
+    Some(render_tick)
+  }
+
+  // Where the player is at the given tick, held at the last known position past the newest frame
+  fn sample(&self, render_tick: f64, id: u64) -> Option<(Vec3, PolarRotation)> {
+    let next = self.snapshots.iter().position(|(tick, _)| *tick as f64 > render_tick);
+    let (from, to) = match next {
+      Some(0) => (&self.snapshots[0], None),
+      Some(next) => (&self.snapshots[next - 1], Some(&self.snapshots[next])),
+      None => (self.snapshots.back()?, None),
+    };
+    let from_transform = from.1.get(&id)?;
+    match to.and_then(|(tick, snapshot)| snapshot.get(&id).map(|transform| (*tick, transform))) {
+      Some((to_tick, to_transform)) => {
+        let alpha = ((render_tick - from.0 as f64) / (to_tick - from.0) as f64).clamp(0.0, 1.0);
+        Some(from_transform.lerp(to_transform, alpha as f32))
+      }
+      None => Some((from_transform.translation(), from_transform.rotation())),
+    }
+  }
+}
+
+fn awaited_chunk(game_world: &GameWorld, location: DDD) -> Option<DD> {
+  let chunk_coord = GameWorld::get_chunk_coord(location);
+  (!game_world.chunks.contains_key(&chunk_coord)).then_some(chunk_coord)
+}
+
 pub struct ShikataganaiClientPlugin;
 
 impl Plugin for ShikataganaiClientPlugin {
@@ -68,12 +172,20 @@ impl Plugin for ShikataganaiClientPlugin {
       .run_if(in_game)
       .with_system(send_system)
       .with_system(receive_system)
+      .with_system(interpolate_players)
       .into();
 
     app
       .add_plugin(RenetClientPlugin { clear_events: false })
       .init_resource::<ClientLobby>()
       .init_resource::<NetworkMapping>()
+      .init_resource::<ChunkReassembly>()
+      .init_resource::<PendingChunkEvents>()
+      .init_resource::<ReceivedDefinitions>()
+      .init_resource::<SnapshotBuffer>()
+      .init_resource::<Registries>()
+      .init_resource::<ClientBlocks>()
+      .init_resource::<ClientItems>()
       .add_system(panic_handler)
       .add_system_set(on_game_simulation_continuous);
   }
@@ -158,21 +270,33 @@ fn receive_system(
   mut commands: Commands,
   mut relight: EventWriter<RelightEvent>,
   mut remesh: EventWriter<RemeshEvent>,
-  (mut network_mapping, mut game_world, mut recollide, mut client, mut lobby, mut player_inventory): (
+  (
+    mut network_mapping,
+    mut game_world,
+    mut recollide,
+    mut client,
+    mut lobby,
+    mut player_inventory,
+    mut reassembly,
+    mut pending_events,
+    mut received_definitions,
+    mut extracted_items,
+    (mut registries, mut client_blocks, mut client_items),
+  ): (
     ResMut<NetworkMapping>,
     ResMut<GameWorld>,
     ResMut<Recollide>,
     ResMut<RenetClient>,
     ResMut<ClientLobby>,
     ResMut<PlayerInventory>,
+    ResMut<ChunkReassembly>,
+    ResMut<PendingChunkEvents>,
+    ResMut<ReceivedDefinitions>,
+    ResMut<ExtractedItems>,
+    (ResMut<Registries>, ResMut<ClientBlocks>, ResMut<ClientItems>),
   ),
-  mesh_storage_handle: Res<GltfMeshStorageHandle>,
-  amonger_texture: Res<AmongerTextureHandle>,
-  mesh_storage: Res<Assets<GltfMeshStorage>>,
   player_nickname: Res<PlayerNickname>,
-  time: Res<Time>,
-  mut query_leg_animation: Query<&mut LegAnimationFrame>,
-  query_skeleton: Query<&AmongerSkeleton>,
+  mut snapshots: ResMut<SnapshotBuffer>,
   mut player_entity: Query<Entity, With<Player>>,
   mut fps_camera_query: Query<&mut FPSCamera>,
   mut query: Query<&mut Transform>,
@@ -181,37 +305,131 @@ fn receive_system(
 ) {
   let client_id = client.client_id();
 
-  while let Some(message) = client.receive_message(ServerChannel::GameEvent.id()) {
-    let server_message: ServerMessage = deserialize(&message).unwrap();
+  let mut replay = vec![];
+  while let Some(message) = client.receive_message(ServerChannel::ChunkStream.id()) {
+    let fragment = match deserialize(&message) {
+      Ok(ChunkStreamMessage::Fragment(fragment)) => fragment,
+      Ok(ChunkStreamMessage::Unload { chunk_coord }) => {
+        reassembly.forget(chunk_coord);
+        pending_events.0.remove(&chunk_coord);
+        if let Some(chunk) = game_world.chunks.remove(&chunk_coord) {
+          chunk.foreach(|_, block| {
+            if block.entity != Entity::from_bits(0) {
+              commands.entity(block.entity).despawn_recursive();
+            }
+          });
+          for section in game_world.column_sections(chunk_coord) {
+            remesh.send(RemeshEvent::Remesh(section));
+          }
+          recollide.0 = true;
+        }
+        continue;
+      }
+      Err(error) => {
+        event_writer.send(ConsoleText {
+          text: format!("Unreadable chunk stream message from the server: {}", error),
+          level: Level::ERROR,
+          age: **tick,
+        });
+        continue;
+      }
+    };
+    let fragment_coord = fragment.chunk_coord;
+    let Some(data) = reassembly.push(fragment) else {
+      continue;
+    };
+    let mut decoder = ZlibDecoder::new(data.as_slice());
+    let mut message = Vec::new();
+    let chunk = decoder
+      .read_to_end(&mut message)
+      .map_err(|error| error.to_string())
+      .and_then(|_| deserialize::<Chunk>(&message).map_err(|error| error.to_string()));
+    // Dropped, it is asked for again the next time something needs it
+    let mut chunk = match chunk {
+      Ok(chunk) => chunk,
+      Err(error) => {
+        game_world.remove_from_generating(fragment_coord);
+        event_writer.send(ConsoleText {
+          text: format!("Unreadable chunk from the server: {}", error),
+          level: Level::ERROR,
+          age: **tick,
+        });
+        continue;
+      }
+    };
+    chunk.foreach_mut(|_, block| block.entity = Entity::from_bits(0));
+    let chunk_coord = chunk.coord();
+    let sections = GameWorld::get_section_coord(chunk.bounds.0).1..=GameWorld::get_section_coord(chunk.bounds.1).1;
+    game_world.chunks.insert(chunk_coord, chunk);
+    game_world.remove_from_generating(chunk_coord);
+
+    for i in chunk_coord.0 - 1..=chunk_coord.0 + 1 {
+      for j in chunk_coord.1 - 1..=chunk_coord.1 + 1 {
+        for y in sections.clone() {
+          remesh.send(RemeshEvent::Remesh((i, y, j)));
+        }
+      }
+    }
+    if let Some(events) = pending_events.0.remove(&chunk_coord) {
+      replay.extend(events);
+    }
+  }
+
+  let mut replay = replay.into_iter();
+  loop {
+    let server_message: ServerMessage = match replay.next() {
+      Some(server_message) => server_message,
+      None => match client.receive_message(ServerChannel::GameEvent.id()) {
+        Some(message) => match deserialize(&message) {
+          Ok(server_message) => server_message,
+          // A server of another version can still say why it drops us
+          Err(error) => match read_disconnect_reason(&message) {
+            Some(reason) => ServerMessage::Disconnect { reason },
+            None => {
+              event_writer.send(ConsoleText {
+                text: format!("Unreadable message from the server: {}", error),
+                level: Level::ERROR,
+                age: **tick,
+              });
+              continue;
+            }
+          },
+        },
+        None => break,
+      },
+    };
     event_writer.send(ConsoleText {
       text: format!("{}", &server_message),
       level: Level::DEBUG,
       age: **tick,
     });
     match server_message {
-      ServerMessage::PlayerSpawn {
-        entity,
-        id,
-        translation,
-      } => {
+      ServerMessage::PlayerSpawn { entity, id, .. } => {
         if client_id == id {
           continue;
         }
-        let client_entity = spawn_amonger(
-          &mut commands,
-          mesh_storage.as_ref(),
-          &mesh_storage_handle,
-          translation.0,
-          amonger_texture.as_ref(),
-        );
-        lobby.players.insert(id, PlayerInfo { client_entity });
-        network_mapping.0.insert(entity, client_entity);
+        // The body is spawned once the player shows up in a frame
+        if let Some(PlayerInfo {
+          client_entity: Some(client_entity),
+        }) = lobby.players.insert(id, PlayerInfo { client_entity: None })
+        {
+          commands.entity(client_entity).despawn_recursive();
+        }
+        network_mapping.0.insert(entity, id);
       }
       ServerMessage::PlayerDespawn { id } => {
-        let client_entity = lobby.players.get(&id).unwrap().client_entity;
-        commands.entity(client_entity).despawn_recursive();
+        if let Some(PlayerInfo { client_entity }) = lobby.players.remove(&id) {
+          network_mapping.0.retain(|_, player| *player != id);
+          if let Some(client_entity) = client_entity {
+            commands.entity(client_entity).despawn_recursive();
+          }
+        }
       }
       ServerMessage::BlockRemove { location } => {
+        if let Some(chunk_coord) = awaited_chunk(game_world.as_ref(), location) {
+          pending_events.0.entry(chunk_coord).or_default().push(ServerMessage::BlockRemove { location });
+          continue;
+        }
         game_world.get_mut(location).map(|b| {
           b.block = BlockId::Air;
           if b.entity != Entity::from_bits(0) {
@@ -220,37 +438,31 @@ fn receive_system(
             recollide.0 = true;
           }
         });
-        remesh.send(RemeshEvent::Remesh(GameWorld::get_chunk_coord(location)));
+        remesh.send(RemeshEvent::Remesh(GameWorld::get_section_coord(location)));
       }
       ServerMessage::BlockPlace {
         location,
         block_transfer,
       } => {
+        if let Some(chunk_coord) = awaited_chunk(game_world.as_ref(), location) {
+          pending_events.0.entry(chunk_coord).or_default().push(ServerMessage::BlockPlace {
+            location,
+            block_transfer,
+          });
+          continue;
+        }
         game_world.get_mut(location).map(|b| *b = block_transfer.into());
-        remesh.send(RemeshEvent::Remesh(GameWorld::get_chunk_coord(location)));
+        remesh.send(RemeshEvent::Remesh(GameWorld::get_section_coord(location)));
         recollide.0 = true;
       }
-      ServerMessage::ChunkData { chunk } => {
-        let mut decoder = ZlibDecoder::new(chunk.as_slice());
-        let mut message = Vec::new();
-        decoder.read_to_end(&mut message).unwrap();
-        let mut chunk: Chunk = deserialize(&message).unwrap();
-        chunk.grid.map_in_place(|_, block| Block {
-          entity: Entity::from_bits(0),
-          ..*block
-        });
-        let chunk_coord = GameWorld::get_chunk_coord(chunk.grid.bounds.0);
-        game_world.chunks.insert(chunk_coord, chunk);
-        game_world.remove_from_generating(chunk_coord);
-
-        for i in chunk_coord.0 - 1..=chunk_coord.0 + 1 {
-          for j in chunk_coord.1 - 1..=chunk_coord.1 + 1 {
-            remesh.send(RemeshEvent::Remesh((i, j)));
-          }
-        }
-      }
       ServerMessage::Relight { relights } => {
         for (coord, light) in relights {
+          if let Some(chunk_coord) = awaited_chunk(game_world.as_ref(), coord) {
+            pending_events.0.entry(chunk_coord).or_default().push(ServerMessage::Relight {
+              relights: vec![(coord, light)],
+            });
+            continue;
+          }
           game_world.set_light_level(coord, light);
           relight.send(RelightEvent::Relight(coord));
         }
@@ -260,6 +472,14 @@ fn receive_system(
         functor_type,
         functor,
       } => {
+        if let Some(chunk_coord) = awaited_chunk(game_world.as_ref(), location) {
+          pending_events.0.entry(chunk_coord).or_default().push(ServerMessage::Functor {
+            location,
+            functor_type,
+            functor,
+          });
+          continue;
+        }
         if let Some(block) = game_world.get_mut(location) {
           let mut commands = if block.entity == Entity::from_bits(0) {
             commands.spawn_empty()
@@ -279,14 +499,67 @@ fn receive_system(
       }
       ServerMessage::AuthConfirmed {
         translation: (translation, rotation),
+        inventory,
+        world_height,
+        view_distance,
+        tick_rate,
+        motd,
       } => {
+        let ReceivedDefinitions { items, blocks } = std::mem::take(received_definitions.as_mut());
+        // Nothing changes if this client can't draw what the server plays with
+        let installed = Registries::new(items, blocks).and_then(|installed| {
+          Ok((
+            ClientBlocks::new(&installed.blocks)?,
+            ClientItems::new(&installed.items)?,
+            installed,
+          ))
+        });
+        match installed {
+          Ok((blocks, items, installed)) => {
+            *client_blocks = blocks;
+            *client_items = items;
+            *registries = installed;
+          }
+          Err(error) => {
+            event_writer.send(ConsoleText {
+              text: format!("Can't play on this server: {}", error),
+              level: Level::ERROR,
+              age: **tick,
+            });
+            client.disconnect();
+            break;
+          }
+        }
+        // Icons drawn for another server's items and blocks
+        extracted_items.rendered.0.clear();
+        game_world.height = world_height;
+        // Chunks that overtook the definitions were meshed with the previous blocks
+        for chunk_coord in game_world.chunks.keys() {
+          for section in game_world.column_sections(*chunk_coord) {
+            remesh.send(RemeshEvent::Remesh(section));
+          }
+        }
+        snapshots.tick_rate = tick_rate;
+        event_writer.send(ConsoleText {
+          text: format!("View distance: {} chunks", view_distance),
+          level: Level::INFO,
+          age: **tick,
+        });
+        if !motd.is_empty() {
+          event_writer.send(ConsoleText {
+            text: motd,
+            level: Level::INFO,
+            age: **tick,
+          });
+        }
         let entity = player_entity.single_mut();
         let mut fps_camera = fps_camera_query.single_mut();
         let mut transform = query.get_mut(entity).unwrap();
         commands.entity(entity).insert(player_nickname.as_ref().clone());
         fps_camera.phi = rotation.phi;
         fps_camera.theta = rotation.theta;
-        transform.translation = translation + Vec3::new(0.0, 1.8, 0.0); // TODO: figure this out, player spawns below actual position
+        transform.translation = translation + Vec3::new(0.0, EYE_HEIGHT, 0.0);
+        *player_inventory = inventory;
         recollide.0 = true;
       }
       ServerMessage::AnimationStart { location, animation } => {
@@ -294,83 +567,169 @@ fn receive_system(
           animate(&mut commands, entity, animation);
         }
       }
-      ServerMessage::ItemAdd { item, quant } => {
-        add_item_inventory(player_inventory.as_mut(), item, quant);
+      ServerMessage::PlayerCorrection { translation } => {
+        let entity = player_entity.single_mut();
+        if let Ok(mut transform) = query.get_mut(entity) {
+          transform.translation = translation + Vec3::new(0.0, EYE_HEIGHT, 0.0);
+        }
+        fps_camera_query.single_mut().velocity = Vec3::ZERO;
+        recollide.0 = true;
+      }
+      ServerMessage::Inventory { inventory } => {
+        *player_inventory = inventory;
+      }
+      ServerMessage::Disconnect { reason } => {
+        event_writer.send(ConsoleText {
+          text: format!("Disconnected by the server: {}", reason),
+          level: Level::ERROR,
+          age: **tick,
+        });
+      }
+      ServerMessage::ItemDefinition { definition } => {
+        received_definitions.items.push(definition);
+      }
+      ServerMessage::BlockDefinition { definition } => {
+        received_definitions.blocks.push(definition);
+      }
+      ServerMessage::Chat { sender, message } => {
+        // Players talk at INFO, what the server has to say stands out at WARN
+        let (text, level) = match sender {
+          Some(sender) => (format!("<{}> {}", sender, message), Level::INFO),
+          None => (message, Level::WARN),
+        };
+        event_writer.send(ConsoleText {
+          text,
+          level,
+          age: **tick,
+        });
       }
     }
   }
 
   while let Some(message) = client.receive_message(ServerChannel::GameFrame.id()) {
-    let server_message: NetworkFrame = deserialize(&message).unwrap();
-    for (id, translation) in server_message
-      .entities
-      .players
-      .iter()
-      .zip(server_message.entities.translations.iter())
-    {
-      if client_id == *id {
+    // Not acked, the server keeps encoding against the last frame that was
+    let frame: NetworkFrame = match deserialize(&message) {
+      Ok(frame) => frame,
+      Err(error) => {
+        event_writer.send(ConsoleText {
+          text: format!("Unreadable frame from the server: {}", error),
+          level: Level::ERROR,
+          age: **tick,
+        });
         continue;
       }
-      if let Some(entity) = lobby.players.get(id).map(|e| e.client_entity) {
-        if query.get(entity).is_err() {
-          continue;
-        }
-        let current_location = query.get_mut(entity).unwrap().translation;
-        let leg_animation = query_leg_animation.get_mut(entity).unwrap().into_inner();
+    };
+    if snapshots.get(frame.tick).is_some() {
+      continue;
+    }
+    let baseline = match frame.baseline {
+      Some(baseline) => match snapshots.get(baseline) {
+        Some(baseline) => Some(baseline),
+        None => continue,
+      },
+      None => None,
+    };
+    let Some(snapshot) = frame.decode(baseline) else {
+      continue;
+    };
+    snapshots.insert(frame.tick, snapshot);
+    client.send_message(ClientChannel::FrameAck.id(), serialize(&FrameAck { tick: frame.tick }).unwrap());
+  }
+}
 
-        if current_location.distance(translation.0) <= f32::epsilon() * 10.0 {
-          leg_animation.1 += 1;
-        } else {
-          leg_animation.1 = 0;
-        }
+// Remote players are drawn INTERPOLATION_DELAY ticks behind the newest frame, between the two frames around that tick.
+// Players the newest frame leaves out went out of view, their body is despawned instead of freezing in place.
+fn interpolate_players(
+  mut commands: Commands,
+  time: Res<Time>,
+  mut snapshots: ResMut<SnapshotBuffer>,
+  mut lobby: ResMut<ClientLobby>,
+  mesh_storage_handle: Res<GltfMeshStorageHandle>,
+  amonger_texture: Res<AmongerTextureHandle>,
+  mesh_storage: Res<Assets<GltfMeshStorage>>,
+  mut query_leg_animation: Query<&mut LegAnimationFrame>,
+  query_skeleton: Query<&AmongerSkeleton>,
+  mut query: Query<&mut Transform>,
+) {
+  let Some(render_tick) = snapshots.advance(time.delta_seconds_f64()) else {
+    return;
+  };
+  for (id, info) in lobby.players.iter_mut() {
+    let in_view = snapshots.newest().map_or(false, |snapshot| snapshot.contains_key(id));
+    let Some(translation) = snapshots.sample(render_tick, *id).filter(|_| in_view) else {
+      if let Some(entity) = info.client_entity.take() {
+        commands.entity(entity).despawn_recursive();
+      }
+      continue;
+    };
+    let entity = *info.client_entity.get_or_insert_with(|| {
+      spawn_amonger(
+        &mut commands,
+        mesh_storage.as_ref(),
+        &mesh_storage_handle,
+        translation.0,
+        amonger_texture.as_ref(),
+      )
+    });
+    let Ok(current_location) = query.get(entity).map(|transform| transform.translation) else {
+      continue;
+    };
+    let Ok(mut leg_animation) = query_leg_animation.get_mut(entity) else {
+      continue;
+    };
+    let leg_animation = leg_animation.as_mut();
 
-        if leg_animation.1 > 20 {
-          leg_animation.0 *= 0.5;
-        } else {
-          leg_animation.0 += time.delta().as_secs_f32() * 3.0;
-          if leg_animation.0 >= 2.0 {
-            leg_animation.0 = 0.0;
-          }
+    if current_location.distance(translation.0) <= f32::epsilon() * 10.0 {
+      leg_animation.1 += 1;
+    } else {
+      leg_animation.1 = 0;
+    }
+
+    if leg_animation.1 > 20 {
+      leg_animation.0 *= 0.5;
+    } else {
+      leg_animation.0 += time.delta().as_secs_f32() * 3.0;
+      if leg_animation.0 >= 2.0 {
+        leg_animation.0 = 0.0;
+      }
+    }
+    if let Ok(skeleton) = query_skeleton.get(entity) {
+      let PolarRotation { phi, theta } = translation.1;
+      let _ = query.get_mut(entity).map(|mut transform| {
+        transform.translation = translation.0;
+        transform.rotation = Quat::from_rotation_y(-phi);
+      });
+      let _ = query.get_mut(skeleton.visor).map(|mut transform| {
+        transform.rotation = Quat::from_rotation_z(-theta + f32::FRAC_PI_2());
+      });
+      let _ = query.get_mut(skeleton.legl).map(|mut transform| {
+        if leg_animation.0 > 0.0 && leg_animation.0 <= 0.5 {
+          transform.rotation = Quat::from_rotation_z(leg_animation.0 * f32::PI());
         }
-        if let Ok(skeleton) = query_skeleton.get(entity) {
-          let PolarRotation { phi, theta } = translation.1;
-          let _ = query.get_mut(entity).map(|mut transform| {
-            transform.translation = translation.0;
-            transform.rotation = Quat::from_rotation_y(-phi);
-          });
-          let _ = query.get_mut(skeleton.visor).map(|mut transform| {
-            transform.rotation = Quat::from_rotation_z(-theta + f32::FRAC_PI_2());
-          });
-          let _ = query.get_mut(skeleton.legl).map(|mut transform| {
-            if leg_animation.0 > 0.0 && leg_animation.0 <= 0.5 {
-              transform.rotation = Quat::from_rotation_z(leg_animation.0 * f32::PI());
-            }
-            if leg_animation.0 > 0.5 && leg_animation.0 <= 1.0 {
-              transform.rotation = Quat::from_rotation_z((1.0 - leg_animation.0) * f32::PI());
-            }
-            if leg_animation.0 > 1.0 && leg_animation.0 <= 1.5 {
-              transform.rotation = Quat::from_rotation_z(-(leg_animation.0 - 1.0) * f32::PI());
-            }
-            if leg_animation.0 > 1.5 && leg_animation.0 <= 2.0 {
-              transform.rotation = Quat::from_rotation_z(-(2.0 - leg_animation.0) * f32::PI());
-            }
-          });
-          let _ = query.get_mut(skeleton.legr).map(|mut transform| {
-            if leg_animation.0 > 0.0 && leg_animation.0 <= 0.5 {
-              transform.rotation = Quat::from_rotation_z(-leg_animation.0 * f32::PI());
-            }
-            if leg_animation.0 > 0.5 && leg_animation.0 <= 1.0 {
-              transform.rotation = Quat::from_rotation_z(-(1.0 - leg_animation.0) * f32::PI());
-            }
-            if leg_animation.0 > 1.0 && leg_animation.0 <= 1.5 {
-              transform.rotation = Quat::from_rotation_z((leg_animation.0 - 1.0) * f32::PI());
-            }
-            if leg_animation.0 > 1.5 && leg_animation.0 <= 2.0 {
-              transform.rotation = Quat::from_rotation_z((2.0 - leg_animation.0) * f32::PI());
-            }
-          });
+        if leg_animation.0 > 0.5 && leg_animation.0 <= 1.0 {
+          transform.rotation = Quat::from_rotation_z((1.0 - leg_animation.0) * f32::PI());
         }
-      }
+        if leg_animation.0 > 1.0 && leg_animation.0 <= 1.5 {
+          transform.rotation = Quat::from_rotation_z(-(leg_animation.0 - 1.0) * f32::PI());
+        }
+        if leg_animation.0 > 1.5 && leg_animation.0 <= 2.0 {
+          transform.rotation = Quat::from_rotation_z(-(2.0 - leg_animation.0) * f32::PI());
+        }
+      });
+      let _ = query.get_mut(skeleton.legr).map(|mut transform| {
+        if leg_animation.0 > 0.0 && leg_animation.0 <= 0.5 {
+          transform.rotation = Quat::from_rotation_z(-leg_animation.0 * f32::PI());
+        }
+        if leg_animation.0 > 0.5 && leg_animation.0 <= 1.0 {
+          transform.rotation = Quat::from_rotation_z(-(1.0 - leg_animation.0) * f32::PI());
+        }
+        if leg_animation.0 > 1.0 && leg_animation.0 <= 1.5 {
+          transform.rotation = Quat::from_rotation_z((leg_animation.0 - 1.0) * f32::PI());
+        }
+        if leg_animation.0 > 1.5 && leg_animation.0 <= 2.0 {
+          transform.rotation = Quat::from_rotation_z((2.0 - leg_animation.0) * f32::PI());
+        }
+      });
     }
   }
 }
@@ -385,7 +744,7 @@ fn send_system(
       ClientChannel::ClientCommand.id(),
       serialize(&PlayerCommand::PlayerMove {
         translation: (
-          Vec3::new(translation.x, translation.y - 1.5, translation.z),
+          Vec3::new(translation.x, translation.y - EYE_HEIGHT, translation.z),
           PolarRotation {
             phi: rotation.phi,
             theta: rotation.theta,
@@ -397,25 +756,36 @@ fn send_system(
   }
 }
 
-pub fn spawn_client(commands: &mut Commands, _player_entity: Entity, address: String, nickname: String) {
+pub fn spawn_client(
+  commands: &mut Commands,
+  _player_entity: Entity,
+  address: String,
+  nickname: String,
+  password: Option<String>,
+) {
   let server_addr = address.parse().unwrap();
   let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
   let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
-  let client_id = current_time.as_millis() as u64;
-  commands.insert_resource(PlayerNickname(nickname));
 
-  let client = RenetClient::new(
-    current_time,
-    socket,
-    client_connection_config(),
-    ClientAuthentication::Unsecure {
+  // Servers in secure mode hand out tokens, one is picked up when it sits next to the client named after the nickname
+  let token_path = format!("{}.token", nickname);
+  let authentication = match File::open(&token_path).and_then(|mut file| ConnectToken::read(&mut file)) {
+    Ok(connect_token) => {
+      println!("Connecting with {}", token_path);
+      ClientAuthentication::Secure { connect_token }
+    }
+    Err(_) => ClientAuthentication::Unsecure {
       protocol_id: PROTOCOL_ID,
-      client_id,
+      client_id: rand::random(),
       server_addr,
       user_data: None,
     },
-  )
-  .unwrap();
+  };
+  commands.insert_resource(PlayerNickname(nickname));
+  commands.insert_resource(PlayerPassword(password));
+  commands.insert_resource(ReceivedDefinitions::default());
+
+  let client = RenetClient::new(current_time, socket, client_connection_config(), authentication).unwrap();
 
   commands.insert_resource(client);
 }
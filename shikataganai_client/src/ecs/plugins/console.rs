@@ -1,11 +1,18 @@
 use crate::ecs::plugins::game::{in_game, LocalTick};
 use crate::App;
 use bevy::app::Plugin;
+use bevy::input::InputSystem;
 use bevy::prelude::*;
 use bevy::window::CursorGrabMode;
 use bevy_egui::EguiContext;
+use bevy_renet::renet::RenetClient;
+use bincode::serialize;
+use egui::text::CCursor;
+use egui::text_edit::{CCursorRange, TextEditState};
 use egui::{Color32, Frame};
 use iyes_loopless::prelude::ConditionSet;
+use shikataganai_common::networking::{ClientChannel, PlayerCommand, MAX_CHAT_LENGTH};
+use std::collections::BTreeMap;
 use tracing::Level;
 
 pub struct ConsolePlugin;
@@ -22,8 +29,40 @@ impl Plugin for ConsolePlugin {
       .add_event::<ConsoleText>()
       .init_resource::<ConsoleMenuOpened>()
       .init_resource::<ConsoleTextVec>()
+      .init_resource::<ConsoleFilter>()
+      .init_resource::<ConsoleInput>()
+      .add_console_command(ConsoleCommand {
+        name: "help",
+        args: &[("command", ArgKind::Word)],
+        optional: 1,
+        help: "List the commands, or explain one of them",
+        run: help_command,
+      })
+      .add_console_command(ConsoleCommand {
+        name: "clear",
+        args: &[],
+        optional: 0,
+        help: "Empty the console",
+        run: |world, _| {
+          world.resource_mut::<ConsoleTextVec>().clear();
+          Ok(String::new())
+        },
+      })
+      .add_console_command(ConsoleCommand {
+        name: "filter",
+        args: &[("level", ArgKind::Word)],
+        optional: 0,
+        help: "Hide the lines more verbose than error, warn, info, debug or trace",
+        run: |world, args| {
+          let level = args[0].word().parse::<Level>().map_err(|_| format!("Unknown level {}", args[0].word()))?;
+          world.resource_mut::<ConsoleFilter>().0 = level;
+          Ok(format!("Showing {} and up", level))
+        },
+      })
+      .add_system_to_stage(CoreStage::PreUpdate, swallow_keys.after(InputSystem))
       .add_system_set_to_stage(CoreStage::Last, on_game_simulation_last)
-      .add_system_set(on_game_simulation_continuous);
+      .add_system_set(on_game_simulation_continuous)
+      .add_system(run_console_lines);
   }
 }
 
@@ -40,10 +79,277 @@ pub struct ConsoleTextVec(pub Vec<ConsoleText>);
 #[derive(Default, Resource)]
 pub struct ConsoleMenuOpened(pub bool);
 
+// Lines more verbose than this are hidden
+#[derive(Resource)]
+pub struct ConsoleFilter(pub Level);
+
+impl Default for ConsoleFilter {
+  fn default() -> Self {
+    Self(Level::INFO)
+  }
+}
+
+// Lines kept for the up and down keys
+pub const HISTORY_LENGTH: usize = 100;
+
+#[derive(Default, Resource)]
+pub struct ConsoleInput {
+  // What has been typed so far
+  pub text: String,
+  pub history: Vec<String>,
+  // Position in the history while going through it
+  browsing: Option<usize>,
+  // Entered lines waiting for run_console_lines
+  submitted: Vec<String>,
+}
+
+#[derive(Copy, Clone)]
+pub enum ArgKind {
+  Bool,
+  Int,
+  Float,
+  Word,
+  // Everything left of the line
+  Text,
+}
+
+#[derive(Clone)]
+pub enum ConsoleArg {
+  Bool(bool),
+  Int(i64),
+  Float(f32),
+  Word(String),
+}
+
+impl ConsoleArg {
+  // The parser has checked the kind, so these only go wrong when a command asks for another kind than it declares
+  pub fn bool(&self) -> bool {
+    match self {
+      ConsoleArg::Bool(value) => *value,
+      _ => panic!("Not a bool argument"),
+    }
+  }
+
+  pub fn int(&self) -> i64 {
+    match self {
+      ConsoleArg::Int(value) => *value,
+      _ => panic!("Not an int argument"),
+    }
+  }
+
+  pub fn float(&self) -> f32 {
+    match self {
+      ConsoleArg::Float(value) => *value,
+      ConsoleArg::Int(value) => *value as f32,
+      _ => panic!("Not a float argument"),
+    }
+  }
+
+  pub fn word(&self) -> &str {
+    match self {
+      ConsoleArg::Word(value) => value,
+      _ => panic!("Not a word argument"),
+    }
+  }
+}
+
+// The answer is logged at INFO, the error at ERROR
+pub type ConsoleRun = fn(&mut World, &[ConsoleArg]) -> Result<String, String>;
+
+pub struct ConsoleCommand {
+  pub name: &'static str,
+  // Name and kind of every argument, the way they are shown in the usage
+  pub args: &'static [(&'static str, ArgKind)],
+  // How many of the last arguments may be left out
+  pub optional: usize,
+  pub help: &'static str,
+  pub run: ConsoleRun,
+}
+
+impl ConsoleCommand {
+  pub fn usage(&self) -> String {
+    let required = self.args.len() - self.optional;
+    self
+      .args
+      .iter()
+      .enumerate()
+      .fold(format!("/{}", self.name), |usage, (i, (name, kind))| {
+        let name = match kind {
+          ArgKind::Text => format!("{}...", name),
+          _ => name.to_string(),
+        };
+        if i < required {
+          format!("{} <{}>", usage, name)
+        } else {
+          format!("{} [{}]", usage, name)
+        }
+      })
+  }
+
+  pub fn parse(&self, line: &str) -> Result<Vec<ConsoleArg>, String> {
+    let mut words = line.split_whitespace();
+    let mut args = vec![];
+    for (name, kind) in self.args {
+      if let ArgKind::Text = kind {
+        let text = words.by_ref().collect::<Vec<_>>().join(" ");
+        if !text.is_empty() {
+          args.push(ConsoleArg::Word(text));
+        }
+        break;
+      }
+      let Some(word) = words.next() else {
+        break;
+      };
+      let invalid = || format!("Invalid {} {}, usage: {}", name, word, self.usage());
+      args.push(match kind {
+        ArgKind::Bool => ConsoleArg::Bool(match word {
+          "1" | "true" | "on" => true,
+          "0" | "false" | "off" => false,
+          _ => return Err(invalid()),
+        }),
+        ArgKind::Int => ConsoleArg::Int(word.parse().map_err(|_| invalid())?),
+        ArgKind::Float => ConsoleArg::Float(word.parse().map_err(|_| invalid())?),
+        ArgKind::Word | ArgKind::Text => ConsoleArg::Word(word.to_string()),
+      });
+    }
+    if args.len() < self.args.len() - self.optional {
+      return Err(format!("Missing {}, usage: {}", self.args[args.len()].0, self.usage()));
+    }
+    if let Some(word) = words.next() {
+      return Err(format!("Unexpected {}, usage: {}", word, self.usage()));
+    }
+    Ok(args)
+  }
+}
+
+// Commands typed with a leading slash. Anything else typed into the console is chat, and slash-commands nobody has
+// registered are left to the server.
+#[derive(Default, Resource)]
+pub struct ConsoleCommands {
+  pub commands: BTreeMap<&'static str, ConsoleCommand>,
+}
+
+pub trait AddConsoleCommand {
+  fn add_console_command(&mut self, command: ConsoleCommand) -> &mut Self;
+}
+
+impl AddConsoleCommand for App {
+  fn add_console_command(&mut self, command: ConsoleCommand) -> &mut Self {
+    self.init_resource::<ConsoleCommands>();
+    self
+      .world
+      .resource_mut::<ConsoleCommands>()
+      .commands
+      .insert(command.name, command);
+    self
+  }
+}
+
+impl ConsoleCommands {
+  // The longest line every command starting with what has been typed agrees on, along with the candidates
+  pub fn complete(&self, text: &str) -> (String, Vec<&'static str>) {
+    let Some(typed) = text.strip_prefix('/') else {
+      return (text.to_string(), vec![]);
+    };
+    if typed.contains(char::is_whitespace) {
+      return self.complete_argument(text);
+    }
+    let candidates: Vec<&'static str> = self
+      .commands
+      .keys()
+      .copied()
+      .filter(|name| name.starts_with(typed))
+      .collect();
+    let completed = match candidates.as_slice() {
+      [] => text.to_string(),
+      [name] => format!("/{} ", name),
+      [first, rest @ ..] => {
+        let common = rest.iter().fold(first.len(), |common, name| {
+          first
+            .chars()
+            .zip(name.chars())
+            .take(common)
+            .take_while(|(a, b)| a == b)
+            .count()
+        });
+        format!("/{}", &first[..common])
+      }
+    };
+    (completed, candidates)
+  }
+
+  // Arguments of the kinds with a known set of values, currently on and off for bools
+  fn complete_argument(&self, text: &str) -> (String, Vec<&'static str>) {
+    let mut words: Vec<&str> = text[1..].split_whitespace().collect();
+    let typing = !text.ends_with(char::is_whitespace);
+    let last = if typing { words.pop().unwrap_or("") } else { "" };
+    let Some(command) = words.first().and_then(|name| self.commands.get(name)) else {
+      return (text.to_string(), vec![]);
+    };
+    let Some((_, ArgKind::Bool)) = command.args.get(words.len() - 1) else {
+      return (text.to_string(), vec![]);
+    };
+    let candidates: Vec<&'static str> = ["on", "off"].into_iter().filter(|value| value.starts_with(last)).collect();
+    match candidates.as_slice() {
+      [value] => (format!("/{} {} ", words.join(" "), value), candidates),
+      _ => (text.to_string(), candidates),
+    }
+  }
+}
+
+fn help_command(world: &mut World, args: &[ConsoleArg]) -> Result<String, String> {
+  let commands = world.resource::<ConsoleCommands>();
+  match args.first() {
+    Some(name) => {
+      let name = name.word().trim_start_matches('/');
+      let command = commands
+        .commands
+        .get(name)
+        .ok_or_else(|| format!("No command {} here, try /{} on the server", name, name))?;
+      Ok(format!("{}\n{}", command.usage(), command.help))
+    }
+    None => Ok(
+      commands
+        .commands
+        .values()
+        .map(|command| format!("{}  {}", command.usage(), command.help))
+        .chain(std::iter::once("Other slash-commands go to the server".to_string()))
+        .collect::<Vec<_>>()
+        .join("\n"),
+    ),
+  }
+}
+
+const LEVELS: [Level; 5] = [Level::ERROR, Level::WARN, Level::INFO, Level::DEBUG, Level::TRACE];
+
+fn level_color(level: Level) -> Color32 {
+  match level {
+    Level::ERROR => Color32::RED,
+    Level::INFO => Color32::GRAY,
+    Level::WARN => Color32::YELLOW,
+    Level::TRACE => Color32::YELLOW,
+    Level::DEBUG => Color32::YELLOW,
+  }
+}
+
 pub fn commit_log_lines(mut events: EventReader<ConsoleText>, mut lines: ResMut<ConsoleTextVec>) {
   lines.extend(events.iter().cloned());
 }
 
+// Typing into the console shouldn't open the inventory or switch hot bar slots as well
+pub fn swallow_keys(console_opened: Res<ConsoleMenuOpened>, mut keys: ResMut<Input<KeyCode>>) {
+  if console_opened.0 {
+    let pressed: Vec<KeyCode> = keys
+      .get_just_pressed()
+      .filter(|key| **key != KeyCode::Grave)
+      .copied()
+      .collect();
+    for key in pressed {
+      keys.reset(key);
+    }
+  }
+}
+
 pub fn open_close_console(
   mut windows: ResMut<Windows>,
   key: Res<Input<KeyCode>>,
@@ -61,178 +367,174 @@ pub fn open_close_console(
   }
 }
 
+// Runs what has been entered into the console. Commands get the whole world, so that any plugin can register them.
+pub fn run_console_lines(world: &mut World) {
+  let Some(mut input) = world.get_resource_mut::<ConsoleInput>() else {
+    return;
+  };
+  let lines = std::mem::take(&mut input.submitted);
+  if lines.is_empty() {
+    return;
+  }
+  let age = world.resource::<LocalTick>().0;
+  for line in lines {
+    let command = line.strip_prefix('/').and_then(|command_line| {
+      let name = command_line.split_whitespace().next().unwrap_or("");
+      let command = world.resource::<ConsoleCommands>().commands.get(name)?;
+      Some((command.run, command.parse(&command_line[name.len()..])))
+    });
+    let Some((run, args)) = command else {
+      if let Some(mut client) = world.get_resource_mut::<RenetClient>() {
+        client.send_message(
+          ClientChannel::ClientCommand.id(),
+          serialize(&PlayerCommand::Chat { message: line }).unwrap(),
+        );
+      }
+      continue;
+    };
+    world.resource_mut::<Events<ConsoleText>>().send(ConsoleText {
+      text: format!("> {}", line),
+      level: Level::INFO,
+      age,
+    });
+    let (text, level) = match args.and_then(|args| run(world, &args)) {
+      Ok(answer) => (answer, Level::INFO),
+      Err(error) => (error, Level::ERROR),
+    };
+    if !text.is_empty() {
+      world
+        .resource_mut::<Events<ConsoleText>>()
+        .send(ConsoleText { text, level, age });
+    }
+  }
+}
+
+// Closed, the console only shows the latest lines. Open, it holds the whole log and a line to type commands and chat
+// into.
 pub fn debug_console(
   mut egui: ResMut<EguiContext>,
   window: ResMut<Windows>,
   items: ResMut<ConsoleTextVec>,
   tick: Res<LocalTick>,
+  console_opened: Res<ConsoleMenuOpened>,
+  mut filter: ResMut<ConsoleFilter>,
+  mut input: ResMut<ConsoleInput>,
+  commands: Res<ConsoleCommands>,
+  mut log: EventWriter<ConsoleText>,
 ) {
   let ui = egui.ctx_mut();
   let active_window = window.get_primary().unwrap();
-  // if !debug_console_opened.0 {
-  egui::Window::new("Console")
-    .frame(Frame::none())
+  if !console_opened.0 {
+    egui::Window::new("Console")
+      .frame(Frame::none())
+      .title_bar(false)
+      .resizable(false)
+      .fixed_pos([0.0, 0.0])
+      .fixed_size([active_window.width(), 100.0])
+      .show(ui, |ui| {
+        for item in items
+          .iter()
+          .filter(|item| item.age + 400 > tick.0 && item.level <= filter.0)
+        {
+          ui.label(egui::WidgetText::RichText(
+            egui::RichText::new(&item.text).color(level_color(item.level)),
+          ));
+        }
+      });
+    return;
+  }
+  egui::Window::new("Console Log")
     .title_bar(false)
     .resizable(false)
     .fixed_pos([0.0, 0.0])
-    .fixed_size([active_window.width(), 100.0])
+    .fixed_size([active_window.width(), 300.0])
     .show(ui, |ui| {
-      for item in items.iter().filter(|item| item.age + 400 > tick.0) {
-        const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
-        const GREEN: [f32; 4] = [0.0, 1.0, 0.0, 1.0];
-        const YELLOW: [f32; 4] = [1.0, 1.0, 0.0, 1.0];
-
-        let color = match item.level {
-          Level::ERROR => Color32::RED,
-          Level::INFO => Color32::GRAY,
-          Level::WARN => Color32::YELLOW,
-          Level::TRACE => Color32::YELLOW,
-          Level::DEBUG => Color32::YELLOW,
+      egui::ScrollArea::vertical()
+        .max_height(260.0)
+        .auto_shrink([false, false])
+        .stick_to_bottom(true)
+        .show(ui, |ui| {
+          for item in items.iter().filter(|item| item.level <= filter.0) {
+            ui.label(egui::WidgetText::RichText(
+              egui::RichText::new(&item.text).color(level_color(item.level)),
+            ));
+          }
+        });
+      ui.horizontal(|ui| {
+        egui::ComboBox::from_id_source("Console Filter")
+          .selected_text(filter.0.to_string())
+          .show_ui(ui, |ui| {
+            for level in LEVELS {
+              ui.selectable_value(&mut filter.0, level, level.to_string());
+            }
+          });
+        // The key that opens the console would end up in the input otherwise
+        input.text.retain(|c| c != '`');
+        let response = ui.add(
+          egui::TextEdit::singleline(&mut input.text)
+            .char_limit(MAX_CHAT_LENGTH)
+            .hint_text("Say something, or /help")
+            .desired_width(f32::INFINITY)
+            .lock_focus(true),
+        );
+        let (enter, tab, up, down) = {
+          let keys = ui.input();
+          (
+            keys.key_pressed(egui::Key::Enter),
+            keys.key_pressed(egui::Key::Tab),
+            keys.key_pressed(egui::Key::ArrowUp),
+            keys.key_pressed(egui::Key::ArrowDown),
+          )
         };
-        ui.label(egui::WidgetText::RichText(egui::RichText::new(&item.text).color(color)));
-      }
+        let submitted = response.lost_focus() && enter;
+        let mut moved = false;
+        if submitted {
+          let line = std::mem::take(&mut input.text);
+          let line = line.trim();
+          if !line.is_empty() {
+            if input.history.last().map(String::as_str) != Some(line) {
+              input.history.push(line.to_string());
+              if input.history.len() > HISTORY_LENGTH {
+                input.history.remove(0);
+              }
+            }
+            input.submitted.push(line.to_string());
+          }
+          input.browsing = None;
+        } else if response.has_focus() && tab {
+          let (completed, candidates) = commands.complete(&input.text);
+          if candidates.len() > 1 {
+            log.send(ConsoleText {
+              text: candidates.join("  "),
+              level: Level::INFO,
+              age: tick.0,
+            });
+          }
+          input.text = completed;
+          moved = true;
+        } else if response.has_focus() && (up || down) && !input.history.is_empty() {
+          let last = input.history.len() - 1;
+          input.browsing = match (input.browsing, up) {
+            (None, true) => Some(last),
+            (Some(i), true) => Some(i.saturating_sub(1)),
+            (Some(i), false) if i < last => Some(i + 1),
+            _ => None,
+          };
+          input.text = input
+            .browsing
+            .map(|i| input.history[i].clone())
+            .unwrap_or_default();
+          moved = true;
+        }
+        // Whatever replaced the text, the cursor goes after it
+        if moved && let Some(mut state) = TextEditState::load(ui.ctx(), response.id) {
+          let end = CCursor::new(input.text.chars().count());
+          state.set_ccursor_range(Some(CCursorRange::one(end)));
+          state.store(ui.ctx(), response.id);
+        }
+        if submitted || console_opened.is_changed() {
+          response.request_focus();
+        }
+      });
     });
-  // }
-  //   imgui::Window::new("Debug Console Messages")
-  //     .position([0.0, 0.0], Condition::Always)
-  //     .size([active_window.width(), active_window.height()], Condition::Always)
-  //     .movable(false)
-  //     .collapsible(false)
-  //     .resizable(false)
-  //     .title_bar(false)
-  //     .focus_on_appearing(false)
-  //     .focused(false)
-  //     .draw_background(false)
-  //     .build(ui, || {
-  //       ui.set_scroll_y(9999.0);
-  //       for item in items.iter().filter(|item| item.age + 1000 > tick.0) {
-  //         const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
-  //         const GREEN: [f32; 4] = [0.0, 1.0, 0.0, 1.0];
-  //         const YELLOW: [f32; 4] = [1.0, 1.0, 0.0, 1.0];
-  //
-  //         let token = match item.level {
-  //           Level::ERROR => ui.push_style_color(StyleColor::Text, RED),
-  //           Level::INFO => ui.push_style_color(StyleColor::Text, GREEN),
-  //           Level::WARN => ui.push_style_color(StyleColor::Text, YELLOW),
-  //           Level::TRACE => ui.push_style_color(StyleColor::Text, YELLOW),
-  //           Level::DEBUG => ui.push_style_color(StyleColor::Text, YELLOW),
-  //         };
-  //         ui.text(&item.text);
-  //         token.pop();
-  //       }
-  //     })
-  //     .unwrap();
-  //   return;
-  // }
-  //
-  // fn add_log(items: &mut Vec<ConsoleText>, log: &str, m_level: Level, tick: &LocalTick) {
-  //   let ct = ConsoleText {
-  //     text: log.to_string(),
-  //     level: m_level,
-  //     age: **tick,
-  //   };
-  //   items.push(ct);
-  // }
-  // imgui::Window::new("Debug Console")
-  //   .position([0.0, 0.0], Condition::Always)
-  //   .size([active_window.width(), 300.0], Condition::Always)
-  //   .movable(false)
-  //   .collapsible(false)
-  //   .resizable(false)
-  //   .title_bar(false)
-  //   .focus_on_appearing(true)
-  //   .build(ui, || {
-  //     let _f = ui.push_font(big_font.0);
-  //     {
-  //       let cwindow = imgui::ChildWindow::new(0)
-  //         .border(false)
-  //         .draw_background(true)
-  //         .size([active_window.width(), 250.0])
-  //         .begin(ui);
-  //       if cwindow.is_some() {
-  //         ui.set_scroll_y(9999.0); // TODO: lock/unlock autoscroll with manual override
-  //         for item in items.iter() {
-  //           const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
-  //           const GREEN: [f32; 4] = [0.0, 1.0, 0.0, 1.0];
-  //           const YELLOW: [f32; 4] = [1.0, 1.0, 0.0, 1.0];
-  //
-  //           let token = match item.level {
-  //             Level::ERROR => ui.push_style_color(StyleColor::Text, RED),
-  //             Level::INFO => ui.push_style_color(StyleColor::Text, GREEN),
-  //             Level::WARN => ui.push_style_color(StyleColor::Text, YELLOW),
-  //             Level::TRACE => ui.push_style_color(StyleColor::Text, YELLOW),
-  //             Level::DEBUG => ui.push_style_color(StyleColor::Text, YELLOW),
-  //           };
-  //           ui.text(&item.text);
-  //           token.pop();
-  //         }
-  //       }
-  //     }
-  //     let mut buff0 = String::with_capacity(32);
-  //     let input_width = ui.push_item_width(active_window.width() - 10.0);
-  //     if ui.is_window_focused() && !ui.is_any_item_focused() && !ui.is_mouse_clicked(imgui::MouseButton::Left) {
-  //       ui.set_keyboard_focus_here();
-  //     }
-  //     if ui
-  //       .input_text("##", &mut buff0)
-  //       .enter_returns_true(true)
-  //       .auto_select_all(true)
-  //       .allow_tab_input(true)
-  //       .hint("input commands")
-  //       .build()
-  //     {
-  //       fn get_bool_command(command: &str) -> bool {
-  //         command == "1"
-  //       }
-  //       #[inline]
-  //       fn get_float_command(command: &str) -> f32 {
-  //         command.parse::<f32>().unwrap_or(0.0)
-  //       }
-  //       let command = buff0.split_whitespace().collect::<Vec<&str>>();
-  //       match command.first() {
-  //         Some(..) => match command[0] {
-  //           "clear" => {
-  //             items.clear();
-  //           }
-  //           _ => match command.get(1) {
-  //             Some(..) => match command[0] {
-  //               "noclip" => {
-  //                 // let cd = get_bool_command(command[1]);
-  //                 add_log(
-  //                   &mut items,
-  //                   std::format!("noclip {}", command[1]).as_str(),
-  //                   Level::INFO,
-  //                   &tick,
-  //                 );
-  //               }
-  //               "player_speed" => {
-  //                 let cd = get_float_command(command[1]);
-  //                 if cd == 0.0 {
-  //                   return;
-  //                 }
-  //                 add_log(
-  //                   &mut items,
-  //                   std::format!("Changed player speed to: {}", command[1]).as_str(),
-  //                   Level::INFO,
-  //                   &tick,
-  //                 );
-  //               }
-  //               _ => {
-  //                 add_log(
-  //                   &mut items,
-  //                   std::format!("Error: Couldn't find the command '{}'", command[0]).as_str(),
-  //                   Level::ERROR,
-  //                   &tick,
-  //                 );
-  //               }
-  //             },
-  //             None => return,
-  //           },
-  //         },
-  //         None => return,
-  //       }
-  //     }
-  //     input_width.pop(ui);
-  //   })
-  //   .unwrap();
 }
@@ -1,7 +1,8 @@
 use crate::ecs::components::blocks::{animate, AnimationInstance, AnimationTrait, ChestAnimations, Skeleton};
 use crate::ecs::plugins::camera::{Player, SelectionRes};
-use crate::ecs::resources::player::{PlayerInventory, SelectedHotBar};
-use crate::ecs::resources::world::ClientGameWorld;
+use crate::ecs::plugins::client::PlayerPassword;
+use crate::ecs::plugins::settings::ViewDistance;
+use crate::ecs::resources::player::SelectedHotBar;
 use crate::ecs::systems::input::{action_input, hot_bar_scroll_input, keyboard_input};
 use crate::ecs::systems::light::religh_system;
 use crate::ecs::systems::remesh::remesh_system_auxiliary;
@@ -23,9 +24,9 @@ use bincode::serialize;
 use iyes_loopless::prelude::*;
 use shikataganai_common::ecs::components::blocks::animation::AnimationType;
 use shikataganai_common::ecs::components::blocks::ReverseLocation;
-use shikataganai_common::ecs::resources::player::PlayerNickname;
+use shikataganai_common::ecs::resources::player::{PlayerInventory, PlayerNickname};
 use shikataganai_common::ecs::resources::world::GameWorld;
-use shikataganai_common::networking::{ClientChannel, PlayerCommand};
+use shikataganai_common::networking::{ClientChannel, PlayerCommand, PROTOCOL_VERSION};
 use std::time::Duration;
 
 pub struct GamePlugin;
@@ -82,16 +83,20 @@ pub fn transition_to_simulation(
   mut commands: Commands,
   mut window: ResMut<Windows>,
   mut physics_system: ResMut<RapierConfiguration>,
-  mut game_world: ResMut<GameWorld>,
   mut client: ResMut<RenetClient>,
   nickname: Res<PlayerNickname>,
+  password: Res<PlayerPassword>,
+  view_distance: Res<ViewDistance>,
 ) {
   let active_window = window.get_primary_mut().unwrap();
   if client.is_connected() {
     client.send_message(
       ClientChannel::ClientCommand.id(),
       serialize(&PlayerCommand::PlayerAuth {
+        version: PROTOCOL_VERSION,
         nickname: nickname.0.clone(),
+        password: password.0.clone(),
+        view_distance: view_distance.0,
       })
       .unwrap(),
     );
@@ -99,11 +104,6 @@ pub fn transition_to_simulation(
     active_window.set_cursor_grab_mode(CursorGrabMode::Locked);
     active_window.set_cursor_visibility(false);
     physics_system.physics_pipeline_active = true;
-    for i in -5..=5 {
-      for j in -5..=5 {
-        game_world.get_chunk_or_request((i, j), client.as_mut());
-      }
-    }
   }
 }
 
@@ -169,6 +169,13 @@ pub fn interface_input(
         })
         .unwrap(),
       );
+      client.send_message(
+        ClientChannel::ClientCommand.id(),
+        serialize(&PlayerCommand::CloseFunctor {
+          location: reverse_location.get(inventory_opened.0).unwrap().0,
+        })
+        .unwrap(),
+      );
     }
     if player_inventory_opened.is_some() {
       commands.remove_resource::<PlayerInventoryOpened>();
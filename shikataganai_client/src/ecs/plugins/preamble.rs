@@ -1,4 +1,6 @@
-use crate::ecs::plugins::settings::{AmbientOcclusion, FullScreen, MouseSensitivity, RecentConnections, Resolution, Settings, VSync};
+use crate::ecs::plugins::settings::{
+  AmbientOcclusion, FullScreen, MouseSensitivity, RecentConnections, Resolution, Settings, VSync, ViewDistance,
+};
 use bevy::app::AppExit;
 use bevy::prelude::*;
 use bevy::winit::WinitWindows;
@@ -29,6 +31,7 @@ fn exit(
   vsync: Res<VSync>,
   fullscreen: Res<FullScreen>,
   ambient_occlusion: Res<AmbientOcclusion>,
+  view_distance: Res<ViewDistance>,
   recent_connections: Res<RecentConnections>,
   client: Option<ResMut<RenetClient>>,
 ) {
@@ -48,6 +51,7 @@ fn exit(
       vsync: vsync.0,
       fullscreen: fullscreen.0,
       ambient_occlusion: ambient_occlusion.0,
+      view_distance: view_distance.0,
       recent_connections: recent_connections.0.clone()
     })
     .unwrap();
@@ -1,3 +1,5 @@
+use crate::ecs::components::blocks::ClientBlocks;
+use crate::ecs::components::items::ClientItems;
 use crate::ecs::plugins::game::{in_game, in_game_extract};
 // use crate::ecs::plugins::imgui::{IMGUI_PASS, TEXTURE_NODE_INPUT_SLOT};
 use crate::ecs::plugins::rendering::inventory_pipeline::inventory_cache::ExtractedItems;
@@ -92,6 +94,8 @@ impl Plugin for InventoryRendererPlugin {
     let i = egui.add_image(handle.clone());
     app.world.insert_resource(InventoryTextureOutputHandle(handle, i));
     app.add_plugin(ExtractResourcePlugin::<InventoryTextureOutputHandle>::default());
+    app.add_plugin(ExtractResourcePlugin::<ClientBlocks>::default());
+    app.add_plugin(ExtractResourcePlugin::<ClientItems>::default());
 
     if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
       let inventory_node = {
@@ -1,5 +1,5 @@
-use crate::ecs::components::blocks::{BlockRenderInfo, BlockSprite, DerefExt};
-use crate::ecs::components::items::ItemDerefExt;
+use crate::ecs::components::blocks::{BlockRenderInfo, BlockSprite, ClientBlocks};
+use crate::ecs::components::items::ClientItems;
 use crate::ecs::plugins::rendering::inventory_pipeline::inventory_cache::{ItemRenderEntry, ItemRenderMap};
 use crate::ecs::plugins::rendering::inventory_pipeline::pipeline::InventoryNode;
 use crate::ecs::plugins::rendering::inventory_pipeline::{
@@ -111,6 +111,8 @@ impl Node for InventoryNode {
       let mut meshes_to_render = vec![];
 
       let to_render = world.resource::<ItemRenderMap>();
+      let client_blocks = world.resource::<ClientBlocks>();
+      let client_items = world.resource::<ClientItems>();
       const RADIUS: f32 = 0.49;
       let mut vertex_buffer = vec![];
       let mut rendered_item_icons = HashMap::new();
@@ -146,7 +148,7 @@ impl Node for InventoryNode {
       for (item, ItemRenderEntry { coord: (x, y), .. } ) in to_render.iter() {
         match item {
           BlockOrItem::Block(blockid) => {
-            match blockid.deref_ext().render_info() {
+            match client_blocks.block(*blockid).render_info() {
               BlockRenderInfo::AsBlock(block_sprite) => {
                 add_block_to_vertices(&mut vertex_buffer, block_sprite, x, y);
               }
@@ -164,7 +166,7 @@ impl Node for InventoryNode {
             }
           }
           BlockOrItem::Item(item) => {
-            let sprite = item.deref_ext().render_info().into_uv();
+            let sprite = client_items.item(*item).render_info().into_uv();
             vertex_buffer.extend_from_slice(&[x * INVENTORY_OUTPUT_TEXTURE_WIDTH - 0.0, y * INVENTORY_OUTPUT_TEXTURE_WIDTH - 0.0, sprite.0[0], sprite.0[1], -1.0]);
             vertex_buffer.extend_from_slice(&[x * INVENTORY_OUTPUT_TEXTURE_WIDTH - 0.0, y * INVENTORY_OUTPUT_TEXTURE_WIDTH + 1.0, sprite.0[0], sprite.1[1], -1.0]);
             vertex_buffer.extend_from_slice(&[x * INVENTORY_OUTPUT_TEXTURE_WIDTH + 1.0, y * INVENTORY_OUTPUT_TEXTURE_WIDTH - 0.0, sprite.1[0], sprite.0[1], -1.0]);
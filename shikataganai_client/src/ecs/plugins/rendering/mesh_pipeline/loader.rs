@@ -12,7 +12,7 @@ use gltf::buffer::Source;
 use gltf::Gltf;
 use std::path::Path;
 use strum::IntoEnumIterator;
-use strum_macros::EnumIter;
+use strum_macros::{EnumIter, EnumString};
 
 #[derive(Default)]
 pub struct GltfLoaderII;
@@ -172,7 +172,8 @@ impl AssetLoader for GltfLoaderII {
   }
 }
 
-#[derive(EnumIter, Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[derive(EnumIter, EnumString, Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[strum(serialize_all = "snake_case")]
 pub enum Meshes {
   Stair,
   ChestBase,
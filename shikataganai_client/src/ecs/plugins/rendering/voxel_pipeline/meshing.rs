@@ -3,12 +3,15 @@ use crate::ecs::plugins::rendering::voxel_pipeline::consts::{Vertex, VERTEX};
 use bevy::prelude::*;
 use bevy::render::render_resource::Buffer;
 use bytemuck_derive::{Pod, Zeroable};
+use shikataganai_common::ecs::components::blocks::registry::BlockRegistry;
+use shikataganai_common::ecs::components::blocks::BlockTrait;
 use shikataganai_common::ecs::resources::world::GameWorld;
-use shikataganai_common::util::array::{add_ddd, DD, DDD};
+use shikataganai_common::util::array::{add_ddd, DDD};
 
 #[allow(dead_code)]
 pub enum RemeshEvent {
-  Remesh(DD),
+  // Section coordinate, see GameWorld::get_section_coord
+  Remesh(DDD),
   Dummy,
 }
 
@@ -22,13 +25,14 @@ pub struct SingleVertex {
   pub uv: [f32; 2],
   pub tile_side: [i32; 4],
   pub meta: [u8; 4],
+  pub tint: [u8; 4],
 }
 
 #[repr(C)]
 #[derive(Copy, Clone, Pod, Zeroable)]
 pub struct SingleSide([SingleVertex; 6]);
 
-fn occluded(neighbours: &GameWorld, c: DDD, vx: f32, vy: f32, vz: f32, sx: i32, sy: i32, sz: i32) -> u8 {
+fn occluded(neighbours: &GameWorld, blocks: &BlockRegistry, c: DDD, vx: f32, vy: f32, vz: f32, sx: i32, sy: i32, sz: i32) -> u8 {
   let edgex = ((vx * 2.0) - 1.0).round() as i32;
   let edgey = ((vy * 2.0) - 1.0).round() as i32;
   let edgez = ((vz * 2.0) - 1.0).round() as i32;
@@ -43,13 +47,13 @@ fn occluded(neighbours: &GameWorld, c: DDD, vx: f32, vy: f32, vz: f32, sx: i32,
 
   let left = neighbours
     .get(add_ddd(left, c))
-    .map_or(0, |x| if x.visible() { 1 } else { 0 });
+    .map_or(0, |x| if blocks.block(x.block).visible() { 1 } else { 0 });
   let center = neighbours
     .get(add_ddd(center, c))
-    .map_or(0, |x| if x.visible() { 1 } else { 0 });
+    .map_or(0, |x| if blocks.block(x.block).visible() { 1 } else { 0 });
   let right = neighbours
     .get(add_ddd(right, c))
-    .map_or(0, |x| if x.visible() { 1 } else { 0 });
+    .map_or(0, |x| if blocks.block(x.block).visible() { 1 } else { 0 });
 
   let result = left + center + right;
   if result == 2 && center == 0 {
@@ -85,7 +89,9 @@ impl SingleSide {
     (ix, iy, iz): (i32, i32, i32),
     block: [BlockSprite; 6],
     lighting: (u8, u8),
+    grass_tint: [f32; 3],
     neighbours: &GameWorld,
+    blocks: &BlockRegistry,
     ambient_occlusion: bool,
   ) -> Self {
     let fx = x;
@@ -93,6 +99,12 @@ impl SingleSide {
     let fz = z;
     let side = delta_to_side((ix, iy, iz));
     let mut triangles = VERTEX[side];
+    // Only the grass sprite is tinted, the dirt on the sides of a grass block keeps its colour
+    let tint = if block[side] == BlockSprite::Grass {
+      grass_tint.map(|c| (c.clamp(0.0, 1.0) * 255.0) as u8)
+    } else {
+      [255; 3]
+    };
     let make_face = |triangles: [Vertex; 6]| {
       SingleSide(triangles.map(
         |Vertex {
@@ -111,6 +123,7 @@ impl SingleSide {
             if ambient_occlusion {
               occluded(
                 neighbours,
+                blocks,
                 (x.round() as i32, y.round() as i32, z.round() as i32),
                 vx,
                 vy,
@@ -124,6 +137,7 @@ impl SingleSide {
             },
             0,
           ],
+          tint: [tint[0], tint[1], tint[2], 255],
         },
       ))
     };
@@ -31,6 +31,7 @@ impl SpecializedRenderPipeline for VoxelPipeline {
       VertexFormat::Float32x2,
       VertexFormat::Sint32x4,
       VertexFormat::Uint8x4,
+      VertexFormat::Unorm8x4,
     ];
 
     let vertex_layout = VertexBufferLayout::from_vertex_formats(VertexStepMode::Vertex, vertex_formats);
@@ -1,4 +1,4 @@
-use crate::ecs::components::blocks::{BlockRenderInfo, BlockSprite, DerefExt};
+use crate::ecs::components::blocks::{BlockRenderInfo, BlockSprite, ClientBlocks};
 use crate::ecs::components::OverlayRender;
 use crate::ecs::plugins::camera::{Selection, SelectionRes};
 use crate::ecs::plugins::rendering::voxel_pipeline::bind_groups::{
@@ -20,16 +20,17 @@ use bevy::render::view::ViewUniforms;
 use bevy::render::Extract;
 use bevy::utils::hashbrown::HashMap;
 use itertools::Itertools;
-use shikataganai_common::ecs::components::blocks::{Block, ReverseLocation};
+use shikataganai_common::ecs::components::blocks::{Block, BlockTrait, ReverseLocation};
+use shikataganai_common::ecs::resources::registries::Registries;
 use shikataganai_common::ecs::resources::world::GameWorld;
-use shikataganai_common::util::array::{sub_ddd, ArrayIndex, ImmediateNeighbours, DD};
+use shikataganai_common::util::array::{sub_ddd, ArrayIndex, ImmediateNeighbours, DDD};
 use std::ops::Deref;
 use wgpu::util::BufferInitDescriptor;
 use wgpu::{BindGroupDescriptor, BindGroupEntry, BindingResource};
 
 #[derive(Resource)]
 pub struct ExtractedBlocks {
-  pub blocks: HashMap<DD, BufferVec<SingleSide>>,
+  pub blocks: HashMap<DDD, BufferVec<SingleSide>>,
 }
 
 #[derive(Resource)]
@@ -43,6 +44,11 @@ impl Default for ExtractedBlocks {
   }
 }
 
+// Grass takes the colour of the biome it grows in
+fn grass_tint(game_world: &GameWorld, c: DDD) -> [f32; 3] {
+  game_world.get_biome(c).map_or([1.0; 3], |biome| biome.grass_tint)
+}
+
 pub fn extract_chunks(
   mut commands: Commands,
   game_world: Extract<Res<GameWorld>>,
@@ -52,7 +58,10 @@ pub fn extract_chunks(
   mut extracted_blocks: ResMut<ExtractedBlocks>,
   mut overlay_buffer: ResMut<OverlayBuffer>,
   overlay_query: Extract<Query<(&ReverseLocation, &OverlayRender)>>,
+  registries: Extract<Res<Registries>>,
+  client_blocks: Extract<Res<ClientBlocks>>,
 ) {
+  let blocks = registries.blocks.as_ref();
   commands.insert_resource(selection.clone());
   let mut updated: UpdatedVec = UpdatedVec(vec![]);
 
@@ -66,7 +75,9 @@ pub fn extract_chunks(
           delta,
           overlay.overlays,
           (16, 16),
+          grass_tint(&game_world, location.0),
           &game_world,
+          blocks,
           ambient_occlusion.0,
         ));
       }
@@ -78,7 +89,9 @@ pub fn extract_chunks(
     .filter_map(|p| if let RemeshEvent::Remesh(d) = p { Some(d) } else { None })
     .unique()
   {
-    if !game_world.chunks.contains_key(ch) {
+    // Sections that turned into air lose their buffer altogether
+    if game_world.get_section(*ch).is_none() {
+      extracted_blocks.blocks.remove(ch);
       continue;
     }
     updated.push(*ch);
@@ -86,16 +99,16 @@ pub fn extract_chunks(
       .blocks
       .insert(*ch, BufferVec::new(BufferUsages::VERTEX));
     let extracted_blocks = extracted_blocks.blocks.get_mut(ch).unwrap();
-    let bounds = game_world.chunks[ch].grid.bounds;
+    let bounds = GameWorld::section_bounds(*ch);
     let mut i = bounds.0;
     loop {
       let block: Block = *game_world.get(i).unwrap();
-      match block.deref_ext().render_info() {
+      match client_blocks.block(block.block).render_info() {
         BlockRenderInfo::Nothing => {}
         BlockRenderInfo::AsBlock(block_sprites) => {
-          if block.visible() {
+          if blocks.block(block.block).visible() {
             for neighbour in i.immediate_neighbours() {
-              if game_world.get(neighbour).map_or(true, |b| !b.visible()) {
+              if game_world.get(neighbour).map_or(true, |b| !blocks.block(b.block).visible()) {
                 let light_level = game_world.get_light_level(neighbour);
                 let lighting = match light_level {
                   Some(light_level) => (light_level.heaven, light_level.hearth),
@@ -107,7 +120,9 @@ pub fn extract_chunks(
                   sub_ddd(neighbour, i),
                   block_sprites,
                   lighting,
+                  grass_tint(&game_world, i),
                   &game_world,
+                  blocks,
                   ambient_occlusion.0,
                 ));
               }
@@ -127,7 +142,7 @@ pub fn extract_chunks(
 }
 
 #[derive(Default, Deref, DerefMut, Resource)]
-pub struct UpdatedVec(pub Vec<DD>);
+pub struct UpdatedVec(pub Vec<DDD>);
 
 pub fn queue_chunks(
   mut commands: Commands,
@@ -18,9 +18,15 @@ pub struct Settings {
   pub vsync: bool,
   pub fullscreen: bool,
   pub ambient_occlusion: bool,
+  #[serde(default = "default_view_distance")]
+  pub view_distance: u32,
   pub recent_connections: Vec<(String, String)>
 }
 
+fn default_view_distance() -> u32 {
+  6
+}
+
 impl Default for Settings {
   fn default() -> Self {
     Self {
@@ -30,6 +36,7 @@ impl Default for Settings {
       vsync: true,
       fullscreen: false,
       ambient_occlusion: true,
+      view_distance: default_view_distance(),
       recent_connections: vec![]
     }
   }
@@ -50,6 +57,9 @@ pub struct VSync(pub bool);
 pub struct FullScreen(pub bool);
 #[derive(Resource)]
 pub struct AmbientOcclusion(pub bool);
+// Asked for when connecting, the server may grant less
+#[derive(Resource)]
+pub struct ViewDistance(pub u32);
 
 impl VSync {
   pub fn as_present_mode(&self) -> PresentMode {
@@ -89,6 +99,7 @@ impl Plugin for SettingsPlugin {
     app.insert_resource(VSync(toml.vsync));
     app.insert_resource(FullScreen(toml.fullscreen));
     app.insert_resource(AmbientOcclusion(toml.ambient_occlusion));
+    app.insert_resource(ViewDistance(toml.view_distance));
     app.insert_resource(RecentConnections(toml.recent_connections));
   }
 }
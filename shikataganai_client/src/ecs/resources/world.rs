@@ -44,14 +44,14 @@ impl ClientGameWorld for GameWorld {
     let chunk_coord = GameWorld::get_chunk_coord(coord);
     self
       .get_chunk_or_request(chunk_coord, client)
-      .map(|chunk| &chunk.grid[coord])
+      .and_then(|chunk| chunk.get(coord))
   }
 
   fn get_block_or_request_mut(&mut self, coord: DDD, client: &mut RenetClient) -> Option<&mut Block> {
     let chunk_coord = GameWorld::get_chunk_coord(coord);
     self
       .get_chunk_or_request_mut(chunk_coord, client)
-      .map(|chunk| &mut chunk.grid[coord])
+      .and_then(|chunk| chunk.get_mut(coord))
   }
 }
 
@@ -1,7 +1,7 @@
-use crate::ecs::components::blocks::DerefExt;
+use crate::ecs::components::blocks::ClientBlocks;
 use crate::ecs::plugins::camera::{FPSCamera, Recollide, Selection, SelectionRes};
 use crate::ecs::plugins::game::ShikataganaiGameState;
-use crate::ecs::resources::player::{PlayerInventory, SelectedHotBar};
+use crate::ecs::resources::player::SelectedHotBar;
 use crate::ecs::systems::user_interface::player_inventory::PlayerInventoryOpened;
 use bevy::input::keyboard::KeyboardInput;
 use bevy::input::mouse::MouseWheel;
@@ -12,17 +12,16 @@ use bevy_rapier3d::prelude::{Collider, InteractionGroups, RapierContext};
 use bevy_rapier3d::rapier::prelude::Group;
 use bevy_renet::renet::RenetClient;
 use bincode::serialize;
-use itertools::Itertools;
 use iyes_loopless::prelude::NextState;
 use num_traits::FloatConst;
 use shikataganai_common::ecs::components::blocks::block_id::BlockId;
 use shikataganai_common::ecs::components::blocks::{Block, BlockOrItem, BlockRotation, QuantifiedBlockOrItem};
-use shikataganai_common::ecs::components::item::ItemId;
 use shikataganai_common::ecs::resources::light::{LightLevel, RelightEvent};
+use shikataganai_common::ecs::resources::player::PlayerInventory;
+use shikataganai_common::ecs::resources::registries::Registries;
 use shikataganai_common::ecs::resources::world::GameWorld;
 use shikataganai_common::networking::{ClientChannel, PlayerCommand};
 use shikataganai_common::util::array::DDD;
-use std::cmp::Ordering;
 use std::ops::Deref;
 
 fn place_item_from_inventory(
@@ -87,112 +86,27 @@ fn place_item_from_inventory(
   }
 }
 
-pub fn add_item_inventory(player_inventory: &mut PlayerInventory, item_id: ItemId, quant: u32) -> Option<()> {
-  match player_inventory
-    .items
-    .iter_mut()
-    .filter(|slot| {
-      slot
-        .as_ref()
-        .map(|item| item.block_or_item == BlockOrItem::Item(item_id))
-        .unwrap_or(true)
-    })
-    .sorted_by(|slot1, slot2| {
-      if let Some(slot1) = slot1 && let Some(_slot2) = slot2 {
-        if slot1.block_or_item == BlockOrItem::Item(item_id) {
-          Ordering::Greater
-        } else {
-          Ordering::Less
-        }
-      } else if slot1.is_some() {
-        Ordering::Less
-      } else {
-        Ordering::Greater
-      }
-    })
-    .next()
-    .map(|slot| {
-      slot.get_or_insert(QuantifiedBlockOrItem {
-        block_or_item: BlockOrItem::Item(item_id),
-        quant: 0,
-      })
-    })
-    .map(|slot| {
-      slot.quant += quant;
-      slot.quant == 1
-    }) {
-    None => {
-      return None;
-    }
-    Some(true) => {}
-    _ => {}
-  }
-
-  Some(())
-}
-
 fn pick_up_block(
   commands: &mut Commands,
   player_inventory: &mut PlayerInventory,
   coord: DDD,
   game_world: &mut GameWorld,
+  registries: &Registries,
 ) -> Option<()> {
-  if let Some(source_block) = game_world.get_mut(coord) {
-    let block: BlockId = std::mem::replace(&mut source_block.block, BlockId::Air);
-
-    // Check for the first slot in inventory containing required block or the next empty slot to insert that block into.
-    match player_inventory
-      .items
-      .iter_mut()
-      .filter(|slot| {
-        slot
-          .as_ref()
-          .map(|item| item.block_or_item == BlockOrItem::Block(block))
-          .unwrap_or(true)
-      })
-      .sorted_by(|slot1, slot2| {
-        if let Some(slot1) = slot1 && let Some(_slot2) = slot2 {
-          if slot1.block_or_item == BlockOrItem::Block(block) {
-            Ordering::Greater
-          } else {
-            Ordering::Less
-          }
-        } else if slot1.is_some() {
-          Ordering::Less
-        } else {
-          Ordering::Greater
-        }
-      })
-      .next()
-      .map(|slot| {
-        slot.get_or_insert(QuantifiedBlockOrItem {
-          block_or_item: BlockOrItem::Block(block),
-          quant: 0,
-        })
-      })
-      .map(|slot| {
-        slot.quant += 1;
-        slot.quant == 1
-      }) {
-      None => {
-        // No empty or correct slots found, return block back into place
-        let _ = std::mem::replace(&mut source_block.block, block);
-        return None;
-      }
-      Some(true) => {
-        // Pick up, do the rerender event
-      }
-      _ => {}
-    }
-
-    if source_block.entity != Entity::from_bits(0) {
-      commands.entity(source_block.entity).despawn_recursive();
-      source_block.entity = Entity::from_bits(0);
-    }
-    Some(())
-  } else {
-    None
+  let source_block = game_world.get_mut(coord)?;
+  // Predict what the server will do, it answers with the authoritative inventory either way
+  if source_block.block == BlockId::Air {
+    return None;
+  }
+  if let Some(drop) = registries.block(source_block.block).drops() && !player_inventory.add(registries, drop, 1) {
+    return None;
   }
+  source_block.block = BlockId::Air;
+  if source_block.entity != Entity::from_bits(0) {
+    commands.entity(source_block.entity).despawn_recursive();
+    source_block.entity = Entity::from_bits(0);
+  }
+  Some(())
 }
 
 pub fn keyboard_input(
@@ -230,6 +144,8 @@ pub fn action_input(
   rapier_context: Res<RapierContext>,
   mut recollide: ResMut<Recollide>,
   mut client: ResMut<RenetClient>,
+  registries: Res<Registries>,
+  client_blocks: Res<ClientBlocks>,
 ) {
   match selection.into_inner().deref() {
     None => {}
@@ -237,7 +153,7 @@ pub fn action_input(
       let source: DDD = *cube;
       let target_negative = *face;
       if mouse.just_pressed(MouseButton::Left) {
-        if let Some(()) = pick_up_block(&mut commands, player_inventory.as_mut(), source, &mut game_world) {
+        if let Some(()) = pick_up_block(&mut commands, player_inventory.as_mut(), source, &mut game_world, &registries) {
           client.send_message(
             ClientChannel::ClientCommand.id(),
             serialize(&PlayerCommand::BlockRemove { location: source }).unwrap(),
@@ -251,8 +167,8 @@ pub fn action_input(
         && game_world
           .get(source)
           .and_then(|block| {
-            block
-              .deref_ext()
+            client_blocks
+              .block(block.block)
               .right_click_interface(block.entity, source, &mut commands, &mut client)
           })
           .is_none()
@@ -272,6 +188,7 @@ pub fn action_input(
             serialize(&PlayerCommand::BlockPlace {
               location: target_negative,
               block_transfer: block.into(),
+              slot: hotbar_selection.0 as usize,
             })
             .unwrap(),
           );
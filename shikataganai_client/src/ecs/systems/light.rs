@@ -2,20 +2,22 @@ use crate::ecs::plugins::rendering::voxel_pipeline::meshing::RemeshEvent;
 use bevy::prelude::*;
 use itertools::Itertools;
 use shikataganai_common::ecs::resources::light::{relight_helper, RelightEvent};
+use shikataganai_common::ecs::resources::registries::Registries;
 use shikataganai_common::ecs::resources::world::GameWorld;
-use shikataganai_common::util::array::FlatFullNeighbours;
+use shikataganai_common::util::array::FullNeighbours;
 
 pub fn religh_system(
   mut relight: EventReader<RelightEvent>,
   mut remesh: EventWriter<RemeshEvent>,
   mut game_world: ResMut<GameWorld>,
+  registries: Res<Registries>,
 ) {
-  for coord in relight_helper(&mut relight, game_world.as_mut()).iter() {
+  for coord in relight_helper(&mut relight, game_world.as_mut(), &registries.blocks).iter() {
     coord
-      .flat_full_neighbours()
-      .map(GameWorld::get_chunk_coord)
+      .full_neighbours()
+      .map(GameWorld::get_section_coord)
       .unique()
-      .for_each(|chunk_coord| remesh.send(RemeshEvent::Remesh(chunk_coord)));
+      .for_each(|section| remesh.send(RemeshEvent::Remesh(section)));
   }
 }
 //
@@ -1,5 +1,4 @@
-use crate::ecs::components::blocks::DerefExt;
-use crate::ecs::components::blocks::{BlockRenderInfo, Skeleton};
+use crate::ecs::components::blocks::{BlockRenderInfo, ClientBlocks, Skeleton};
 use crate::ecs::plugins::rendering::mesh_pipeline::loader::GltfMeshStorageHandle;
 use crate::ecs::plugins::rendering::mesh_pipeline::systems::MeshMarker;
 use crate::ecs::plugins::rendering::voxel_pipeline::meshing::RemeshEvent;
@@ -9,6 +8,7 @@ use bevy::utils::hashbrown::HashMap;
 use itertools::Itertools;
 use num_traits::FloatConst;
 use shikataganai_common::ecs::components::blocks::ReverseLocation;
+use shikataganai_common::ecs::resources::registries::Registries;
 use shikataganai_common::ecs::resources::world::GameWorld;
 use shikataganai_common::util::array::{from_ddd, ArrayIndex};
 
@@ -21,20 +21,23 @@ pub fn remesh_system_auxiliary(
   mut remesh_events: EventReader<RemeshEvent>,
   storage: Res<GltfMeshStorageHandle>,
   mesh_storage_assets: Res<Assets<GltfMeshStorage>>,
+  registries: Res<Registries>,
+  client_blocks: Res<ClientBlocks>,
 ) {
   for ch in remesh_events
     .iter()
     .filter_map(|p| if let RemeshEvent::Remesh(d) = p { Some(d) } else { None })
     .unique()
   {
-    if !game_world.chunks.contains_key(ch) {
+    // Sections of pure air have nothing that needs an entity
+    if game_world.get_section(*ch).is_none() {
       continue;
     }
-    let bounds = game_world.chunks.get(ch).unwrap().grid.bounds;
+    let bounds = GameWorld::section_bounds(*ch);
     let mut i = bounds.0;
     loop {
       let mut block = game_world.get_mut(i).unwrap();
-      if block.need_reverse_location() {
+      if registries.block(block.block).need_reverse_location() {
         block.entity = if block.entity == Entity::from_bits(0) {
           commands.spawn_empty()
         } else {
@@ -43,7 +46,7 @@ pub fn remesh_system_auxiliary(
         .insert(ReverseLocation(i))
         .id();
       }
-      match block.deref_ext().render_info() {
+      match client_blocks.block(block.block).render_info() {
         BlockRenderInfo::AsMesh(mesh) => {
           if block.entity == Entity::from_bits(0) {
             if let Some(mesh_assets_hash_map) = mesh_storage_assets.get(&storage.0) {
@@ -1,10 +1,11 @@
 use crate::ecs::plugins::rendering::inventory_pipeline::inventory_cache::ExtractedItems;
 use crate::ecs::plugins::rendering::inventory_pipeline::InventoryTextureOutputHandle;
-use crate::ecs::resources::player::{PlayerInventory, SelectedHotBar};
+use crate::ecs::resources::player::SelectedHotBar;
 use bevy::prelude::*;
 use bevy_egui::EguiContext;
 use egui::{Align, Color32, Layout, TextStyle, Widget};
 use shikataganai_common::ecs::components::blocks::QuantifiedBlockOrItem;
+use shikataganai_common::ecs::resources::player::PlayerInventory;
 
 pub fn hot_bar(
   mut egui: ResMut<EguiContext>,
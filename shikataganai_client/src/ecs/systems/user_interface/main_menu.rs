@@ -1,19 +1,39 @@
 use crate::ecs::plugins::camera::Player;
 use crate::ecs::plugins::client::spawn_client;
 use crate::ecs::plugins::game::ShikataganaiGameState;
-use crate::ecs::plugins::settings::{AmbientOcclusion, FullScreen, MouseSensitivity, RecentConnections, Resolution, VSync};
+use crate::ecs::plugins::settings::{
+  AmbientOcclusion, FullScreen, MouseSensitivity, RecentConnections, Resolution, VSync, ViewDistance,
+};
 use bevy::app::AppExit;
 use bevy::prelude::*;
 use bevy_egui::*;
 use egui::{Align, emath, Layout, Widget};
 use iyes_loopless::state::NextState;
-use shikataganai_server::ecs::plugins::server::ShikataganaiServerAddress;
-use shikataganai_server::spawn_server;
+use shikataganai_common::networking::MAX_VIEW_DISTANCE;
+use shikataganai_server::config::ServerSettings;
+use shikataganai_server::ecs::systems::save::ShutdownSignal;
+use shikataganai_server::{spawn_server, WorldOptions};
 use std::ops::{DerefMut, RangeInclusive};
 
 #[derive(Default)]
 pub struct LocalString<const T: &'static str>(pub String);
 
+// Runs a server with the default settings next to the client
+fn start_local_server(address: &str) {
+  let settings = match address.parse() {
+    Ok(address) => ServerSettings { address, ..default() },
+    Err(_) => {
+      println!("Invalid address {}, expected <IP>:<PORT>", address);
+      return;
+    }
+  };
+  std::thread::spawn(move || {
+    if let Err(error) = spawn_server(settings, WorldOptions::default(), ShutdownSignal::default()) {
+      println!("{}", error);
+    }
+  });
+}
+
 pub fn main_menu(
   mut commands: Commands,
   mut egui: ResMut<EguiContext>,
@@ -26,8 +46,10 @@ pub fn main_menu(
   mut fullscreen: ResMut<FullScreen>,
   mut recent_connections: ResMut<RecentConnections>,
   mut ambient_occlusion: ResMut<AmbientOcclusion>,
+  mut view_distance: ResMut<ViewDistance>,
   mut address_string: Local<LocalString<"IP">>,
   mut nickname_string: Local<LocalString<"Nickname">>,
+  mut password_string: Local<LocalString<"Password">>,
   player_entity: Query<Entity, With<Player>>,
 ) {
   const MAIN_MENU_SIZE : (f32, f32) = (500.0, 400.0);
@@ -46,12 +68,13 @@ pub fn main_menu(
     } else {
       nickname_string.0.clone()
     };
+    let password = (!password_string.0.is_empty()).then(|| password_string.0.clone());
     if ui.button("Connect").clicked() {
       if !recent_connections.0.contains(&(address.clone(), nickname.clone())) {
         recent_connections.0.push((address.clone(), nickname.clone()));
       }
       commands.insert_resource(NextState(ShikataganaiGameState::PreSimulation));
-      spawn_client(&mut commands, player_entity, address.clone(), nickname);
+      spawn_client(&mut commands, player_entity, address.clone(), nickname, password.clone());
     }
 
     let mut to_delete = vec![];
@@ -59,15 +82,12 @@ pub fn main_menu(
       ui.with_layout(Layout::left_to_right(Align::Min), |ui| {
         if egui::widgets::Link::new(format!("{} @ {}", nick, ip)).ui(ui).clicked() {
           commands.insert_resource(NextState(ShikataganaiGameState::PreSimulation));
-          spawn_client(&mut commands, player_entity, ip.clone(), nick.clone());
+          spawn_client(&mut commands, player_entity, ip.clone(), nick.clone(), password.clone());
         }
         if egui::Link::new("Local Server").ui(ui).clicked() {
-          let address = ip.clone();
-          std::thread::spawn(|| {
-            spawn_server(ShikataganaiServerAddress { address });
-          });
+          start_local_server(ip);
           commands.insert_resource(NextState(ShikataganaiGameState::PreSimulation));
-          spawn_client(&mut commands, player_entity, ip.clone(), nick.clone());
+          spawn_client(&mut commands, player_entity, ip.clone(), nick.clone(), password.clone());
         }
         if egui::Link::new("Delete").ui(ui).clicked() {
           to_delete.push(i);
@@ -84,11 +104,14 @@ pub fn main_menu(
       .desired_width(MAIN_MENU_SIZE.0)
       .hint_text("Player")
       .show(ui);
+    egui::TextEdit::singleline(&mut password_string.deref_mut().0)
+      .desired_width(MAIN_MENU_SIZE.0)
+      .hint_text("Password (optional)")
+      .password(true)
+      .show(ui);
 
     if ui.button("Start Server").clicked() {
-      std::thread::spawn(|| {
-        spawn_server(ShikataganaiServerAddress { address });
-      });
+      start_local_server(&address);
     }
     if ui.button("Settings").clicked() {
       *settings_menu_opened = true;
@@ -140,6 +163,9 @@ pub fn main_menu(
       window.get_primary_mut().unwrap().set_mode(fullscreen.as_mode());
     }
     ui.checkbox(&mut ambient_occlusion.as_mut().0, "Ambient Occlusion");
+    egui::Slider::new(&mut view_distance.as_mut().0, RangeInclusive::new(1, MAX_VIEW_DISTANCE))
+      .text("View Distance")
+      .ui(ui);
     if ui.button("Close").clicked() {
       *settings_menu_opened = false;
     }
@@ -3,7 +3,8 @@ use crate::ecs::plugins::rendering::inventory_pipeline::InventoryTextureOutputHa
 use bevy::prelude::{Res, ResMut, Windows, Resource, Entity};
 use bevy_egui::EguiContext;
 use egui::{Color32, LayerId, Response, Sense, TextStyle, Ui, Widget};
-use shikataganai_common::ecs::components::blocks::QuantifiedBlockOrItem;
+use shikataganai_common::ecs::components::blocks::{BlockOrItem, QuantifiedBlockOrItem};
+use shikataganai_common::ecs::resources::registries::Registries;
 use std::ops::Range;
 
 pub mod chest_inventory;
@@ -28,6 +29,7 @@ pub fn item_button(
   content: Option<&QuantifiedBlockOrItem>,
   extracted_items: &mut ExtractedItems,
   inventory_texture: &InventoryTextureOutputHandle,
+  registries: &Registries,
 ) -> Response {
   let mut response = match content {
     None => egui::ImageButton::new(inventory_texture.1, [95.0, 95.0])
@@ -57,6 +59,13 @@ pub fn item_button(
       .inner
     }
   };
+  if let Some(QuantifiedBlockOrItem { block_or_item, .. }) = content {
+    let mut text = registries.display_name(*block_or_item).to_string();
+    if let BlockOrItem::Item(item) = block_or_item && !registries.item(*item).tooltip().is_empty() {
+      text = format!("{}\n{}", text, registries.item(*item).tooltip());
+    }
+    response = response.on_hover_text(text);
+  }
   if response.drag_started() {
     response.clicked[0] = true;
   }
@@ -71,6 +80,7 @@ fn item_button_grid<'a, F>(
   grid_width: usize,
   extracted_items: &mut ExtractedItems,
   inventory_texture: &InventoryTextureOutputHandle,
+  registries: &Registries,
 ) -> Option<usize>
 where
   F: Fn(usize) -> Option<&'a QuantifiedBlockOrItem>,
@@ -79,7 +89,7 @@ where
   egui::Grid::new(id).show(ui, |ui| {
     for celli in cell_range {
       let item = content_fetch(celli);
-      if item_button(ui, item, extracted_items, inventory_texture).clicked() {
+      if item_button(ui, item, extracted_items, inventory_texture, registries).clicked() {
         clicked = Some(celli)
       }
       if celli % grid_width == grid_width - 1 {
@@ -1,11 +1,15 @@
+use crate::ecs::plugins::client::send_message;
 use crate::ecs::plugins::rendering::inventory_pipeline::inventory_cache::ExtractedItems;
 use crate::ecs::plugins::rendering::inventory_pipeline::InventoryTextureOutputHandle;
-use crate::ecs::resources::player::PlayerInventory;
 use crate::ecs::systems::user_interface::{InventoryItemMovementStatus, item_button_grid};
 use bevy::prelude::*;
 use bevy_egui::EguiContext;
+use bevy_renet::renet::RenetClient;
 use egui::{emath, Context, Id, Ui, Widget};
 use shikataganai_common::ecs::components::blocks::QuantifiedBlockOrItem;
+use shikataganai_common::ecs::resources::player::PlayerInventory;
+use shikataganai_common::ecs::resources::registries::Registries;
+use shikataganai_common::networking::PlayerCommand;
 
 #[derive(Resource)]
 pub struct PlayerInventoryOpened;
@@ -16,6 +20,7 @@ pub fn render_player_inventory(
   extracted_items: &mut ExtractedItems,
   inventory_texture: &InventoryTextureOutputHandle,
   item_move: &InventoryItemMovementStatus,
+  registries: &Registries,
   range_start: usize
 ) -> Option<usize> {
   let content_fetch = |x| {
@@ -33,6 +38,7 @@ pub fn render_player_inventory(
     player_inventory.hot_bar_width,
     extracted_items,
     inventory_texture,
+    registries,
   );
   ui.separator();
   clicked = clicked.or(item_button_grid(
@@ -43,6 +49,7 @@ pub fn render_player_inventory(
     player_inventory.hot_bar_width,
     extracted_items,
     inventory_texture,
+    registries,
   ));
   clicked
 }
@@ -55,6 +62,8 @@ pub fn player_inventory(
   mut extracted_items: ResMut<ExtractedItems>,
   inventory_texture: Res<InventoryTextureOutputHandle>,
   mut item_move: ResMut<InventoryItemMovementStatus>,
+  mut client: ResMut<RenetClient>,
+  registries: Res<Registries>,
 ) {
   if let Some(_) = inventory_opened {
     let active_window = window.get_primary().unwrap();
@@ -94,6 +103,7 @@ pub fn player_inventory(
           extracted_items.as_mut(),
           inventory_texture.as_ref(),
           item_move.as_ref(),
+          registries.as_ref(),
           0
         );
         if let Some(clicked) = clicked {
@@ -110,7 +120,8 @@ pub fn player_inventory(
         }
         if let Some((from, to)) = swap {
           *item_move = InventoryItemMovementStatus::Nothing;
-          player_inventory.items.swap(from, to);
+          player_inventory.swap(from, to);
+          send_message(client.as_mut(), PlayerCommand::InventorySwap { from, to });
         }
       });
   }
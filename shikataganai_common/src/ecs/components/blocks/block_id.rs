@@ -1,19 +1,25 @@
-use crate::ecs::components::blocks::{regular_blocks, regular_meshes, Block, BlockMeta, BlockTrait};
+use crate::ecs::components::blocks::{Block, BlockMeta};
 use bevy::prelude::Entity;
 use serde::{Deserialize, Serialize};
-use std::ops::Deref;
 
+// Index into the BlockRegistry, looked up through the Registries resource. Serializes the same way the old enum did,
+// so saved worlds keep loading.
 #[derive(Copy, Clone, PartialEq, Debug, Eq, Hash, Serialize, Deserialize)]
-#[repr(u32)]
-pub enum BlockId {
-  Air,
-  Dirt,
-  Grass,
-  Cobble,
-  Iron,
-  Stair,
-  Chest,
-  Furnace,
+pub struct BlockId(pub u32);
+
+// Blocks the game itself refers to, they always get these ids
+#[allow(non_upper_case_globals)]
+impl BlockId {
+  pub const Air: BlockId = BlockId(0);
+  pub const Dirt: BlockId = BlockId(1);
+  pub const Grass: BlockId = BlockId(2);
+  pub const Cobble: BlockId = BlockId(3);
+  pub const Iron: BlockId = BlockId(4);
+  pub const Stair: BlockId = BlockId(5);
+  pub const Chest: BlockId = BlockId(6);
+  pub const Furnace: BlockId = BlockId(7);
+  pub const Wood: BlockId = BlockId(8);
+  pub const Leaves: BlockId = BlockId(9);
 }
 
 impl Into<Block> for BlockId {
@@ -25,23 +31,3 @@ impl Into<Block> for BlockId {
     }
   }
 }
-
-static BLOCK_TRAITS: [&(dyn BlockTrait + Sync); 8] = [
-  &regular_blocks::Air,
-  &regular_blocks::Dirt,
-  &regular_blocks::Grass,
-  &regular_blocks::Cobblestone,
-  &regular_blocks::Iron,
-  &regular_meshes::Stair,
-  &regular_meshes::Chest,
-  &regular_blocks::Furnace,
-];
-
-impl Deref for BlockId {
-  type Target = dyn BlockTrait;
-
-  #[inline]
-  fn deref(&self) -> &'static Self::Target {
-    BLOCK_TRAITS[*self as usize]
-  }
-}
@@ -5,20 +5,22 @@ use crate::util::array::DDD;
 use bevy::ecs::system::EntityCommands;
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::ops::Deref;
 
 pub mod animation;
 pub mod block_id;
-pub mod regular_blocks;
-pub mod regular_meshes;
+pub mod registry;
 
 pub trait BlockTrait {
+  fn name(&self) -> &str;
   fn visible(&self) -> bool {
     true
   }
   fn passable(&self) -> bool {
     false
   }
+  // What the player gets for removing the block
+  fn drops(&self) -> Option<BlockOrItem>;
+  fn max_stack(&self) -> u32;
   fn need_to_spawn_functors(&self) -> bool {
     false
   } // Can be done better ? ? ?
@@ -65,7 +67,7 @@ impl BlockMeta {
   }
 }
 
-#[derive(Debug, Component, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Component, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Block {
   pub block: BlockId,
   pub meta: BlockMeta,
@@ -101,22 +103,13 @@ impl Block {
   }
 }
 
-impl Deref for Block {
-  type Target = dyn BlockTrait;
-
-  #[inline]
-  fn deref(&self) -> &Self::Target {
-    self.block.deref()
-  }
-}
-
 #[derive(Component, Copy, Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub enum BlockOrItem {
   Block(BlockId),
   Item(ItemId),
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct QuantifiedBlockOrItem {
   pub block_or_item: BlockOrItem,
   pub quant: u32,
@@ -0,0 +1,217 @@
+use crate::ecs::components::blocks::block_id::BlockId;
+use crate::ecs::components::blocks::{BlockOrItem, BlockTrait};
+use crate::ecs::components::functors::InternalInventory;
+use crate::ecs::components::item::registry::{ItemRegistry, DEFAULT_MAX_STACK};
+use crate::util::array::DDD;
+use crate::util::registry::{assign_ids, Definition};
+use bevy::ecs::system::EntityCommands;
+use bevy::utils::hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub const DEFAULT_BLOCKS: &str = include_str!("../../../../data/blocks.toml");
+
+// Have to be defined, in this order, the BlockId constants point at them
+pub const CORE_BLOCKS: [&str; 10] = [
+  "air", "dirt", "grass", "cobble", "iron", "stair", "chest", "furnace", "wood", "leaves",
+];
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FunctorDefinition {
+  InternalInventory { capacity: usize },
+}
+
+// Names are resolved by the client against its sprite sheet and meshes
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderDefinition {
+  Nothing,
+  Block([String; 6]),
+  Mesh(String),
+  Skeleton(String),
+}
+
+fn visible_by_default() -> bool {
+  true
+}
+
+fn max_stack_by_default() -> u32 {
+  DEFAULT_MAX_STACK
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlockDefinition {
+  pub name: String,
+  #[serde(default = "visible_by_default")]
+  pub visible: bool,
+  #[serde(default)]
+  pub passable: bool,
+  #[serde(default)]
+  pub functors: Vec<FunctorDefinition>,
+  #[serde(default)]
+  pub interface: Option<String>,
+  pub render: RenderDefinition,
+  // The block itself if left out
+  #[serde(default)]
+  pub drop: Option<String>,
+  #[serde(default = "max_stack_by_default")]
+  pub max_stack: u32,
+}
+
+impl Definition for BlockDefinition {
+  fn name(&self) -> &str {
+    &self.name
+  }
+
+  fn missing(name: String) -> Self {
+    Self {
+      name,
+      visible: true,
+      passable: false,
+      functors: vec![],
+      interface: None,
+      render: RenderDefinition::Block(std::array::from_fn(|_| "empty".to_string())),
+      drop: Some("nothing".to_string()),
+      max_stack: DEFAULT_MAX_STACK,
+    }
+  }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BlockFile {
+  block: Vec<BlockDefinition>,
+}
+
+pub struct RegisteredBlock {
+  pub id: BlockId,
+  pub definition: BlockDefinition,
+  drop: Option<BlockOrItem>,
+}
+
+impl BlockTrait for RegisteredBlock {
+  fn name(&self) -> &str {
+    &self.definition.name
+  }
+  fn visible(&self) -> bool {
+    self.definition.visible
+  }
+  fn passable(&self) -> bool {
+    self.definition.passable
+  }
+  fn drops(&self) -> Option<BlockOrItem> {
+    self.drop
+  }
+  fn max_stack(&self) -> u32 {
+    self.definition.max_stack
+  }
+  fn need_to_spawn_functors(&self) -> bool {
+    !self.definition.functors.is_empty()
+  }
+  fn spawn_functors(&self, _location: DDD, commands: &mut EntityCommands) {
+    for functor in self.definition.functors.iter() {
+      match functor {
+        FunctorDefinition::InternalInventory { capacity } => {
+          commands.insert(InternalInventory::with_capacity(*capacity));
+        }
+      }
+    }
+  }
+  fn need_reverse_location(&self) -> bool {
+    !self.definition.functors.is_empty()
+  }
+}
+
+pub struct BlockRegistry {
+  blocks: Vec<RegisteredBlock>,
+  ids: HashMap<String, BlockId>,
+}
+
+impl BlockRegistry {
+  // The built-in blocks when there is no path
+  pub fn read_definitions(path: Option<&Path>) -> Result<Vec<BlockDefinition>, String> {
+    let text = match path {
+      Some(path) => std::fs::read_to_string(path).map_err(|error| format!("Failed to read {:?}: {}", path, error))?,
+      None => DEFAULT_BLOCKS.to_string(),
+    };
+    toml::from_str::<BlockFile>(&text)
+      .map(|file| file.block)
+      .map_err(|error| format!("Invalid block definitions in {:?}: {}", path.unwrap_or(Path::new("built-in")), error))
+  }
+
+  pub fn assign_ids(definitions: Vec<BlockDefinition>, known: &[String]) -> Result<Vec<BlockDefinition>, String> {
+    assign_ids("Block", &CORE_BLOCKS, definitions, known)
+  }
+
+  // Definitions in id order, as returned by assign_ids or sent by the server. Drops may name items.
+  pub fn new(definitions: Vec<BlockDefinition>, items: &ItemRegistry) -> Result<Self, String> {
+    let mut ids = HashMap::new();
+    for (index, definition) in definitions.iter().enumerate() {
+      if definition.name.is_empty() || definition.name == "nothing" {
+        return Err(format!("Invalid block name {:?}", definition.name));
+      }
+      if ids.insert(definition.name.clone(), BlockId(index as u32)).is_some() {
+        return Err(format!("Block {} is defined twice", definition.name));
+      }
+      if definition.max_stack == 0 {
+        return Err(format!("Block {} has to stack to at least 1", definition.name));
+      }
+      for functor in definition.functors.iter() {
+        match functor {
+          FunctorDefinition::InternalInventory { capacity } if *capacity == 0 => {
+            return Err(format!("Block {} has an inventory without room", definition.name));
+          }
+          FunctorDefinition::InternalInventory { .. } => {}
+        }
+      }
+    }
+    for (index, name) in CORE_BLOCKS.iter().enumerate() {
+      if ids.get(*name) != Some(&BlockId(index as u32)) {
+        return Err(format!("Block {} has to have id {}", name, index));
+      }
+    }
+    let blocks = definitions
+      .into_iter()
+      .enumerate()
+      .map(|(index, definition)| {
+        let id = BlockId(index as u32);
+        let drop = match definition.drop.as_deref() {
+          None => Some(BlockOrItem::Block(id)),
+          Some("nothing") => None,
+          Some(name) => Some(
+            ids
+              .get(name)
+              .map(|id| BlockOrItem::Block(*id))
+              .or_else(|| items.by_name(name).map(BlockOrItem::Item))
+              .ok_or_else(|| format!("Block {} drops {}, which is neither a block nor an item", definition.name, name))?,
+          ),
+        };
+        Ok(RegisteredBlock { id, definition, drop })
+      })
+      .collect::<Result<_, String>>()?;
+    Ok(Self { blocks, ids })
+  }
+
+  // Ids outside of the registry read as air
+  pub fn block(&self, id: BlockId) -> &RegisteredBlock {
+    self.blocks.get(id.0 as usize).unwrap_or(&self.blocks[0])
+  }
+
+  pub fn by_name(&self, name: &str) -> Option<BlockId> {
+    self.ids.get(name).copied()
+  }
+
+  pub fn blocks(&self) -> &[RegisteredBlock] {
+    &self.blocks
+  }
+
+  pub fn definitions(&self) -> Vec<BlockDefinition> {
+    self.blocks.iter().map(|block| block.definition.clone()).collect()
+  }
+
+  pub fn names(&self) -> Vec<String> {
+    self.blocks.iter().map(|block| block.definition.name.clone()).collect()
+  }
+}
@@ -1,96 +1,422 @@
 use bevy::prelude::*;
-use noise::{NoiseFn, Perlin};
-use rand::prelude::*;
 
 use crate::ecs::components::blocks::block_id::BlockId;
-use crate::ecs::components::blocks::Block;
+use crate::ecs::components::blocks::registry::BlockRegistry;
+use crate::ecs::components::blocks::{Block, BlockMeta, BlockTrait};
 use crate::ecs::resources::light::LightLevel;
-use crate::util::array::{Array, Array2d, Array3d, Bounds, DD, DDD};
-use serde::{Deserialize, Serialize};
+use crate::util::array::{Array, Array2d, ArrayIndex, Bounds, ImmediateNeighbours, DD, DDD};
+use crate::util::palette::Palette;
+use crate::worldgen::biomes::BiomeId;
+use bevy::utils::hashbrown::HashMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::VecDeque;
+use std::str::FromStr;
 
-pub const CHUNK_MAX_HEIGHT: i32 = 127;
+pub const SECTION_SIZE: i32 = 16;
 
-#[derive(Component, Serialize, Deserialize)]
-pub struct Chunk {
-  pub grid: Array3d<Block>,
-  pub light_map: Array3d<LightLevel>,
+// Returned for blocks in sections that are not stored
+static AIR: Block = Block {
+  block: BlockId::Air,
+  meta: BlockMeta { v: 0 },
+  entity: Entity::from_raw(0),
+};
+
+// Vertical extent of the world, inclusive on both ends and aligned to sections
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorldHeight {
+  pub min_y: i32,
+  pub max_y: i32,
 }
 
-fn noise(perlin: &Perlin, c: DDD) -> f64 {
-  perlin.get([c.0 as f64 / 20.0, 0.0, c.2 as f64 / 20.0])
+impl Default for WorldHeight {
+  fn default() -> Self {
+    Self { min_y: 0, max_y: 127 }
+  }
 }
 
-impl Chunk {
+impl WorldHeight {
+  pub fn new(min_y: i32, max_y: i32) -> Result<Self, String> {
+    if min_y.rem_euclid(SECTION_SIZE) != 0 || (max_y + 1).rem_euclid(SECTION_SIZE) != 0 {
+      return Err(format!(
+        "World height {}..{} has to start and end on a {} block boundary",
+        min_y, max_y, SECTION_SIZE
+      ));
+    }
+    if max_y <= min_y {
+      return Err(format!("World height {}..{} is empty", min_y, max_y));
+    }
+    Ok(Self { min_y, max_y })
+  }
+
+  pub fn sections(&self) -> usize {
+    ((self.max_y - self.min_y + 1) / SECTION_SIZE) as usize
+  }
+
+  // Section coordinates (y / SECTION_SIZE) covered by the world
+  pub fn section_range(&self) -> std::ops::RangeInclusive<i32> {
+    self.min_y.div_euclid(SECTION_SIZE)..=self.max_y.div_euclid(SECTION_SIZE)
+  }
+
+  // How far terrain reaches below and above sea level, 30 in the default 0:127 and scaled with the height otherwise
+  pub fn terrain_extent(&self) -> i32 {
+    (self.max_y - self.min_y + 1) * 15 / 64
+  }
+
+  // Terrain is built around it, and skylight coming straight down keeps its full strength only above it
+  pub fn sea_level(&self) -> i32 {
+    self.min_y + self.terrain_extent()
+  }
+}
+
+// MIN_Y:MAX_Y, e.g. -64:255
+impl FromStr for WorldHeight {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (min_y, max_y) = s
+      .split_once(':')
+      .ok_or_else(|| format!("Expected world height as MIN_Y:MAX_Y, got {}", s))?;
+    let min_y = min_y.trim().parse().map_err(|_| format!("{} is not a number", min_y))?;
+    let max_y = max_y.trim().parse().map_err(|_| format!("{} is not a number", max_y))?;
+    Self::new(min_y, max_y)
+  }
+}
+
+// Blocks and light of a SECTION_SIZE³ cube, both palette compressed. Entities are sparse, so blocks that have one
+// are kept whole in a side table while the palette only holds the block without it.
+pub struct ChunkSection {
+  blocks: Palette<Block>,
+  light: Palette<LightLevel>,
+  entities: HashMap<DDD, Block>,
+  // The block last handed out by get_mut, written back before anything else reads or writes the palette
+  borrowed: Option<(DDD, Block)>,
+}
+
+impl ChunkSection {
   pub fn new<F: Fn(DDD) -> BlockId>(bounds: Bounds<DDD>, block_f: F) -> Self {
+    let mut section = Self::empty(bounds, LightLevel::dark());
+    let mut i = bounds.0;
+    loop {
+      section.set(i, Block::new(block_f(i)));
+      i = match i.next(&bounds) {
+        None => break,
+        Some(i) => i,
+      }
+    }
+    section
+  }
+
+  pub fn empty(bounds: Bounds<DDD>, light: LightLevel) -> Self {
+    Self {
+      blocks: Palette::new(bounds, AIR),
+      light: Palette::new(bounds, light),
+      entities: HashMap::new(),
+      borrowed: None,
+    }
+  }
+
+  pub fn bounds(&self) -> Bounds<DDD> {
+    self.blocks.bounds()
+  }
+
+  pub fn get(&self, c: DDD) -> &Block {
+    match &self.borrowed {
+      Some((borrowed, block)) if *borrowed == c => block,
+      _ => self.entities.get(&c).unwrap_or_else(|| self.blocks.get(c)),
+    }
+  }
+
+  pub fn get_mut(&mut self, c: DDD) -> &mut Block {
+    self.write_back();
+    let block = *self.get(c);
+    &mut self.borrowed.insert((c, block)).1
+  }
+
+  pub fn set(&mut self, c: DDD, block: Block) {
+    self.write_back();
+    self.store(c, block);
+  }
+
+  fn write_back(&mut self) {
+    if let Some((c, block)) = self.borrowed.take() {
+      self.store(c, block);
+    }
+  }
+
+  fn store(&mut self, c: DDD, block: Block) {
+    if block.entity == Entity::from_bits(0) {
+      self.entities.remove(&c);
+    } else {
+      self.entities.insert(c, block);
+    }
+    self.blocks.set(
+      c,
+      Block {
+        entity: Entity::from_bits(0),
+        ..block
+      },
+    );
+  }
+
+  pub fn get_light_level(&self, c: DDD) -> LightLevel {
+    *self.light.get(c)
+  }
+
+  pub fn set_light_level(&mut self, c: DDD, light_level: LightLevel) {
+    self.light.set(c, light_level)
+  }
+
+  // Light level of the section if it holds nothing but evenly lit air
+  fn empty_light(&mut self) -> Option<LightLevel> {
+    self.write_back();
+    if self.entities.is_empty() && self.blocks.uniform() == Some(AIR) {
+      self.light.uniform()
+    } else {
+      None
+    }
+  }
+}
+
+impl Serialize for ChunkSection {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    // Entities mean nothing on the other end, the palette already holds the blocks without them
+    let Some((c, block)) = self.borrowed else {
+      return (&self.blocks, &self.light).serialize(serializer);
+    };
+    let mut blocks = self.blocks.clone();
+    blocks.set(
+      c,
+      Block {
+        entity: Entity::from_bits(0),
+        ..block
+      },
+    );
+    (&blocks, &self.light).serialize(serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for ChunkSection {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    let (blocks, light) = Deserialize::deserialize(deserializer)?;
+    Ok(Self {
+      blocks,
+      light,
+      entities: HashMap::new(),
+      borrowed: None,
+    })
+  }
+}
+
+// A column of SECTION_SIZE³ sections. Sections that are all air and evenly lit are not stored at all,
+// their light level is kept in empty_light instead.
+#[derive(Component, Serialize, Deserialize)]
+pub struct Chunk {
+  pub bounds: Bounds<DDD>,
+  // Indexed from the bottom of the world up
+  pub sections: Vec<Option<ChunkSection>>,
+  pub empty_light: Vec<LightLevel>,
+  // One per (x, z) column
+  pub biomes: Array2d<BiomeId>,
+}
+
+impl Chunk {
+  pub fn new<F: Fn(DDD) -> BlockId>(bounds: Bounds<DDD>, blocks: &BlockRegistry, block_f: F) -> Self {
+    let section_count = ((bounds.1 .1 - bounds.0 .1 + 1) / SECTION_SIZE) as usize;
     let mut chunk = Self {
-      grid: Array::new_init(bounds, |c| Block::new(block_f(c))),
-      // light_map: Array::new_init(bounds, |_| LightLevel::new(0, 0, 0)),
-      light_map: Array::new_zeroed(bounds), // TODO: UB ? ? ?
+      bounds,
+      sections: vec![],
+      empty_light: vec![LightLevel::dark(); section_count],
+      biomes: Array::new_init(((bounds.0 .0, bounds.0 .2), (bounds.1 .0, bounds.1 .2)), |_| BiomeId::Plains),
     };
+    for index in 0..section_count {
+      let section_bounds = chunk.section_bounds(index);
+      chunk.sections.push(Some(ChunkSection::new(section_bounds, &block_f)));
+    }
+    chunk.init_heaven(blocks);
+    chunk.compact();
+    chunk
+  }
+
+  pub fn coord(&self) -> DD {
+    (self.bounds.0 .0.div_euclid(SECTION_SIZE), self.bounds.0 .2.div_euclid(SECTION_SIZE))
+  }
+
+  pub fn section_bounds(&self, index: usize) -> Bounds<DDD> {
+    let bottom = self.bounds.0 .1 + index as i32 * SECTION_SIZE;
+    (
+      (self.bounds.0 .0, bottom, self.bounds.0 .2),
+      (self.bounds.1 .0, bottom + SECTION_SIZE - 1, self.bounds.1 .2),
+    )
+  }
+
+  pub fn section_index(&self, y: i32) -> usize {
+    ((y - self.bounds.0 .1) / SECTION_SIZE) as usize
+  }
+
+  pub fn get(&self, c: DDD) -> Option<&Block> {
+    if !c.in_bounds(&self.bounds) {
+      return None;
+    }
+    match &self.sections[self.section_index(c.1)] {
+      None => Some(&AIR),
+      Some(section) => Some(section.get(c)),
+    }
+  }
+
+  // Writing into a section that isn't stored brings it back
+  pub fn get_mut(&mut self, c: DDD) -> Option<&mut Block> {
+    if !c.in_bounds(&self.bounds) {
+      return None;
+    }
+    let index = self.section_index(c.1);
+    Some(self.materialize(index).get_mut(c))
+  }
+
+  // Writing anything but air into a section that isn't stored brings it back
+  pub fn set(&mut self, c: DDD, block: Block) -> Option<()> {
+    if !c.in_bounds(&self.bounds) {
+      return None;
+    }
+    let index = self.section_index(c.1);
+    if self.sections[index].is_none() && block == AIR {
+      return Some(());
+    }
+    self.materialize(index).set(c, block);
+    Some(())
+  }
+
+  pub fn get_light_level(&self, c: DDD) -> Option<LightLevel> {
+    if !c.in_bounds(&self.bounds) {
+      return None;
+    }
+    let index = self.section_index(c.1);
+    match &self.sections[index] {
+      None => Some(self.empty_light[index]),
+      Some(section) => Some(section.get_light_level(c)),
+    }
+  }
+
+  pub fn set_light_level(&mut self, c: DDD, light_level: LightLevel) -> Option<()> {
+    if !c.in_bounds(&self.bounds) {
+      return None;
+    }
+    let index = self.section_index(c.1);
+    if self.sections[index].is_none() && self.empty_light[index] == light_level {
+      return Some(());
+    }
+    self.materialize(index).set_light_level(c, light_level);
+    Some(())
+  }
+
+  pub fn section(&self, index: usize) -> Option<&ChunkSection> {
+    self.sections.get(index).and_then(|section| section.as_ref())
+  }
+
+  fn materialize(&mut self, index: usize) -> &mut ChunkSection {
+    if self.sections[index].is_none() {
+      let bounds = self.section_bounds(index);
+      self.sections[index] = Some(ChunkSection::empty(bounds, self.empty_light[index]));
+    }
+    self.sections[index].as_mut().unwrap()
+  }
+
+  // Drops sections that are all air and evenly lit
+  pub fn compact(&mut self) {
+    for index in 0..self.sections.len() {
+      if let Some(section) = &mut self.sections[index] && let Some(light) = section.empty_light() {
+        self.sections[index] = None;
+        self.empty_light[index] = light;
+      }
+    }
+  }
+
+  // Visits the blocks of stored sections only, everything else is air
+  pub fn foreach<F: FnMut(DDD, &Block)>(&self, mut f: F) {
+    for section in self.sections.iter().flatten() {
+      let bounds = section.bounds();
+      let mut i = bounds.0;
+      loop {
+        f(i, section.get(i));
+        i = match i.next(&bounds) {
+          None => break,
+          Some(i) => i,
+        }
+      }
+    }
+  }
+
+  // Only the blocks that f actually changes end up being written back
+  pub fn foreach_mut<F: FnMut(DDD, &mut Block)>(&mut self, mut f: F) {
+    for section in self.sections.iter_mut().flatten() {
+      let bounds = section.bounds();
+      let mut i = bounds.0;
+      loop {
+        let mut block = *section.get(i);
+        f(i, &mut block);
+        if block != *section.get(i) {
+          section.set(i, block);
+        }
+        i = match i.next(&bounds) {
+          None => break,
+          Some(i) => i,
+        }
+      }
+    }
+  }
+
+  pub fn biome(&self, column: DD) -> BiomeId {
+    self.biomes[column]
+  }
+
+  // Skylight falls straight down until it hits a solid block, then floods sideways into caves and under overhangs
+  // the same way do_relight would spread it. Light coming in from neighbouring chunks is left to the relight system.
+  fn init_heaven(&mut self, blocks: &BlockRegistry) {
+    let bounds = self.bounds;
+    let sea_level = WorldHeight {
+      min_y: bounds.0 .1,
+      max_y: bounds.1 .1,
+    }
+    .sea_level();
+    let mut queue = VecDeque::new();
     for ix in bounds.0 .0..=bounds.1 .0 {
       for iz in bounds.0 .2..=bounds.1 .2 {
-        let mut heaven = 16;
-        for iy in (0..=CHUNK_MAX_HEIGHT).rev() {
-          if chunk.grid[(ix, iy, iz)].visible() {
-            heaven -= 1;
-            if heaven > 16 {
-              heaven = 0;
-            }
+        let mut heaven: u8 = 16;
+        for iy in (bounds.0 .1..=bounds.1 .1).rev() {
+          if blocks.block(self.get((ix, iy, iz)).unwrap().block).visible() {
+            heaven = 0;
             continue;
           }
-          chunk.light_map[(ix, iy, iz)] = LightLevel::new(heaven, 0, 0);
-          if heaven < 16 || iy < 30 {
-            heaven -= 1;
-            if heaven > 16 {
-              heaven = 0;
-            }
+          self.set_light_level((ix, iy, iz), LightLevel::new(heaven, 0, 0));
+          if heaven > 1 {
+            queue.push_back((ix, iy, iz));
+          }
+          if heaven < 16 || iy < sea_level {
+            heaven = heaven.saturating_sub(1);
           }
         }
       }
     }
-    chunk
-  }
-
-  pub async fn generate(coord: DD) -> Chunk {
-    let perlin = Perlin::new(11);
-    let from = (coord.0 * 16, 0, coord.1 * 16);
-    let to = (coord.0 * 16 + 15, CHUNK_MAX_HEIGHT, coord.1 * 16 + 15);
-    let perlin_top = Perlin::new(12);
-    let v = Array2d::new_init(((from.0, from.2), (to.0, to.2)), |(x, z)| noise(&perlin, (x, 0, z)));
-    let vtop = Array2d::new_init(((from.0, from.2), (to.0, to.2)), |(x, z)| noise(&perlin_top, (x, 0, z)));
-
-    Chunk::new((from, to), |(x, y, z)| {
-      let bottom = v[(x, z)];
-      let top = vtop[(x, z)];
-
-      let bottom_extent = (bottom * 30.0).floor() as i32;
-      let top_extent = ((top + 1.0) * bottom / 2.0 * 30.0).floor() as i32;
-
-      if bottom <= 0.0 {
-        BlockId::Air
-      } else if y < 30 {
-        if (30 - y) < bottom_extent {
-          if rand::thread_rng().gen_range(0..10) == 0 {
-            BlockId::Iron
-          } else {
-            BlockId::Cobble
+    while let Some(coord) = queue.pop_front() {
+      let spread = self.get_light_level(coord).unwrap().heaven - 1;
+      for neighbour in coord.immediate_neighbours() {
+        if let Some(block) = self.get(neighbour)
+          && !blocks.block(block.block).visible()
+          && let Some(mut light_level) = self.get_light_level(neighbour)
+          && light_level.heaven < spread
+        {
+          light_level.heaven = spread;
+          self.set_light_level(neighbour, light_level);
+          if spread > 1 {
+            queue.push_back(neighbour);
           }
-        } else {
-          BlockId::Air
-        }
-      } else if (y - 30) > top_extent {
-        BlockId::Air
-      } else if (y - 30) == top_extent {
-        BlockId::Grass
-      } else if y - 28 >= top_extent {
-        BlockId::Dirt
-      } else {
-        if rand::thread_rng().gen_range(0..10) == 0 {
-          BlockId::Iron
-        } else {
-          BlockId::Cobble
         }
       }
-    })
+    }
   }
 }
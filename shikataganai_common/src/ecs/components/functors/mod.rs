@@ -1,5 +1,7 @@
-use crate::ecs::components::blocks::block_id::BlockId;
-use crate::ecs::components::blocks::{BlockOrItem, QuantifiedBlockOrItem};
+use crate::ecs::components::blocks::QuantifiedBlockOrItem;
+use crate::ecs::resources::player::PlayerInventory;
+use crate::ecs::resources::registries::Registries;
+use crate::networking::{InventoryIndex, TransactionType};
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -9,27 +11,73 @@ pub struct InternalInventory {
 }
 
 impl InternalInventory {
+  // Starts out empty, whatever is put in comes back out when the block is removed
   pub fn with_capacity(len: usize) -> Self {
     Self {
-      inventory: (0..len)
-        .map(|x| {
-          if x == 2 {
-            Some(QuantifiedBlockOrItem {
-              block_or_item: BlockOrItem::Block(BlockId::Dirt),
-              quant: 5,
-            })
-          } else {
-            Some(QuantifiedBlockOrItem {
-              block_or_item: BlockOrItem::Block(BlockId::Cobble),
-              quant: 5,
-            })
-          }
-        })
-        .collect(),
+      inventory: vec![None; len],
     }
   }
 }
 
+impl InternalInventory {
+  fn slot<'a>(
+    &'a mut self,
+    foreign: &'a mut PlayerInventory,
+    index: InventoryIndex,
+  ) -> Option<&'a mut Option<QuantifiedBlockOrItem>> {
+    match index {
+      InventoryIndex::Local(index) => self.inventory.get_mut(index),
+      InventoryIndex::Foreign(index) => foreign.items.get_mut(index),
+    }
+  }
+
+  // Moves items between this inventory and the player's one. Invalid transactions leave both untouched and return false
+  pub fn transaction(
+    &mut self,
+    foreign: &mut PlayerInventory,
+    registries: &Registries,
+    _type: TransactionType,
+    from: InventoryIndex,
+    to: InventoryIndex,
+  ) -> bool {
+    if from == to {
+      return false;
+    }
+    let (Some(source), Some(target)) = (
+      self.slot(foreign, from).map(|slot| slot.clone()),
+      self.slot(foreign, to).map(|slot| slot.clone()),
+    ) else {
+      return false;
+    };
+    let (source, target) = match (_type, source, target) {
+      (TransactionType::Swap, source, target) => (target, source),
+      (TransactionType::Split, Some(mut source), None) if source.quant >= 2 => {
+        let moved = source.quant / 2;
+        source.quant -= moved;
+        let target = QuantifiedBlockOrItem {
+          block_or_item: source.block_or_item,
+          quant: moved,
+        };
+        (Some(source), Some(target))
+      }
+      (TransactionType::Merge, Some(source), None) => (None, Some(source)),
+      // Whatever doesn't fit on the target stack stays behind
+      (TransactionType::Merge, Some(mut source), Some(mut target))
+        if source.block_or_item == target.block_or_item && target.quant < registries.max_stack(target.block_or_item) =>
+      {
+        let moved = source.quant.min(registries.max_stack(target.block_or_item) - target.quant);
+        source.quant -= moved;
+        target.quant += moved;
+        ((source.quant > 0).then_some(source), Some(target))
+      }
+      _ => return false,
+    };
+    *self.slot(foreign, from).unwrap() = source;
+    *self.slot(foreign, to).unwrap() = target;
+    true
+  }
+}
+
 pub enum FunctorTransit {
   InternalInventory(Vec<QuantifiedBlockOrItem>),
 }
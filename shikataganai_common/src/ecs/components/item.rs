@@ -1,8 +0,0 @@
-use serde::{Deserialize, Serialize};
-
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub enum ItemId {
-  Coal,
-  Wand,
-  Iron,
-}
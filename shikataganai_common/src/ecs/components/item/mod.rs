@@ -0,0 +1,28 @@
+use crate::ecs::components::item::registry::ToolClass;
+use serde::{Deserialize, Serialize};
+
+pub mod registry;
+
+// Index into the ItemRegistry, serialized the same way the old enum was
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ItemId(pub u32);
+
+// Items the game itself refers to, they always get these ids
+#[allow(non_upper_case_globals)]
+impl ItemId {
+  pub const Coal: ItemId = ItemId(0);
+  pub const Wand: ItemId = ItemId(1);
+  pub const Iron: ItemId = ItemId(2);
+}
+
+pub trait ItemTrait {
+  fn name(&self) -> &str;
+  fn display_name(&self) -> &str;
+  fn tooltip(&self) -> &str;
+  fn max_stack(&self) -> u32;
+  fn tool(&self) -> Option<ToolClass>;
+  // Uses before the item breaks, None for items that don't wear out
+  fn durability(&self) -> Option<u32>;
+  // Seconds it burns for, None for items that don't
+  fn fuel(&self) -> Option<f32>;
+}
@@ -0,0 +1,182 @@
+use crate::ecs::components::item::{ItemId, ItemTrait};
+use crate::util::registry::{assign_ids, Definition};
+use bevy::utils::hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub const DEFAULT_ITEMS: &str = include_str!("../../../../data/items.toml");
+pub const DEFAULT_MAX_STACK: u32 = 64;
+
+// Have to be defined, in this order, the ItemId constants point at them
+pub const CORE_ITEMS: [&str; 3] = ["coal", "wand", "iron_ingot"];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolClass {
+  Pickaxe,
+  Axe,
+  Shovel,
+  Wand,
+}
+
+fn max_stack_by_default() -> u32 {
+  DEFAULT_MAX_STACK
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ItemDefinition {
+  pub name: String,
+  // The name if left out
+  #[serde(default)]
+  pub display_name: Option<String>,
+  #[serde(default)]
+  pub tooltip: String,
+  #[serde(default = "max_stack_by_default")]
+  pub max_stack: u32,
+  #[serde(default)]
+  pub tool: Option<ToolClass>,
+  #[serde(default)]
+  pub durability: Option<u32>,
+  #[serde(default)]
+  pub fuel: Option<f32>,
+  // Resolved by the client against its item sheet
+  pub sprite: String,
+}
+
+impl Definition for ItemDefinition {
+  fn name(&self) -> &str {
+    &self.name
+  }
+
+  fn missing(name: String) -> Self {
+    Self {
+      name,
+      display_name: None,
+      tooltip: "No longer exists".to_string(),
+      max_stack: DEFAULT_MAX_STACK,
+      tool: None,
+      durability: None,
+      fuel: None,
+      sprite: "nothing".to_string(),
+    }
+  }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ItemFile {
+  item: Vec<ItemDefinition>,
+}
+
+pub struct RegisteredItem {
+  pub id: ItemId,
+  pub definition: ItemDefinition,
+}
+
+impl ItemTrait for RegisteredItem {
+  fn name(&self) -> &str {
+    &self.definition.name
+  }
+  fn display_name(&self) -> &str {
+    self.definition.display_name.as_deref().unwrap_or(&self.definition.name)
+  }
+  fn tooltip(&self) -> &str {
+    &self.definition.tooltip
+  }
+  fn max_stack(&self) -> u32 {
+    self.definition.max_stack
+  }
+  fn tool(&self) -> Option<ToolClass> {
+    self.definition.tool
+  }
+  fn durability(&self) -> Option<u32> {
+    self.definition.durability
+  }
+  fn fuel(&self) -> Option<f32> {
+    self.definition.fuel
+  }
+}
+
+pub struct ItemRegistry {
+  items: Vec<RegisteredItem>,
+  ids: HashMap<String, ItemId>,
+}
+
+impl ItemRegistry {
+  // The built-in items when there is no path
+  pub fn read_definitions(path: Option<&Path>) -> Result<Vec<ItemDefinition>, String> {
+    let text = match path {
+      Some(path) => std::fs::read_to_string(path).map_err(|error| format!("Failed to read {:?}: {}", path, error))?,
+      None => DEFAULT_ITEMS.to_string(),
+    };
+    toml::from_str::<ItemFile>(&text)
+      .map(|file| file.item)
+      .map_err(|error| format!("Invalid item definitions in {:?}: {}", path.unwrap_or(Path::new("built-in")), error))
+  }
+
+  pub fn assign_ids(definitions: Vec<ItemDefinition>, known: &[String]) -> Result<Vec<ItemDefinition>, String> {
+    assign_ids("Item", &CORE_ITEMS, definitions, known)
+  }
+
+  // Definitions in id order, as returned by assign_ids or sent by the server
+  pub fn new(definitions: Vec<ItemDefinition>) -> Result<Self, String> {
+    let mut ids = HashMap::new();
+    for (index, definition) in definitions.iter().enumerate() {
+      if definition.name.is_empty() || definition.name == "nothing" {
+        return Err(format!("Invalid item name {:?}", definition.name));
+      }
+      if ids.insert(definition.name.clone(), ItemId(index as u32)).is_some() {
+        return Err(format!("Item {} is defined twice", definition.name));
+      }
+      if definition.max_stack == 0 {
+        return Err(format!("Item {} has to stack to at least 1", definition.name));
+      }
+      match definition.durability {
+        Some(0) => return Err(format!("Item {} breaks before it is used", definition.name)),
+        Some(_) if definition.max_stack != 1 => {
+          return Err(format!("Item {} wears out, so it has to stack to 1", definition.name));
+        }
+        _ => {}
+      }
+      if let Some(fuel) = definition.fuel && !(fuel > 0.0) {
+        return Err(format!("Item {} has to burn for some time, got {}", definition.name, fuel));
+      }
+    }
+    for (index, name) in CORE_ITEMS.iter().enumerate() {
+      if ids.get(*name) != Some(&ItemId(index as u32)) {
+        return Err(format!("Item {} has to have id {}", name, index));
+      }
+    }
+    let items = definitions
+      .into_iter()
+      .enumerate()
+      .map(|(index, definition)| RegisteredItem {
+        id: ItemId(index as u32),
+        definition,
+      })
+      .collect();
+    Ok(Self { items, ids })
+  }
+
+  // Ids outside of the registry read as the first item
+  pub fn item(&self, id: ItemId) -> &RegisteredItem {
+    self.items.get(id.0 as usize).unwrap_or(&self.items[0])
+  }
+
+  pub fn by_name(&self, name: &str) -> Option<ItemId> {
+    self.ids.get(name).copied()
+  }
+
+  pub fn items(&self) -> &[RegisteredItem] {
+    &self.items
+  }
+
+  pub fn definitions(&self) -> Vec<ItemDefinition> {
+    self.items.iter().map(|item| item.definition.clone()).collect()
+  }
+
+  pub fn names(&self) -> Vec<String> {
+    self.items.iter().map(|item| item.definition.name.clone()).collect()
+  }
+}
@@ -1,3 +1,5 @@
+use crate::ecs::components::blocks::registry::BlockRegistry;
+use crate::ecs::components::blocks::BlockTrait;
 use crate::ecs::resources::world::GameWorld;
 use crate::util::array::{ImmediateNeighbours, DDD};
 use bevy::prelude::*;
@@ -12,7 +14,7 @@ pub enum RelightEvent {
   Relight(DDD),
 }
 
-#[derive(Copy, Clone, Debug, Serialize, Deserialize, Component)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Component)]
 pub struct LightLevel {
   pub heaven: u8,
   pub hearth: u8,
@@ -50,9 +52,15 @@ impl LightLevel {
   }
 }
 
-pub fn do_relight(coord: DDD, game_world: &mut GameWorld, remesh: &mut HashSet<DDD>, queue: &mut VecDeque<DDD>) {
+pub fn do_relight(
+  coord: DDD,
+  game_world: &mut GameWorld,
+  blocks: &BlockRegistry,
+  remesh: &mut HashSet<DDD>,
+  queue: &mut VecDeque<DDD>,
+) {
   if let Some(light_level) = game_world.get_light_level(coord) && let Some(block) = game_world.get(coord) {
-    if block.visible() {
+    if blocks.block(block.block).visible() {
       return;
     }
     let (heavens, hearths): (Vec<_>, Vec<_>) = coord
@@ -68,7 +76,7 @@ pub fn do_relight(coord: DDD, game_world: &mut GameWorld, remesh: &mut HashSet<D
             .get_light_level((coord.0, coord.1+1, coord.2))
             .map(|light_level|light_level.heaven)
             .unwrap_or(0);
-          if above == 16 && coord.1 >= 29 {
+          if above == 16 && coord.1 >= game_world.height.sea_level() - 1 {
             &17u8
           } else {
             &0u8
@@ -82,7 +90,7 @@ pub fn do_relight(coord: DDD, game_world: &mut GameWorld, remesh: &mut HashSet<D
       game_world.set_light_level(coord, LightLevel::new(max_heaven, light_level.light_source.max(light_level.hearth.max(max_hearth)), light_level.light_source));
       remesh.insert(coord);
       for neighbour in coord.immediate_neighbours() {
-        if !game_world.get(neighbour).map(|block|block.visible()).unwrap_or(true) {
+        if !game_world.get(neighbour).map(|block| blocks.block(block.block).visible()).unwrap_or(true) {
           queue.push_front(neighbour);
         }
       }
@@ -90,18 +98,22 @@ pub fn do_relight(coord: DDD, game_world: &mut GameWorld, remesh: &mut HashSet<D
   }
 }
 
-pub fn relight_helper(relight_events: &mut EventReader<RelightEvent>, game_world: &mut GameWorld) -> HashSet<DDD> {
+pub fn relight_helper(
+  relight_events: &mut EventReader<RelightEvent>,
+  game_world: &mut GameWorld,
+  blocks: &BlockRegistry,
+) -> HashSet<DDD> {
   let mut remesh = HashSet::new();
   for RelightEvent::Relight(coord) in relight_events.iter() {
     remesh.insert(*coord);
     let mut queue = VecDeque::new();
-    if game_world.get(*coord).map(|block| block.visible()).unwrap_or(false) {
+    if game_world.get(*coord).map(|block| blocks.block(block.block).visible()).unwrap_or(false) {
       coord.immediate_neighbours().for_each(|coord| queue.push_back(coord));
     } else {
       queue.push_back(*coord);
     }
     while let Some(coord) = queue.pop_front() {
-      do_relight(coord, game_world, &mut remesh, &mut queue);
+      do_relight(coord, game_world, blocks, &mut remesh, &mut queue);
     }
   }
   remesh
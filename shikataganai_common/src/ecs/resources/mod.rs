@@ -1,3 +1,4 @@
 pub mod light;
 pub mod player;
+pub mod registries;
 pub mod world;
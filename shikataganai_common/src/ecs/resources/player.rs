@@ -1,4 +1,138 @@
+use crate::ecs::components::blocks::block_id::BlockId;
+use crate::ecs::components::blocks::{BlockOrItem, QuantifiedBlockOrItem};
+use crate::ecs::components::item::ItemId;
+use crate::ecs::resources::registries::Registries;
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 #[derive(Component, Clone, Resource)]
 pub struct PlayerNickname(pub String);
+
+#[derive(Component, Resource, Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerInventory {
+  pub hot_bar_width: usize,
+  pub items: Vec<Option<QuantifiedBlockOrItem>>,
+}
+
+impl Default for PlayerInventory {
+  fn default() -> Self {
+    Self {
+      hot_bar_width: 9,
+      items: vec![
+        Some(QuantifiedBlockOrItem {
+          block_or_item: BlockOrItem::Block(BlockId::Stair),
+          quant: 64,
+        }),
+        Some(QuantifiedBlockOrItem {
+          block_or_item: BlockOrItem::Block(BlockId::Chest),
+          quant: 64,
+        }),
+        None,
+        Some(QuantifiedBlockOrItem {
+          block_or_item: BlockOrItem::Item(ItemId::Coal),
+          quant: 2,
+        }),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        Some(QuantifiedBlockOrItem {
+          block_or_item: BlockOrItem::Block(BlockId::Grass),
+          quant: 25,
+        }),
+        None,
+        None,
+        None,
+        None,
+        None,
+      ],
+    }
+  }
+}
+
+impl PlayerInventory {
+  // First slot holding block_or_item with room left, or the first empty slot if there is none
+  pub fn slot_for(&self, registries: &Registries, block_or_item: BlockOrItem) -> Option<usize> {
+    let max_stack = registries.max_stack(block_or_item);
+    self
+      .items
+      .iter()
+      .position(|slot| {
+        slot
+          .as_ref()
+          .map(|item| item.block_or_item == block_or_item && item.quant < max_stack)
+          .unwrap_or(false)
+      })
+      .or_else(|| self.items.iter().position(|slot| slot.is_none()))
+  }
+
+  // Tops up the stacks of block_or_item first, then fills empty slots. Adds nothing and returns false if it doesn't all fit.
+  pub fn add(&mut self, registries: &Registries, block_or_item: BlockOrItem, quant: u32) -> bool {
+    let max_stack = registries.max_stack(block_or_item);
+    let room: u64 = self
+      .items
+      .iter()
+      .map(|slot| match slot {
+        None => max_stack as u64,
+        Some(item) if item.block_or_item == block_or_item => max_stack.saturating_sub(item.quant) as u64,
+        Some(_) => 0,
+      })
+      .sum();
+    if room < quant as u64 {
+      return false;
+    }
+    let mut left = quant;
+    for item in self.items.iter_mut().flatten() {
+      if item.block_or_item == block_or_item {
+        let moved = left.min(max_stack.saturating_sub(item.quant));
+        item.quant += moved;
+        left -= moved;
+      }
+    }
+    for slot in self.items.iter_mut().filter(|slot| slot.is_none()) {
+      if left == 0 {
+        break;
+      }
+      let moved = left.min(max_stack);
+      *slot = Some(QuantifiedBlockOrItem { block_or_item, quant: moved });
+      left -= moved;
+    }
+    true
+  }
+
+  // Removes a single block_or_item from the slot, fails if the slot holds anything else
+  pub fn take(&mut self, slot: usize, block_or_item: BlockOrItem) -> bool {
+    match self.items.get_mut(slot) {
+      Some(Some(item)) if item.block_or_item == block_or_item && item.quant > 0 => {
+        item.quant -= 1;
+        if item.quant == 0 {
+          self.items[slot] = None;
+        }
+        true
+      }
+      _ => false,
+    }
+  }
+
+  pub fn swap(&mut self, from: usize, to: usize) -> bool {
+    if from < self.items.len() && to < self.items.len() {
+      self.items.swap(from, to);
+      true
+    } else {
+      false
+    }
+  }
+}
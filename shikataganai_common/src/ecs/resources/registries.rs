@@ -0,0 +1,77 @@
+use crate::ecs::components::blocks::block_id::BlockId;
+use crate::ecs::components::blocks::registry::{BlockDefinition, BlockRegistry};
+use crate::ecs::components::blocks::{BlockOrItem, BlockTrait};
+use crate::ecs::components::item::registry::{ItemDefinition, ItemRegistry};
+use crate::ecs::components::item::{ItemId, ItemTrait};
+use bevy::ecs::system::Resource;
+use std::sync::Arc;
+
+// Blocks and items of the game an App is running. Every App has its own, a client can play on a server with other
+// blocks than the one it started itself. Cheap to clone into tasks.
+#[derive(Resource, Clone)]
+pub struct Registries {
+  pub blocks: Arc<BlockRegistry>,
+  pub items: Arc<ItemRegistry>,
+}
+
+impl Registries {
+  // Definitions in id order, as returned by assign_ids or sent by the server
+  pub fn new(items: Vec<ItemDefinition>, blocks: Vec<BlockDefinition>) -> Result<Self, String> {
+    let items = ItemRegistry::new(items)?;
+    let blocks = BlockRegistry::new(blocks, &items)?;
+    Ok(Self {
+      blocks: Arc::new(blocks),
+      items: Arc::new(items),
+    })
+  }
+
+  pub fn block(&self, id: BlockId) -> &dyn BlockTrait {
+    self.blocks.block(id)
+  }
+
+  pub fn item(&self, id: ItemId) -> &dyn ItemTrait {
+    self.items.item(id)
+  }
+
+  // Blocks win over items of the same name
+  pub fn by_name(&self, name: &str) -> Option<BlockOrItem> {
+    self
+      .blocks
+      .by_name(name)
+      .map(BlockOrItem::Block)
+      .or_else(|| self.items.by_name(name).map(BlockOrItem::Item))
+  }
+
+  pub fn name(&self, block_or_item: BlockOrItem) -> &str {
+    match block_or_item {
+      BlockOrItem::Block(block) => self.block(block).name(),
+      BlockOrItem::Item(item) => self.item(item).name(),
+    }
+  }
+
+  pub fn display_name(&self, block_or_item: BlockOrItem) -> &str {
+    match block_or_item {
+      BlockOrItem::Block(block) => self.block(block).name(),
+      BlockOrItem::Item(item) => self.item(item).display_name(),
+    }
+  }
+
+  // Most of it a single inventory slot holds
+  pub fn max_stack(&self, block_or_item: BlockOrItem) -> u32 {
+    match block_or_item {
+      BlockOrItem::Block(block) => self.block(block).max_stack(),
+      BlockOrItem::Item(item) => self.item(item).max_stack(),
+    }
+  }
+}
+
+// The built-in blocks and items
+impl Default for Registries {
+  fn default() -> Self {
+    let items = ItemRegistry::read_definitions(None).and_then(|definitions| ItemRegistry::assign_ids(definitions, &[]));
+    let blocks = BlockRegistry::read_definitions(None).and_then(|definitions| BlockRegistry::assign_ids(definitions, &[]));
+    items
+      .and_then(|items| Self::new(items, blocks?))
+      .expect("The built-in blocks and items are invalid")
+  }
+}
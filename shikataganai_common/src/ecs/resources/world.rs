@@ -1,7 +1,8 @@
 use crate::ecs::components::blocks::Block;
-use crate::ecs::components::chunk::Chunk;
+use crate::ecs::components::chunk::{Chunk, ChunkSection, WorldHeight, SECTION_SIZE};
 use crate::ecs::resources::light::LightLevel;
-use crate::util::array::{ArrayIndex, DD, DDD};
+use crate::util::array::{ArrayIndex, Bounds, DD, DDD};
+use crate::worldgen::biomes::BiomeId;
 use bevy::ecs::system::Resource;
 use bevy::utils::hashbrown::HashMap;
 
@@ -10,6 +11,7 @@ pub struct GameWorld {
   // List of chunks that are being generated right now to avoid unnecessary matching on an enum for chunk access for 99.99% of runtime
   pub generating: Vec<DD>,
   pub chunks: HashMap<DD, Chunk>,
+  pub height: WorldHeight,
 }
 
 impl GameWorld {
@@ -29,37 +31,75 @@ impl GameWorld {
     (coord.0 / 16, coord.2 / 16)
   }
 
-  pub fn get(&self, c: DDD) -> Option<&Block> {
-    let chunk_coord = Self::get_chunk_coord(c);
-    self.chunks.get(&chunk_coord).and_then(|chunk| {
-      if c.in_bounds(&chunk.grid.bounds) {
-        Some(&chunk.grid[c])
-      } else {
+  pub fn get_section_coord(coord: DDD) -> DDD {
+    (
+      coord.0.div_euclid(SECTION_SIZE),
+      coord.1.div_euclid(SECTION_SIZE),
+      coord.2.div_euclid(SECTION_SIZE),
+    )
+  }
+
+  pub fn section_bounds(section: DDD) -> Bounds<DDD> {
+    let from = (section.0 * SECTION_SIZE, section.1 * SECTION_SIZE, section.2 * SECTION_SIZE);
+    (from, (from.0 + SECTION_SIZE - 1, from.1 + SECTION_SIZE - 1, from.2 + SECTION_SIZE - 1))
+  }
+
+  // Every section coordinate of a chunk column, bottom to top
+  pub fn column_sections(&self, chunk_coord: DD) -> impl Iterator<Item = DDD> {
+    self
+      .height
+      .section_range()
+      .map(move |y| (chunk_coord.0, y, chunk_coord.1))
+  }
+
+  // None for sections that are missing or hold nothing but air
+  pub fn get_section(&self, section: DDD) -> Option<&ChunkSection> {
+    self.chunks.get(&(section.0, section.2)).and_then(|chunk| {
+      let y = section.1 * SECTION_SIZE;
+      if y < chunk.bounds.0 .1 || y > chunk.bounds.1 .1 {
         None
+      } else {
+        chunk.section(chunk.section_index(y))
       }
     })
   }
 
+  pub fn get(&self, c: DDD) -> Option<&Block> {
+    let chunk_coord = Self::get_chunk_coord(c);
+    self.chunks.get(&chunk_coord).and_then(|chunk| chunk.get(c))
+  }
+
   pub fn get_mut(&mut self, c: DDD) -> Option<&mut Block> {
     let chunk_coord = Self::get_chunk_coord(c);
-    self.chunks.get_mut(&chunk_coord).map(|chunk| &mut chunk.grid[c])
+    self.chunks.get_mut(&chunk_coord).and_then(|chunk| chunk.get_mut(c))
+  }
+
+  pub fn set(&mut self, c: DDD, block: Block) -> Option<()> {
+    let chunk_coord = Self::get_chunk_coord(c);
+    self.chunks.get_mut(&chunk_coord).and_then(|chunk| chunk.set(c, block))
   }
 
   pub fn get_light_level(&self, c: DDD) -> Option<LightLevel> {
     let chunk_coord = Self::get_chunk_coord(c);
-    self.chunks.get(&chunk_coord).and_then(|chunk| {
-      if c.in_bounds(&chunk.light_map.bounds) {
-        Some(chunk.light_map[c])
-      } else {
-        None
-      }
-    })
+    self.chunks.get(&chunk_coord).and_then(|chunk| chunk.get_light_level(c))
   }
 
   pub fn set_light_level(&mut self, c: DDD, light_level: LightLevel) -> Option<()> {
     let chunk_coord = Self::get_chunk_coord(c);
-    self.chunks.get_mut(&chunk_coord).map(|chunk| {
-      chunk.light_map[c] = light_level;
+    self
+      .chunks
+      .get_mut(&chunk_coord)
+      .and_then(|chunk| chunk.set_light_level(c, light_level))
+  }
+
+  pub fn get_biome(&self, c: DDD) -> Option<BiomeId> {
+    let chunk_coord = Self::get_chunk_coord(c);
+    self.chunks.get(&chunk_coord).and_then(|chunk| {
+      if (c.0, c.2).in_bounds(&chunk.biomes.bounds) {
+        Some(chunk.biomes[(c.0, c.2)])
+      } else {
+        None
+      }
     })
   }
 }
@@ -1,12 +1,17 @@
 use crate::ecs::components::blocks::animation::Animation;
 use crate::ecs::components::blocks::block_id::BlockId;
+use crate::ecs::components::blocks::registry::BlockDefinition;
+use crate::ecs::components::item::registry::ItemDefinition;
 use crate::ecs::components::blocks::BlockMeta;
-use crate::ecs::components::item::ItemId;
+use crate::ecs::components::chunk::WorldHeight;
 use crate::ecs::resources::light::LightLevel;
+use crate::ecs::resources::player::PlayerInventory;
 use crate::util::array::{DD, DDD};
 use bevy::prelude::*;
+use bevy::utils::HashMap;
 use bevy_renet::renet::{ChannelConfig, ReliableChannelConfig, RenetConnectionConfig, UnreliableChannelConfig};
 use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
 use std::fmt::{Display, Formatter};
 use std::time::Duration;
 
@@ -21,12 +26,34 @@ use std::time::Duration;
 // --  #   #    #  #   #  #  #   #      #     #  #      #    #    #          #     #  #     --
 // -- ###  #    #   ###   #   #  #####  #     #  #####  #    #    #          #     #  ##### --
 // -------------------------------------------------------------------------------------------
+// Never changes, so that builds speaking another protocol still get through and can be told why they are turned away
 pub const PROTOCOL_ID: u64 = 42;
+// Bumped with every change to the messages, checked when the player authenticates
+pub const PROTOCOL_VERSION: u32 = 6;
 pub const RELIABLE_CHANNEL_MAX_LENGTH: u64 = 10240;
+// Compressed chunks are cut into pieces of this size, a chunk can be as large as it needs to be
+pub const CHUNK_FRAGMENT_SIZE: usize = 8192;
+// In chunks, the server may grant less than a client asks for
+pub const MAX_VIEW_DISTANCE: u32 = 16;
+// Frames the server sends per second unless configured otherwise, remote players are interpolated in between
+pub const DEFAULT_TICK_RATE: f64 = 60.0;
+// Frames both sides remember, a frame acknowledged longer ago than this is no longer used as a baseline
+pub const FRAME_HISTORY: u32 = 64;
+// Positions travel in fractions of a block this fine
+pub const POSITION_SCALE: f32 = 256.0;
+// Players report their position this far below their eyes
+pub const EYE_HEIGHT: f32 = 1.5;
+// How far from their eyes players can reach blocks
+pub const REACH_DISTANCE: f32 = 5.0;
+// Blocks per second, the fastest players can walk or fly without the server correcting them
+pub const MAX_PLAYER_SPEED: f32 = 6.0;
+// Characters in a single chat message
+pub const MAX_CHAT_LENGTH: usize = 256;
 
 pub enum ServerChannel {
   GameEvent,
   GameFrame,
+  ChunkStream,
 }
 
 impl ServerChannel {
@@ -34,6 +61,7 @@ impl ServerChannel {
     match self {
       Self::GameEvent => 0,
       Self::GameFrame => 1,
+      Self::ChunkStream => 2,
     }
   }
 
@@ -54,6 +82,16 @@ impl ServerChannel {
         ..Default::default()
       }
       .into(),
+      // Carries ChunkStreamMessage only, the server paces it so that it doesn't starve game events
+      ReliableChannelConfig {
+        channel_id: Self::ChunkStream.id(),
+        message_resend_time: Duration::from_millis(100),
+        max_message_size: CHUNK_FRAGMENT_SIZE as u64 + 64,
+        packet_budget: (CHUNK_FRAGMENT_SIZE as u64 + 64) * 2,
+        message_send_queue_size: 4096,
+        message_receive_queue_size: 4096,
+      }
+      .into(),
     ]
   }
 }
@@ -68,6 +106,10 @@ type TranslationRotation = (Vec3, PolarRotation);
 
 #[derive(Debug, Serialize, Deserialize, Component)]
 pub enum ServerMessage {
+  // Sent right before the server drops the client. Stays the first variant, so that any build can read it.
+  Disconnect {
+    reason: String,
+  },
   PlayerSpawn {
     entity: Entity,
     id: u64,
@@ -75,6 +117,11 @@ pub enum ServerMessage {
   },
   AuthConfirmed {
     translation: TranslationRotation,
+    inventory: PlayerInventory,
+    world_height: WorldHeight,
+    view_distance: u32,
+    tick_rate: f64,
+    motd: String,
   },
   PlayerDespawn {
     id: u64,
@@ -86,9 +133,6 @@ pub enum ServerMessage {
     location: DDD,
     block_transfer: BlockTransfer,
   },
-  ChunkData {
-    chunk: Vec<u8>,
-  },
   Relight {
     relights: Vec<(DDD, LightLevel)>,
   },
@@ -102,59 +146,287 @@ pub enum ServerMessage {
     location: DDD,
     animation: Animation,
   },
-  ItemAdd {
-    item: ItemId,
-    quant: u32,
+  // The server refused a move, the player is put back where the server has them
+  PlayerCorrection {
+    translation: Vec3,
+  },
+  Inventory {
+    inventory: PlayerInventory,
+  },
+  // Without a sender it comes from the server itself: joins, leaves and the operator console
+  Chat {
+    sender: Option<String>,
+    message: String,
+  },
+  // One for every block in id order, all of them right before AuthConfirmed
+  BlockDefinition {
+    definition: BlockDefinition,
+  },
+  // Same for items, sent ahead of the blocks
+  ItemDefinition {
+    definition: ItemDefinition,
   },
 }
 
 impl Display for ServerMessage {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
     match self {
+      ServerMessage::Disconnect { .. } => f.write_str("Disconnect"),
       ServerMessage::PlayerSpawn { .. } => f.write_str("PlayerSpawn"),
       ServerMessage::AuthConfirmed { .. } => f.write_str("AuthConfirmed"),
       ServerMessage::PlayerDespawn { .. } => f.write_str("PlayerDespawn"),
       ServerMessage::BlockRemove { .. } => f.write_str("BlockRemove"),
       ServerMessage::BlockPlace { .. } => f.write_str("BlockPlace"),
-      ServerMessage::ChunkData { .. } => f.write_str("ChunkData"),
       ServerMessage::Relight { .. } => f.write_str("Relight"),
       ServerMessage::Functor { .. } => f.write_str("Functor"),
       ServerMessage::AnimationStart { .. } => f.write_str("AnimationStart"),
-      ServerMessage::ItemAdd { .. } => f.write_str("ItemAdd"),
+      ServerMessage::PlayerCorrection { .. } => f.write_str("PlayerCorrection"),
+      ServerMessage::Inventory { .. } => f.write_str("Inventory"),
+      ServerMessage::Chat { .. } => f.write_str("Chat"),
+      ServerMessage::BlockDefinition { .. } => f.write_str("BlockDefinition"),
+      ServerMessage::ItemDefinition { .. } => f.write_str("ItemDefinition"),
     }
   }
 }
 
+// Angles are stored in 1/65536 of a turn, so that they wrap around on their own
+fn quantize_angle(angle: f32) -> u16 {
+  ((angle / TAU).rem_euclid(1.0) * 65536.0).round() as u32 as u16
+}
+
+fn dequantize_angle(angle: u16) -> f32 {
+  angle as f32 / 65536.0 * TAU
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct QuantizedTransform {
+  pub translation: [i32; 3],
+  pub phi: u16,
+  pub theta: u16,
+}
+
+impl QuantizedTransform {
+  pub fn new(translation: Vec3, rotation: PolarRotation) -> Self {
+    let translation = (translation * POSITION_SCALE).round();
+    Self {
+      translation: [translation.x as i32, translation.y as i32, translation.z as i32],
+      phi: quantize_angle(rotation.phi),
+      theta: quantize_angle(rotation.theta),
+    }
+  }
+
+  pub fn translation(&self) -> Vec3 {
+    Vec3::new(
+      self.translation[0] as f32,
+      self.translation[1] as f32,
+      self.translation[2] as f32,
+    ) / POSITION_SCALE
+  }
+
+  pub fn rotation(&self) -> PolarRotation {
+    PolarRotation {
+      phi: dequantize_angle(self.phi),
+      theta: dequantize_angle(self.theta),
+    }
+  }
+
+  // Angles take the short way around
+  pub fn lerp(&self, other: &Self, alpha: f32) -> TranslationRotation {
+    let angle = |from: u16, to: u16| from.wrapping_add((to.wrapping_sub(from) as i16 as f32 * alpha).round() as i16 as u16);
+    let rotation = Self {
+      translation: self.translation,
+      phi: angle(self.phi, other.phi),
+      theta: angle(self.theta, other.theta),
+    }
+    .rotation();
+    (self.translation().lerp(other.translation(), alpha), rotation)
+  }
+}
+
+// Where every player in view is on a given tick
+pub type Snapshot = HashMap<u64, QuantizedTransform>;
+
+// A player's state relative to the same player in the frame's baseline
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub enum PlayerState {
+  Unchanged,
+  Delta { translation: [i16; 3], phi: i16, theta: i16 },
+  Full(QuantizedTransform),
+}
+
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct NetworkedEntities {
   pub players: Vec<u64>,
-  pub translations: Vec<TranslationRotation>,
+  pub states: Vec<PlayerState>,
 }
 
+// Players missing from a frame are out of the client's view. Frames without a baseline are sent in full.
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct NetworkFrame {
   pub tick: u32,
+  pub baseline: Option<u32>,
   pub entities: NetworkedEntities,
 }
 
+impl NetworkFrame {
+  pub fn encode(tick: u32, snapshot: &Snapshot, baseline: Option<(u32, &Snapshot)>) -> Self {
+    let mut entities = NetworkedEntities::default();
+    for (id, transform) in snapshot.iter() {
+      let state = match baseline.and_then(|(_, baseline)| baseline.get(id)) {
+        Some(base) if base == transform => PlayerState::Unchanged,
+        Some(base) => {
+          let delta = [0, 1, 2].map(|i| i16::try_from(transform.translation[i] as i64 - base.translation[i] as i64));
+          match delta {
+            [Ok(x), Ok(y), Ok(z)] => PlayerState::Delta {
+              translation: [x, y, z],
+              phi: transform.phi.wrapping_sub(base.phi) as i16,
+              theta: transform.theta.wrapping_sub(base.theta) as i16,
+            },
+            _ => PlayerState::Full(*transform),
+          }
+        }
+        None => PlayerState::Full(*transform),
+      };
+      entities.players.push(*id);
+      entities.states.push(state);
+    }
+    Self {
+      tick,
+      baseline: baseline.map(|(tick, _)| tick),
+      entities,
+    }
+  }
+
+  // None if the frame refers to a player the baseline doesn't have
+  pub fn decode(&self, baseline: Option<&Snapshot>) -> Option<Snapshot> {
+    let mut snapshot = Snapshot::default();
+    for (id, state) in self.entities.players.iter().zip(self.entities.states.iter()) {
+      let transform = match *state {
+        PlayerState::Full(transform) => transform,
+        PlayerState::Unchanged => *baseline?.get(id)?,
+        PlayerState::Delta { translation, phi, theta } => {
+          let base = baseline?.get(id)?;
+          QuantizedTransform {
+            translation: [0, 1, 2].map(|i| base.translation[i].wrapping_add(translation[i] as i32)),
+            phi: base.phi.wrapping_add(phi as u16),
+            theta: base.theta.wrapping_add(theta as u16),
+          }
+        }
+      };
+      snapshot.insert(*id, transform);
+    }
+    Some(snapshot)
+  }
+}
+
+// Tells the server which frame the client has, so that the following ones can be encoded against it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FrameAck {
+  pub tick: u32,
+}
+
+// Reads the reason out of a Disconnect sent by a server of any version
+pub fn read_disconnect_reason(message: &[u8]) -> Option<String> {
+  match bincode::deserialize::<(u32, String)>(message) {
+    Ok((0, reason)) => Some(reason),
+    _ => None,
+  }
+}
+
+// Reads the version out of a PlayerAuth sent by a client of any version
+pub fn read_auth_version(message: &[u8]) -> Option<u32> {
+  match bincode::deserialize::<(u32, u32)>(message) {
+    Ok((0, version)) => Some(version),
+    _ => None,
+  }
+}
+
+// Unloads travel together with chunk data so that they can't overtake a chunk that is still on its way
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ChunkStreamMessage {
+  Fragment(ChunkFragment),
+  Unload { chunk_coord: DD },
+}
+
+// A piece of a compressed chunk
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChunkFragment {
+  pub chunk_coord: DD,
+  pub index: u16,
+  pub count: u16,
+  pub data: Vec<u8>,
+}
+
+pub fn fragment_chunk(chunk_coord: DD, data: &[u8]) -> Vec<ChunkFragment> {
+  let count = ((data.len() + CHUNK_FRAGMENT_SIZE - 1) / CHUNK_FRAGMENT_SIZE).max(1);
+  (0..count)
+    .map(|index| ChunkFragment {
+      chunk_coord,
+      index: index as u16,
+      count: count as u16,
+      data: data[index * CHUNK_FRAGMENT_SIZE..((index + 1) * CHUNK_FRAGMENT_SIZE).min(data.len())].to_vec(),
+    })
+    .collect()
+}
+
+// Collects fragments until a chunk is complete. A chunk that is sent again starts over.
+#[derive(Default, Resource)]
+pub struct ChunkReassembly {
+  partial: HashMap<DD, Vec<Option<Vec<u8>>>>,
+}
+
+impl ChunkReassembly {
+  pub fn push(&mut self, fragment: ChunkFragment) -> Option<Vec<u8>> {
+    let count = fragment.count as usize;
+    let index = fragment.index as usize;
+    if index >= count {
+      return None;
+    }
+    let pieces = self.partial.entry(fragment.chunk_coord).or_default();
+    if pieces.len() != count || index == 0 {
+      *pieces = vec![None; count];
+    }
+    pieces[index] = Some(fragment.data);
+    if pieces.iter().any(|piece| piece.is_none()) {
+      return None;
+    }
+    let pieces = self.partial.remove(&fragment.chunk_coord).unwrap();
+    Some(pieces.into_iter().flatten().flatten().collect())
+  }
+
+  pub fn forget(&mut self, chunk_coord: DD) {
+    self.partial.remove(&chunk_coord);
+  }
+}
+
 pub enum ClientChannel {
   ClientCommand,
+  FrameAck,
 }
 
 impl ClientChannel {
   pub fn id(&self) -> u8 {
     match self {
       Self::ClientCommand => 0,
+      Self::FrameAck => 1,
     }
   }
 
   pub fn channels_config() -> Vec<ChannelConfig> {
-    vec![ReliableChannelConfig {
-      channel_id: Self::ClientCommand.id(),
-      message_resend_time: Duration::ZERO,
-      ..Default::default()
-    }
-    .into()]
+    vec![
+      ReliableChannelConfig {
+        channel_id: Self::ClientCommand.id(),
+        message_resend_time: Duration::ZERO,
+        ..Default::default()
+      }
+      .into(),
+      // A lost ack only means that the next frames are encoded against an older baseline
+      UnreliableChannelConfig {
+        channel_id: Self::FrameAck.id(),
+        ..Default::default()
+      }
+      .into(),
+    ]
   }
 }
 
@@ -185,10 +457,55 @@ pub enum FunctorType {
   InternalInventory,
 }
 
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum TransactionType {
+  // Exchange the contents of two slots
+  Swap,
+  // Move half of a stack into an empty slot
+  Split,
+  // Move a whole stack onto an empty slot or a stack of the same kind
+  Merge,
+}
+
+// Local slots belong to the functor's own inventory, foreign ones to the player acting on it
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum InventoryIndex {
+  Local(usize),
+  Foreign(usize),
+}
+
+// Inventory interfaces number player slots after the functor's ones, starting at this offset
+pub const FOREIGN_INVENTORY_OFFSET: usize = 1000;
+
+impl From<usize> for InventoryIndex {
+  fn from(index: usize) -> Self {
+    if index < FOREIGN_INVENTORY_OFFSET {
+      InventoryIndex::Local(index)
+    } else {
+      InventoryIndex::Foreign(index - FOREIGN_INVENTORY_OFFSET)
+    }
+  }
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum FunctorAction {
+  InventoryTransaction {
+    _type: TransactionType,
+    from: InventoryIndex,
+    to: InventoryIndex,
+  },
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub enum PlayerCommand {
+  // Stays the first variant with the version in front, so that the version of any build can be read
   PlayerAuth {
+    version: u32,
     nickname: String,
+    // Reserves the nickname the first time it is given, is required for it from then on. Only secure mode encrypts
+    // the connection, otherwise it travels in plaintext.
+    password: Option<String>,
+    view_distance: u32,
   },
   PlayerMove {
     translation: TranslationRotation,
@@ -199,6 +516,11 @@ pub enum PlayerCommand {
   BlockPlace {
     location: DDD,
     block_transfer: BlockTransfer,
+    slot: usize,
+  },
+  InventorySwap {
+    from: usize,
+    to: usize,
   },
   RequestChunk {
     chunk_coord: DD,
@@ -207,6 +529,13 @@ pub enum PlayerCommand {
     location: DDD,
     functor: FunctorType,
   },
+  FunctorAction {
+    location: DDD,
+    functor: FunctorAction,
+  },
+  CloseFunctor {
+    location: DDD,
+  },
   AnimationStart {
     location: DDD,
     animation: Animation,
@@ -214,4 +543,147 @@ pub enum PlayerCommand {
   InitiateInWorldCraft {
     location: DDD,
   },
+  Chat {
+    message: String,
+  },
+}
+
+impl Display for PlayerCommand {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      PlayerCommand::PlayerAuth { .. } => f.write_str("PlayerAuth"),
+      PlayerCommand::PlayerMove { .. } => f.write_str("PlayerMove"),
+      PlayerCommand::BlockRemove { .. } => f.write_str("BlockRemove"),
+      PlayerCommand::BlockPlace { .. } => f.write_str("BlockPlace"),
+      PlayerCommand::InventorySwap { .. } => f.write_str("InventorySwap"),
+      PlayerCommand::RequestChunk { .. } => f.write_str("RequestChunk"),
+      PlayerCommand::RequestFunctor { .. } => f.write_str("RequestFunctor"),
+      PlayerCommand::FunctorAction { .. } => f.write_str("FunctorAction"),
+      PlayerCommand::CloseFunctor { .. } => f.write_str("CloseFunctor"),
+      PlayerCommand::AnimationStart { .. } => f.write_str("AnimationStart"),
+      PlayerCommand::InitiateInWorldCraft { .. } => f.write_str("InitiateInWorldCraft"),
+      PlayerCommand::Chat { .. } => f.write_str("Chat"),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn transform(translation: [i32; 3], phi: u16, theta: u16) -> QuantizedTransform {
+    QuantizedTransform { translation, phi, theta }
+  }
+
+  fn snapshot(players: &[(u64, QuantizedTransform)]) -> Snapshot {
+    players.iter().copied().collect()
+  }
+
+  #[test]
+  fn frame_without_baseline_is_sent_in_full() {
+    let current = snapshot(&[(1, transform([10, 20, 30], 5, 6)), (2, transform([-4, 0, 9], 0, u16::MAX))]);
+    let frame = NetworkFrame::encode(7, &current, None);
+    assert_eq!(frame.baseline, None);
+    assert!(frame.entities.states.iter().all(|state| matches!(state, PlayerState::Full(_))));
+    assert_eq!(frame.decode(None), Some(current));
+  }
+
+  #[test]
+  fn frame_against_baseline_round_trips() {
+    let baseline = snapshot(&[
+      (1, transform([10, 20, 30], 5, 6)),
+      (2, transform([0, 0, 0], u16::MAX - 2, 3)),
+      (3, transform([0, 0, 0], 0, 0)),
+      (4, transform([1, 1, 1], 1, 1)),
+    ]);
+    // Unchanged, a small move with angles wrapping around, a move too far for a delta, a new player. 4 went away.
+    let current = snapshot(&[
+      (1, transform([10, 20, 30], 5, 6)),
+      (2, transform([-300, 12, 7], 4, u16::MAX)),
+      (3, transform([100_000, 0, -100_000], 0, 0)),
+      (5, transform([8, 8, 8], 8, 8)),
+    ]);
+    let frame = NetworkFrame::encode(9, &current, Some((8, &baseline)));
+    assert_eq!(frame.baseline, Some(8));
+    for (id, state) in frame.entities.players.iter().zip(frame.entities.states.iter()) {
+      match id {
+        1 => assert!(matches!(state, PlayerState::Unchanged)),
+        2 => assert!(matches!(state, PlayerState::Delta { .. })),
+        _ => assert!(matches!(state, PlayerState::Full(_))),
+      }
+    }
+    assert_eq!(frame.decode(Some(&baseline)), Some(current));
+  }
+
+  #[test]
+  fn frame_against_another_baseline_is_refused() {
+    let baseline = snapshot(&[(1, transform([0, 0, 0], 0, 0)), (2, transform([5, 5, 5], 5, 5))]);
+    let current = snapshot(&[(1, transform([0, 0, 0], 0, 0)), (2, transform([6, 5, 5], 5, 5))]);
+    let frame = NetworkFrame::encode(3, &current, Some((2, &baseline)));
+    // The client no longer has the baseline, or has one without these players
+    assert_eq!(frame.decode(None), None);
+    assert_eq!(frame.decode(Some(&snapshot(&[(2, transform([5, 5, 5], 5, 5))]))), None);
+    assert_eq!(frame.decode(Some(&snapshot(&[(1, transform([0, 0, 0], 0, 0))]))), None);
+  }
+
+  fn chunk_data(len: usize, salt: u8) -> Vec<u8> {
+    (0..len).map(|i| (i as u8).wrapping_mul(31).wrapping_add(salt)).collect()
+  }
+
+  #[test]
+  fn fragments_reassemble_into_the_chunk() {
+    for len in [0, 1, CHUNK_FRAGMENT_SIZE, CHUNK_FRAGMENT_SIZE * 2 + 100] {
+      let data = chunk_data(len, 0);
+      let fragments = fragment_chunk((3, -4), &data);
+      assert_eq!(fragments.len(), ((len + CHUNK_FRAGMENT_SIZE - 1) / CHUNK_FRAGMENT_SIZE).max(1));
+      let mut reassembly = ChunkReassembly::default();
+      let count = fragments.len();
+      let results: Vec<_> = fragments.into_iter().map(|fragment| reassembly.push(fragment)).collect();
+      assert!(results[..count - 1].iter().all(Option::is_none));
+      assert_eq!(results[count - 1].as_ref(), Some(&data));
+    }
+  }
+
+  #[test]
+  fn chunks_in_flight_together_stay_apart() {
+    let first = chunk_data(CHUNK_FRAGMENT_SIZE + 1, 1);
+    let second = chunk_data(CHUNK_FRAGMENT_SIZE * 2, 2);
+    let mut first_fragments = fragment_chunk((0, 0), &first).into_iter();
+    let mut second_fragments = fragment_chunk((0, 1), &second).into_iter();
+    let mut reassembly = ChunkReassembly::default();
+    assert_eq!(reassembly.push(first_fragments.next().unwrap()), None);
+    assert_eq!(reassembly.push(second_fragments.next().unwrap()), None);
+    assert_eq!(reassembly.push(first_fragments.next().unwrap()), Some(first));
+    assert_eq!(reassembly.push(second_fragments.next().unwrap()), Some(second));
+  }
+
+  #[test]
+  fn chunk_sent_again_starts_over() {
+    let old = chunk_data(CHUNK_FRAGMENT_SIZE * 2, 1);
+    let new = chunk_data(CHUNK_FRAGMENT_SIZE * 3, 2);
+    let mut reassembly = ChunkReassembly::default();
+    assert_eq!(reassembly.push(fragment_chunk((0, 0), &old).remove(0)), None);
+    let mut result = None;
+    for fragment in fragment_chunk((0, 0), &new) {
+      result = reassembly.push(fragment);
+    }
+    assert_eq!(result, Some(new));
+  }
+
+  #[test]
+  fn forgotten_and_invalid_fragments_complete_nothing() {
+    let data = chunk_data(CHUNK_FRAGMENT_SIZE + 1, 0);
+    let mut fragments = fragment_chunk((0, 0), &data);
+    let mut reassembly = ChunkReassembly::default();
+    assert_eq!(reassembly.push(fragments.remove(0)), None);
+    reassembly.forget((0, 0));
+    assert_eq!(reassembly.push(fragments.remove(0)), None);
+    let invalid = ChunkFragment {
+      chunk_coord: (0, 0),
+      index: 2,
+      count: 2,
+      data: vec![1],
+    };
+    assert_eq!(reassembly.push(invalid), None);
+  }
 }
@@ -1,16 +1,135 @@
 use crate::ecs::components::blocks::block_id::BlockId;
-use crate::ecs::components::item::ItemId;
-use crate::recipes::in_world::populate_in_world_recipes;
+use crate::ecs::components::blocks::QuantifiedBlockOrItem;
+use crate::ecs::resources::registries::Registries;
 use crate::util::array::{Array, DDD};
 use bevy::ecs::system::Resource;
+use bevy::utils::hashbrown::{HashMap, HashSet};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
 
-pub mod in_world;
+pub const DEFAULT_RECIPES: &str = include_str!("../../data/recipes.toml");
 
 #[derive(Clone)]
 pub struct SimpleRecipe {
+  pub name: String,
   pub from: Array<DDD, BlockId>,
   pub to: Array<DDD, BlockId>,
-  pub item: Option<ItemId>,
+  // Goes to the player who crafted it
+  pub output: Vec<QuantifiedBlockOrItem>,
+}
+
+// Layers from the bottom up, rows along z, a character per block along x
+type Pattern = Vec<Vec<String>>;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RecipeDefinition {
+  name: String,
+  from: Pattern,
+  to: Pattern,
+  key: BTreeMap<String, String>,
+  #[serde(default)]
+  output: BTreeMap<String, u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RecipeFile {
+  #[serde(default)]
+  recipe: Vec<RecipeDefinition>,
+}
+
+// Blocks of the pattern indexed [y][z][x] and its size along x, y and z
+fn parse_pattern(what: &str, pattern: &Pattern, key: &HashMap<char, BlockId>) -> Result<(Vec<Vec<Vec<BlockId>>>, DDD), String> {
+  let height = pattern.len();
+  let depth = pattern.first().map(|layer| layer.len()).unwrap_or(0);
+  let width = pattern
+    .first()
+    .and_then(|layer| layer.first())
+    .map(|row| row.chars().count())
+    .unwrap_or(0);
+  if width == 0 || height == 0 || depth == 0 {
+    return Err(format!("{} is empty", what));
+  }
+  let mut blocks = vec![];
+  for (y, layer) in pattern.iter().enumerate() {
+    if layer.len() != depth {
+      return Err(format!("{} layer {} has {} rows, the first one has {}", what, y, layer.len(), depth));
+    }
+    let mut rows = vec![];
+    for (z, row) in layer.iter().enumerate() {
+      if row.chars().count() != width {
+        return Err(format!(
+          "{} layer {} row {} is {} blocks long, the first one is {}",
+          what,
+          y,
+          z,
+          row.chars().count(),
+          width
+        ));
+      }
+      let row = row
+        .chars()
+        .map(|c| key.get(&c).copied().ok_or_else(|| format!("{} uses {:?}, which the key doesn't have", what, c)))
+        .collect::<Result<Vec<_>, _>>()?;
+      rows.push(row);
+    }
+    blocks.push(rows);
+  }
+  Ok((blocks, (width as i32, height as i32, depth as i32)))
+}
+
+impl SimpleRecipe {
+  fn new(definition: RecipeDefinition, registries: &Registries) -> Result<Self, String> {
+    let blocks = &registries.blocks;
+    let mut key = HashMap::new();
+    for (symbol, name) in definition.key.iter() {
+      let mut chars = symbol.chars();
+      let (Some(c), None) = (chars.next(), chars.next()) else {
+        return Err(format!("Key {:?} has to be a single character", symbol));
+      };
+      let block = blocks
+        .by_name(name)
+        .ok_or_else(|| format!("Key {:?} stands for {}, which is not a block", symbol, name))?;
+      key.insert(c, block);
+    }
+    let (from, from_size) = parse_pattern("from", &definition.from, &key)?;
+    let (to, to_size) = parse_pattern("to", &definition.to, &key)?;
+    if from_size != to_size {
+      return Err(format!(
+        "from is {}x{}x{} blocks but to is {}x{}x{}, they have to be the same size",
+        from_size.0, from_size.1, from_size.2, to_size.0, to_size.1, to_size.2
+      ));
+    }
+    // Crafting starts on a block of the pattern, air would match anywhere
+    if from.iter().flatten().flatten().all(|block| *block == BlockId::Air) {
+      return Err("from needs at least one block that isn't air".to_string());
+    }
+    let output = definition
+      .output
+      .iter()
+      .map(|(name, count)| {
+        let block_or_item = registries
+          .by_name(name)
+          .ok_or_else(|| format!("output {} is neither a block nor an item", name))?;
+        if *count == 0 {
+          return Err(format!("output {} has to be at least 1", name));
+        }
+        Ok(QuantifiedBlockOrItem {
+          block_or_item,
+          quant: *count,
+        })
+      })
+      .collect::<Result<_, String>>()?;
+    let bounds = ((0, 0, 0), (from_size.0 - 1, from_size.1 - 1, from_size.2 - 1));
+    Ok(Self {
+      name: definition.name,
+      from: Array::new_init(bounds, |(x, y, z)| from[y as usize][z as usize][x as usize]),
+      to: Array::new_init(bounds, |(x, y, z)| to[y as usize][z as usize][x as usize]),
+      output,
+    })
+  }
 }
 
 #[derive(Resource)]
@@ -18,10 +137,25 @@ pub struct Recipes {
   pub recipes: Vec<SimpleRecipe>,
 }
 
-impl Default for Recipes {
-  fn default() -> Self {
-    Self {
-      recipes: populate_in_world_recipes().into_iter().collect(),
+impl Recipes {
+  // The built-in recipes when there is no path
+  pub fn load(path: Option<&Path>, registries: &Registries) -> Result<Self, String> {
+    let source = path.unwrap_or(Path::new("built-in"));
+    let text = match path {
+      Some(path) => std::fs::read_to_string(path).map_err(|error| format!("Failed to read {:?}: {}", path, error))?,
+      None => DEFAULT_RECIPES.to_string(),
+    };
+    let file: RecipeFile =
+      toml::from_str(&text).map_err(|error| format!("Invalid recipes in {:?}: {}", source, error))?;
+    let mut names = HashSet::new();
+    let mut recipes = vec![];
+    for definition in file.recipe {
+      if !names.insert(definition.name.clone()) {
+        return Err(format!("Recipe {} in {:?} is defined twice", definition.name, source));
+      }
+      let name = definition.name.clone();
+      recipes.push(SimpleRecipe::new(definition, registries).map_err(|error| format!("Recipe {} in {:?}: {}", name, source, error))?);
     }
+    Ok(Self { recipes })
   }
 }
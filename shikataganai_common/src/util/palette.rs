@@ -0,0 +1,177 @@
+use crate::util::array::{ArrayIndex, Bounds, DDD};
+use serde::{Deserialize, Serialize};
+
+// One value per voxel, stored as an index into a list of the distinct values that are in use. Indices take as many
+// bits as the list needs and never straddle two words, so a uniform section costs a single value and a section of
+// stone with a handful of ores a few bits per voxel.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Palette<T> {
+  bounds: Bounds<DDD>,
+  values: Vec<T>,
+  // Voxels using every value, an entry that drops to zero is reused by the next new value
+  counts: Vec<u32>,
+  bits: u32,
+  words: Vec<u64>,
+}
+
+impl<T: Copy + PartialEq> Palette<T> {
+  pub fn new(bounds: Bounds<DDD>, value: T) -> Self {
+    Self {
+      bounds,
+      values: vec![value],
+      counts: vec![ArrayIndex::size(&bounds) as u32],
+      bits: 0,
+      words: vec![],
+    }
+  }
+
+  pub fn bounds(&self) -> Bounds<DDD> {
+    self.bounds
+  }
+
+  pub fn get(&self, c: DDD) -> &T {
+    &self.values[self.slot(c.idx(&self.bounds))]
+  }
+
+  pub fn set(&mut self, c: DDD, value: T) {
+    let i = c.idx(&self.bounds);
+    let old = self.slot(i);
+    if self.values[old] == value {
+      return;
+    }
+    self.counts[old] -= 1;
+    let new = match self.values.iter().position(|v| *v == value) {
+      Some(new) => new,
+      None => match self.counts.iter().position(|count| *count == 0) {
+        Some(free) => {
+          self.values[free] = value;
+          free
+        }
+        None => {
+          self.values.push(value);
+          self.counts.push(0);
+          if self.values.len() > 1 << self.bits {
+            self.grow();
+          }
+          self.values.len() - 1
+        }
+      },
+    };
+    self.counts[new] += 1;
+    self.put(i, new);
+  }
+
+  // The value of every voxel, if they all share one
+  pub fn uniform(&self) -> Option<T> {
+    let mut used = self.counts.iter().zip(self.values.iter()).filter(|(count, _)| **count > 0);
+    match (used.next(), used.next()) {
+      (Some((_, value)), None) => Some(*value),
+      _ => None,
+    }
+  }
+
+  fn slot(&self, i: usize) -> usize {
+    if self.bits == 0 {
+      return 0;
+    }
+    let per_word = 64 / self.bits as usize;
+    let shift = (i % per_word) * self.bits as usize;
+    ((self.words[i / per_word] >> shift) & ((1 << self.bits) - 1)) as usize
+  }
+
+  fn put(&mut self, i: usize, slot: usize) {
+    if self.bits == 0 {
+      return;
+    }
+    let per_word = 64 / self.bits as usize;
+    let shift = (i % per_word) * self.bits as usize;
+    let mask = ((1u64 << self.bits) - 1) << shift;
+    let word = &mut self.words[i / per_word];
+    *word = (*word & !mask) | ((slot as u64) << shift);
+  }
+
+  // Adds a bit to every index, only happens when all entries of the palette are in use
+  fn grow(&mut self) {
+    let size = ArrayIndex::size(&self.bounds);
+    let slots: Vec<usize> = (0..size).map(|i| self.slot(i)).collect();
+    self.bits += 1;
+    let per_word = 64 / self.bits as usize;
+    self.words = vec![0; (size + per_word - 1) / per_word];
+    for (i, slot) in slots.into_iter().enumerate() {
+      self.put(i, slot);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::util::array::Array;
+  use rand::rngs::StdRng;
+  use rand::{Rng, SeedableRng};
+
+  const BOUNDS: Bounds<DDD> = ((0, -16, 32), (15, -1, 47));
+
+  fn all() -> Vec<DDD> {
+    let mut coords = vec![];
+    let mut i = BOUNDS.0;
+    loop {
+      coords.push(i);
+      i = match i.next(&BOUNDS) {
+        None => break,
+        Some(i) => i,
+      }
+    }
+    coords
+  }
+
+  #[test]
+  fn starts_uniform() {
+    let palette = Palette::new(BOUNDS, 7u32);
+    assert_eq!(palette.uniform(), Some(7));
+    assert!(all().into_iter().all(|c| *palette.get(c) == 7));
+  }
+
+  #[test]
+  fn set_get_through_every_width() {
+    let mut palette = Palette::new(BOUNDS, 0u32);
+    let mut expected = Array::new_init(BOUNDS, |_| 0u32);
+    // Enough distinct values to grow the indices up to 9 bits
+    for (n, c) in all().into_iter().enumerate().take(300) {
+      palette.set(c, n as u32 + 1);
+      expected[c] = n as u32 + 1;
+      assert_eq!(*palette.get(c), n as u32 + 1);
+    }
+    assert!(all().into_iter().all(|c| *palette.get(c) == expected[c]));
+    assert_eq!(palette.uniform(), None);
+  }
+
+  #[test]
+  fn matches_a_plain_array() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let coords = all();
+    let mut palette = Palette::new(BOUNDS, 0u32);
+    let mut expected = Array::new_init(BOUNDS, |_| 0u32);
+    for _ in 0..20000 {
+      let c = coords[rng.gen_range(0..coords.len())];
+      let value = rng.gen_range(0..40);
+      palette.set(c, value);
+      expected[c] = value;
+    }
+    assert!(coords.into_iter().all(|c| *palette.get(c) == expected[c]));
+  }
+
+  #[test]
+  fn reuses_entries_that_are_no_longer_used() {
+    let mut palette = Palette::new(BOUNDS, 0u32);
+    let c = BOUNDS.0;
+    for value in 1..100 {
+      palette.set(c, value);
+    }
+    // Every value but the last one went away again, their entries were reused instead of growing the palette
+    assert_eq!(*palette.get(c), 99);
+    assert!(palette.values.len() <= 2);
+    palette.set(c, 0);
+    assert_eq!(palette.uniform(), Some(0));
+  }
+}
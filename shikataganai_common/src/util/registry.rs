@@ -0,0 +1,44 @@
+use bevy::utils::hashbrown::HashMap;
+
+// Entries of a data file that are looked up by name and stored by id
+pub trait Definition {
+  fn name(&self) -> &str;
+  // Stands in for an entry the world has an id for but the data file no longer defines
+  fn missing(name: String) -> Self;
+}
+
+// Puts definitions in id order. The core ones come first, in order. Names in `known` keep their position, new ones
+// are appended and the ones that went missing get a placeholder, so ids already stored don't change meaning.
+pub fn assign_ids<D: Definition>(
+  kind: &str,
+  core: &[&str],
+  definitions: Vec<D>,
+  known: &[String],
+) -> Result<Vec<D>, String> {
+  let mut by_name = HashMap::new();
+  let mut order: Vec<String> = core.iter().map(|name| name.to_string()).collect();
+  order.extend(known.iter().skip(core.len()).cloned());
+  for definition in definitions {
+    let name = definition.name().to_string();
+    if !order.contains(&name) {
+      order.push(name.clone());
+    }
+    if by_name.insert(name.clone(), definition).is_some() {
+      return Err(format!("{} {} is defined twice", kind, name));
+    }
+  }
+  if let Some(name) = core.iter().find(|name| !by_name.contains_key(**name)) {
+    return Err(format!("{} {} is missing, the game can't do without it", kind, name));
+  }
+  Ok(
+    order
+      .into_iter()
+      .map(|name| {
+        by_name.remove(&name).unwrap_or_else(|| {
+          println!("{} {} is no longer defined, it stays in the world as a placeholder", kind, name);
+          D::missing(name)
+        })
+      })
+      .collect(),
+  )
+}
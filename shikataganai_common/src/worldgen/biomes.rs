@@ -0,0 +1,209 @@
+use crate::ecs::components::blocks::block_id::BlockId;
+use crate::util::array::{Array2d, DD};
+use crate::worldgen::{derive_seed, GenerationContext, GenerationPass};
+use noise::{NoiseFn, Perlin};
+use serde::{Deserialize, Serialize};
+use std::ops::Deref;
+
+const TEMPERATURE_SALT: u64 = 20;
+const HUMIDITY_SALT: u64 = 21;
+
+// Climate changes much slower than the terrain itself
+const CLIMATE_SCALE: f64 = 160.0;
+// Height profiles are averaged over a grid of samples around the column so that biome borders don't turn into cliffs
+const BLEND_RADIUS: i32 = 2;
+const BLEND_STEP: i32 = 4;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum BiomeId {
+  Plains,
+  Forest,
+  Desert,
+  Highlands,
+  Tundra,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct HeightProfile {
+  // Added to the island top, in blocks
+  pub offset: f64,
+  // Multiplies the island top noise
+  pub scale: f64,
+}
+
+#[derive(Debug)]
+pub struct OreEntry {
+  pub ore: BlockId,
+  pub one_in: u32,
+  pub min_y: i32,
+  pub max_y: i32,
+}
+
+#[derive(Debug)]
+pub struct Biome {
+  pub name: &'static str,
+  pub height: HeightProfile,
+  pub surface: BlockId,
+  pub filler: BlockId,
+  pub filler_depth: i32,
+  pub stone: BlockId,
+  pub ores: &'static [OreEntry],
+  // Multiplied into the grass texture by clients
+  pub grass_tint: [f32; 3],
+}
+
+static BIOMES: [Biome; 5] = [
+  Biome {
+    name: "Plains",
+    height: HeightProfile { offset: 0.0, scale: 1.0 },
+    surface: BlockId::Grass,
+    filler: BlockId::Dirt,
+    filler_depth: 2,
+    stone: BlockId::Cobble,
+    ores: &[OreEntry {
+      ore: BlockId::Iron,
+      one_in: 10,
+      min_y: 0,
+      max_y: 127,
+    }],
+    grass_tint: [0.55, 0.8, 0.35],
+  },
+  Biome {
+    name: "Forest",
+    height: HeightProfile { offset: 2.0, scale: 1.2 },
+    surface: BlockId::Grass,
+    filler: BlockId::Dirt,
+    filler_depth: 4,
+    stone: BlockId::Cobble,
+    ores: &[OreEntry {
+      ore: BlockId::Iron,
+      one_in: 12,
+      min_y: 0,
+      max_y: 127,
+    }],
+    grass_tint: [0.35, 0.65, 0.25],
+  },
+  Biome {
+    name: "Desert",
+    height: HeightProfile { offset: -2.0, scale: 0.5 },
+    surface: BlockId::Dirt,
+    filler: BlockId::Dirt,
+    filler_depth: 6,
+    stone: BlockId::Cobble,
+    ores: &[OreEntry {
+      ore: BlockId::Iron,
+      one_in: 16,
+      min_y: 0,
+      max_y: 40,
+    }],
+    grass_tint: [0.75, 0.7, 0.4],
+  },
+  Biome {
+    name: "Highlands",
+    height: HeightProfile { offset: 8.0, scale: 2.0 },
+    surface: BlockId::Grass,
+    filler: BlockId::Dirt,
+    filler_depth: 1,
+    stone: BlockId::Cobble,
+    ores: &[OreEntry {
+      ore: BlockId::Iron,
+      one_in: 6,
+      min_y: 0,
+      max_y: 127,
+    }],
+    grass_tint: [0.5, 0.65, 0.4],
+  },
+  Biome {
+    name: "Tundra",
+    height: HeightProfile { offset: 1.0, scale: 0.8 },
+    surface: BlockId::Grass,
+    filler: BlockId::Dirt,
+    filler_depth: 2,
+    stone: BlockId::Cobble,
+    ores: &[OreEntry {
+      ore: BlockId::Iron,
+      one_in: 10,
+      min_y: 0,
+      max_y: 60,
+    }],
+    grass_tint: [0.75, 0.85, 0.8],
+  },
+];
+
+impl Deref for BiomeId {
+  type Target = Biome;
+
+  #[inline]
+  fn deref(&self) -> &'static Self::Target {
+    &BIOMES[*self as usize]
+  }
+}
+
+// Picks biomes from two low frequency noise fields, temperature and humidity, both in [-1, 1]
+pub struct BiomeMap {
+  temperature: Perlin,
+  humidity: Perlin,
+}
+
+impl BiomeMap {
+  pub fn new(seed: u64) -> Self {
+    Self {
+      temperature: Perlin::new(derive_seed(seed, TEMPERATURE_SALT) as u32),
+      humidity: Perlin::new(derive_seed(seed, HUMIDITY_SALT) as u32),
+    }
+  }
+
+  pub fn climate(&self, c: DD) -> (f64, f64) {
+    let position = [c.0 as f64 / CLIMATE_SCALE, 0.0, c.1 as f64 / CLIMATE_SCALE];
+    (self.temperature.get(position), self.humidity.get(position))
+  }
+
+  pub fn biome(&self, c: DD) -> BiomeId {
+    let (temperature, humidity) = self.climate(c);
+    if temperature < -0.3 {
+      BiomeId::Tundra
+    } else if temperature > 0.3 && humidity < 0.0 {
+      BiomeId::Desert
+    } else if humidity > 0.2 {
+      BiomeId::Forest
+    } else if humidity < -0.3 {
+      BiomeId::Highlands
+    } else {
+      BiomeId::Plains
+    }
+  }
+
+  pub fn height_profile(&self, c: DD) -> HeightProfile {
+    let mut profile = HeightProfile { offset: 0.0, scale: 0.0 };
+    let mut samples = 0.0;
+    for dx in -BLEND_RADIUS..=BLEND_RADIUS {
+      for dz in -BLEND_RADIUS..=BLEND_RADIUS {
+        let height = self.biome((c.0 + dx * BLEND_STEP, c.1 + dz * BLEND_STEP)).height;
+        profile.offset += height.offset;
+        profile.scale += height.scale;
+        samples += 1.0;
+      }
+    }
+    profile.offset /= samples;
+    profile.scale /= samples;
+    profile
+  }
+}
+
+// Has to run first, everything after it reads context.biomes
+pub struct BiomePass {
+  map: BiomeMap,
+}
+
+impl BiomePass {
+  pub fn new(seed: u64) -> Self {
+    Self { map: BiomeMap::new(seed) }
+  }
+}
+
+impl GenerationPass for BiomePass {
+  fn apply(&self, context: &mut GenerationContext) {
+    context.biomes = Array2d::new_init(context.biomes.bounds, |c| self.map.biome(c));
+  }
+}
@@ -0,0 +1,116 @@
+use crate::ecs::components::blocks::block_id::BlockId;
+use crate::ecs::components::chunk::WorldHeight;
+use crate::util::array::{ArrayIndex, DDD};
+use crate::worldgen::biomes::{BiomeMap, HeightProfile};
+use crate::worldgen::{derive_seed, GenerationContext, GenerationPass};
+use noise::{NoiseFn, Perlin};
+
+const TUNNEL_A_SALT: u64 = 10;
+const TUNNEL_B_SALT: u64 = 11;
+const CAVERN_SALT: u64 = 12;
+const DENSITY_SALT: u64 = 13;
+const DENSITY_DETAIL_SALT: u64 = 14;
+
+fn noise3(perlin: &Perlin, c: DDD, scale: (f64, f64, f64)) -> f64 {
+  perlin.get([c.0 as f64 / scale.0, c.1 as f64 / scale.1, c.2 as f64 / scale.2])
+}
+
+// Carves winding tunnels where two noise fields are both close to zero, and larger caverns where a third peaks
+pub struct CavePass {
+  tunnel_a: Perlin,
+  tunnel_b: Perlin,
+  cavern: Perlin,
+  pub tunnel_width: f64,
+  pub cavern_threshold: f64,
+}
+
+impl CavePass {
+  pub fn new(seed: u64) -> Self {
+    Self {
+      tunnel_a: Perlin::new(derive_seed(seed, TUNNEL_A_SALT) as u32),
+      tunnel_b: Perlin::new(derive_seed(seed, TUNNEL_B_SALT) as u32),
+      cavern: Perlin::new(derive_seed(seed, CAVERN_SALT) as u32),
+      tunnel_width: 0.08,
+      cavern_threshold: 0.6,
+    }
+  }
+
+  fn hollow(&self, c: DDD) -> bool {
+    let tunnel = noise3(&self.tunnel_a, c, (24.0, 16.0, 24.0)).abs() < self.tunnel_width
+      && noise3(&self.tunnel_b, c, (24.0, 16.0, 24.0)).abs() < self.tunnel_width;
+    tunnel || noise3(&self.cavern, c, (40.0, 20.0, 40.0)) > self.cavern_threshold
+  }
+}
+
+impl GenerationPass for CavePass {
+  fn apply(&self, context: &mut GenerationContext) {
+    let bounds = context.bounds;
+    let mut i = bounds.0;
+    loop {
+      if context.blocks[i] != BlockId::Air && self.hollow(i) {
+        context.blocks[i] = BlockId::Air;
+      }
+      i = match i.next(&bounds) {
+        None => break,
+        Some(i) => i,
+      }
+    }
+  }
+}
+
+// Terrain from a 3D density field instead of a heightmap. Anything with positive density is solid, which allows
+// overhangs, arches and islands floating above the ground. Density fades out away from a band around island_level,
+// which biomes raise and widen through their height profile.
+pub struct DensityTerrainPass {
+  density: Perlin,
+  detail: Perlin,
+  biome_map: BiomeMap,
+  island_level: f64,
+  island_spread: f64,
+}
+
+impl DensityTerrainPass {
+  // The band sits a little above sea level and is almost as wide as the heightmap terrain reaches
+  pub fn new(seed: u64, height: WorldHeight) -> Self {
+    Self {
+      density: Perlin::new(derive_seed(seed, DENSITY_SALT) as u32),
+      detail: Perlin::new(derive_seed(seed, DENSITY_DETAIL_SALT) as u32),
+      biome_map: BiomeMap::new(seed),
+      island_level: (height.sea_level() + 10) as f64,
+      island_spread: height.terrain_extent() as f64 * 14.0 / 15.0,
+    }
+  }
+
+  fn density(&self, c: DDD, profile: HeightProfile) -> f64 {
+    let falloff = ((c.1 as f64 - self.island_level - profile.offset) / (self.island_spread * profile.scale)).powi(2);
+    noise3(&self.density, c, (48.0, 32.0, 48.0)) + noise3(&self.detail, c, (12.0, 12.0, 12.0)) * 0.25 + 0.2 - falloff
+  }
+}
+
+impl GenerationPass for DensityTerrainPass {
+  fn apply(&self, context: &mut GenerationContext) {
+    let (from, to) = context.bounds;
+    for x in from.0..=to.0 {
+      for z in from.2..=to.2 {
+        let biome = context.biomes[(x, z)];
+        let profile = self.biome_map.height_profile((x, z));
+        // Blocks below the last air block seen: surface on top, then filler, then stone
+        let mut depth = 0;
+        for y in (from.1..=to.1).rev() {
+          if self.density((x, y, z), profile) <= 0.0 {
+            depth = 0;
+            continue;
+          }
+          context.blocks[(x, y, z)] = if depth == 0 {
+            biome.surface
+          } else if depth <= biome.filler_depth {
+            biome.filler
+          } else {
+            biome.stone
+          };
+          depth += 1;
+        }
+      }
+    }
+  }
+}
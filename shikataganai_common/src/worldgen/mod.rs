@@ -0,0 +1,278 @@
+use crate::ecs::components::blocks::block_id::BlockId;
+use crate::ecs::components::blocks::registry::BlockRegistry;
+use crate::ecs::components::chunk::{Chunk, WorldHeight};
+use crate::util::array::{add_ddd, sub_ddd, Array, Array2d, Array3d, ArrayIndex, Bounds, DD, DDD};
+use crate::worldgen::biomes::{BiomeId, BiomePass};
+use crate::worldgen::caves::{CavePass, DensityTerrainPass};
+use crate::worldgen::passes::{OrePass, TerrainPass};
+use crate::worldgen::structures::{populate_features, FeatureRule};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::Arc;
+
+pub mod biomes;
+pub mod caves;
+pub mod passes;
+pub mod structures;
+
+const FEATURE_SALT: u64 = 30;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TerrainMode {
+  // 2D noise heightmaps, flat bottomed islands
+  #[default]
+  Heightmap,
+  // 3D density field with overhangs and floating islands
+  Density,
+}
+
+impl FromStr for TerrainMode {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "heightmap" => Ok(TerrainMode::Heightmap),
+      "density" => Ok(TerrainMode::Density),
+      _ => Err(format!("Unknown terrain mode {}, expected heightmap or density", s)),
+    }
+  }
+}
+
+// splitmix64 finalizer, mixes a salt into a seed so that every noise field and pass gets an independent stream
+pub fn derive_seed(seed: u64, salt: u64) -> u64 {
+  let mut z = seed.wrapping_add(salt.wrapping_add(1).wrapping_mul(0x9E3779B97F4A7C15));
+  z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+  z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+  z ^ (z >> 31)
+}
+
+pub fn chunk_bounds(coord: DD, height: WorldHeight) -> Bounds<DDD> {
+  (
+    (coord.0 * 16, height.min_y, coord.1 * 16),
+    (coord.0 * 16 + 15, height.max_y, coord.1 * 16 + 15),
+  )
+}
+
+// Block ids of a chunk under construction, handed from pass to pass before lighting is computed
+pub struct GenerationContext {
+  pub seed: u64,
+  pub coord: DD,
+  pub bounds: Bounds<DDD>,
+  pub blocks: Array3d<BlockId>,
+  pub biomes: Array2d<BiomeId>,
+}
+
+pub fn chunk_rng(seed: u64, coord: DD, salt: u64) -> StdRng {
+  let coord = ((coord.0 as u32 as u64) << 32) | coord.1 as u32 as u64;
+  StdRng::seed_from_u64(derive_seed(derive_seed(seed, salt), coord))
+}
+
+impl GenerationContext {
+  fn new(seed: u64, coord: DD, bounds: Bounds<DDD>) -> Self {
+    Self {
+      seed,
+      coord,
+      bounds,
+      blocks: Array::new_init(bounds, |_| BlockId::Air),
+      biomes: Array::new_init(((bounds.0 .0, bounds.0 .2), (bounds.1 .0, bounds.1 .2)), |_| BiomeId::Plains),
+    }
+  }
+
+  // Every random decision has to be drawn from here, anything else makes the chunk differ between runs
+  pub fn rng(&self, salt: u64) -> StdRng {
+    chunk_rng(self.seed, self.coord, salt)
+  }
+}
+
+pub trait GenerationPass: Send + Sync {
+  fn apply(&self, context: &mut GenerationContext);
+}
+
+// Must be a pure function of the seed and the chunk coordinate
+pub trait WorldGenerator: Send + Sync {
+  fn seed(&self) -> u64;
+  // Topmost block of a column and its biome, features left out
+  fn surface(&self, column: DD) -> Option<(i32, BlockId, BiomeId)>;
+  fn generate(&self, coord: DD) -> Chunk;
+}
+
+// Runs its passes in the order they were added: biomes, terrain, caves, ores. Features are placed last.
+pub struct PassGenerator {
+  seed: u64,
+  height: WorldHeight,
+  // Lighting of generated chunks depends on which blocks are visible
+  blocks: Arc<BlockRegistry>,
+  passes: Vec<Box<dyn GenerationPass>>,
+  features: Vec<FeatureRule>,
+}
+
+impl PassGenerator {
+  pub fn new(seed: u64, height: WorldHeight, blocks: Arc<BlockRegistry>) -> Self {
+    Self {
+      seed,
+      height,
+      blocks,
+      passes: vec![],
+      features: vec![],
+    }
+  }
+
+  pub fn with_pass<P: GenerationPass + 'static>(mut self, pass: P) -> Self {
+    self.passes.push(Box::new(pass));
+    self
+  }
+
+  // Features may reach at most one chunk away from the column they are anchored in
+  pub fn with_feature(mut self, feature: FeatureRule) -> Self {
+    let (from, to) = feature.structure.blocks.bounds;
+    let anchor = feature.structure.anchor;
+    assert!(
+      anchor.0 - from.0 <= 16 && to.0 - anchor.0 <= 16 && anchor.2 - from.2 <= 16 && to.2 - anchor.2 <= 16,
+      "Feature template is too wide"
+    );
+    self.features.push(feature);
+    self
+  }
+
+  pub fn standard(seed: u64, terrain: TerrainMode, height: WorldHeight, blocks: Arc<BlockRegistry>) -> Self {
+    let generator = Self::new(seed, height, blocks).with_pass(BiomePass::new(seed));
+    let generator = match terrain {
+      TerrainMode::Heightmap => generator.with_pass(TerrainPass::new(seed, height)),
+      TerrainMode::Density => generator.with_pass(DensityTerrainPass::new(seed, height)),
+    };
+    populate_features().into_iter().fold(
+      generator.with_pass(CavePass::new(seed)).with_pass(OrePass),
+      |generator, feature| generator.with_feature(feature),
+    )
+  }
+
+  // Every chunk replays the feature placement of its neighbours as well and keeps the part of each feature that
+  // falls inside of it, so a feature crossing a border comes out whole no matter which chunk is generated first.
+  // Placement only looks at the terrain passes, never at features placed by other chunks.
+  fn place_features(&self, context: &mut GenerationContext) {
+    let bounds = context.bounds;
+    for dx in -1..=1 {
+      for dz in -1..=1 {
+        let source = (context.coord.0 + dx, context.coord.1 + dz);
+        let mut rng = chunk_rng(self.seed, source, FEATURE_SALT);
+        for feature in self.features.iter() {
+          for _ in 0..feature.attempts {
+            // Always draw the same amount of numbers so that attempts don't shift each other around
+            let column = (source.0 * 16 + rng.gen_range(0..16), source.1 * 16 + rng.gen_range(0..16));
+            if rng.gen_range(0..feature.one_in) != 0 {
+              continue;
+            }
+            let structure = &feature.structure;
+            let (from, to) = structure.blocks.bounds;
+            let anchor = structure.anchor;
+            if column.0 + to.0 - anchor.0 < bounds.0 .0
+              || column.0 - (anchor.0 - from.0) > bounds.1 .0
+              || column.1 + to.2 - anchor.2 < bounds.0 .2
+              || column.1 - (anchor.2 - from.2) > bounds.1 .2
+            {
+              continue;
+            }
+            let Some((y, surface, biome)) = self.surface(column) else {
+              continue;
+            };
+            if !feature.biomes.contains(&biome) || surface != biome.surface {
+              continue;
+            }
+            let offset = sub_ddd((column.0, y + 1, column.1), anchor);
+            let mut i = from;
+            loop {
+              let block = structure.blocks[i];
+              let target = add_ddd(i, offset);
+              if block != BlockId::Air && target.in_bounds(&bounds) && context.blocks[target] == BlockId::Air {
+                context.blocks[target] = block;
+              }
+              i = match i.next(&(from, to)) {
+                None => break,
+                Some(i) => i,
+              }
+            }
+          }
+        }
+      }
+    }
+  }
+}
+
+impl WorldGenerator for PassGenerator {
+  fn seed(&self) -> u64 {
+    self.seed
+  }
+
+  // Runs the passes for a single column to find its topmost block and biome
+  fn surface(&self, column: DD) -> Option<(i32, BlockId, BiomeId)> {
+    let coord = (column.0.div_euclid(16), column.1.div_euclid(16));
+    let bounds = chunk_bounds(coord, self.height);
+    let bounds = ((column.0, bounds.0 .1, column.1), (column.0, bounds.1 .1, column.1));
+    let mut context = GenerationContext::new(self.seed, coord, bounds);
+    for pass in self.passes.iter() {
+      pass.apply(&mut context);
+    }
+    (bounds.0 .1..=bounds.1 .1)
+      .rev()
+      .find(|y| context.blocks[(column.0, *y, column.1)] != BlockId::Air)
+      .map(|y| (y, context.blocks[(column.0, y, column.1)], context.biomes[column]))
+  }
+
+  fn generate(&self, coord: DD) -> Chunk {
+    let bounds = chunk_bounds(coord, self.height);
+    let mut context = GenerationContext::new(self.seed, coord, bounds);
+    for pass in self.passes.iter() {
+      pass.apply(&mut context);
+    }
+    self.place_features(&mut context);
+    let mut chunk = Chunk::new(bounds, &self.blocks, |c| context.blocks[c]);
+    chunk.biomes = context.biomes;
+    chunk
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::ecs::resources::registries::Registries;
+
+  const COORDS: [DD; 4] = [(0, 0), (1, 0), (-1, 3), (5, -2)];
+
+  fn generator(seed: u64, terrain: TerrainMode) -> PassGenerator {
+    PassGenerator::standard(seed, terrain, WorldHeight::default(), Registries::default().blocks)
+  }
+
+  fn encoded(generator: &PassGenerator, coord: DD) -> Vec<u8> {
+    bincode::serialize(&generator.generate(coord)).unwrap()
+  }
+
+  #[test]
+  fn same_seed_generates_the_same_chunks_in_any_order() {
+    for terrain in [TerrainMode::Heightmap, TerrainMode::Density] {
+      let first = generator(42, terrain);
+      let second = generator(42, terrain);
+      let forwards: Vec<_> = COORDS.iter().map(|coord| encoded(&first, *coord)).collect();
+      let mut backwards: Vec<_> = COORDS.iter().rev().map(|coord| encoded(&second, *coord)).collect();
+      backwards.reverse();
+      assert!(forwards == backwards, "{:?} terrain differs between runs", terrain);
+    }
+  }
+
+  #[test]
+  fn different_seeds_generate_different_chunks() {
+    let first = generator(1, TerrainMode::Heightmap);
+    let second = generator(2, TerrainMode::Heightmap);
+    assert!(COORDS.iter().any(|coord| encoded(&first, *coord) != encoded(&second, *coord)));
+  }
+
+  #[test]
+  fn surface_is_the_same_between_runs() {
+    let first = generator(7, TerrainMode::Heightmap);
+    let second = generator(7, TerrainMode::Heightmap);
+    for column in [(0, 0), (10, 10), (-17, 33)] {
+      assert_eq!(first.surface(column), second.surface(column));
+    }
+  }
+}
@@ -0,0 +1,99 @@
+use crate::ecs::components::chunk::WorldHeight;
+use crate::util::array::{ArrayIndex, DD};
+use crate::worldgen::biomes::BiomeMap;
+use crate::worldgen::{derive_seed, GenerationContext, GenerationPass};
+use noise::{NoiseFn, Perlin};
+use rand::Rng;
+
+// Salts keep the noise fields and random streams of different passes apart
+const TERRAIN_BOTTOM_SALT: u64 = 1;
+const TERRAIN_TOP_SALT: u64 = 2;
+const ORE_SALT: u64 = 3;
+
+fn noise(perlin: &Perlin, c: DD) -> f64 {
+  perlin.get([c.0 as f64 / 20.0, 0.0, c.1 as f64 / 20.0])
+}
+
+// Islands of stone capped with the biome's filler and surface blocks, floating on nothing
+pub struct TerrainPass {
+  bottom: Perlin,
+  top: Perlin,
+  biome_map: BiomeMap,
+  height: WorldHeight,
+}
+
+impl TerrainPass {
+  pub fn new(seed: u64, height: WorldHeight) -> Self {
+    Self {
+      bottom: Perlin::new(derive_seed(seed, TERRAIN_BOTTOM_SALT) as u32),
+      top: Perlin::new(derive_seed(seed, TERRAIN_TOP_SALT) as u32),
+      biome_map: BiomeMap::new(seed),
+      height,
+    }
+  }
+}
+
+impl GenerationPass for TerrainPass {
+  fn apply(&self, context: &mut GenerationContext) {
+    let (from, to) = context.bounds;
+    let sea_level = self.height.sea_level();
+    let extent = self.height.terrain_extent() as f64;
+    for x in from.0..=to.0 {
+      for z in from.2..=to.2 {
+        let bottom = noise(&self.bottom, (x, z));
+        let top = noise(&self.top, (x, z));
+        if bottom <= 0.0 {
+          continue;
+        }
+        let biome = context.biomes[(x, z)];
+        let profile = self.biome_map.height_profile((x, z));
+
+        let bottom_extent = (bottom * extent).floor() as i32;
+        // Scaling the offset by bottom keeps the edges of islands thin regardless of the biome
+        let top_extent = ((top + 1.0) * bottom / 2.0 * extent * profile.scale + profile.offset * bottom).floor() as i32;
+
+        for y in from.1..=to.1 {
+          if y < sea_level {
+            if (sea_level - y) < bottom_extent {
+              context.blocks[(x, y, z)] = biome.stone;
+            }
+          } else if (y - sea_level) == top_extent {
+            context.blocks[(x, y, z)] = biome.surface;
+          } else if (y - sea_level) < top_extent {
+            context.blocks[(x, y, z)] = if y - sea_level + biome.filler_depth >= top_extent {
+              biome.filler
+            } else {
+              biome.stone
+            };
+          }
+        }
+      }
+    }
+  }
+}
+
+// Turns a share of each biome's stone into the ores from its table
+pub struct OrePass;
+
+impl GenerationPass for OrePass {
+  fn apply(&self, context: &mut GenerationContext) {
+    let mut rng = context.rng(ORE_SALT);
+    let bounds = context.bounds;
+    let mut i = bounds.0;
+    loop {
+      let biome = context.biomes[(i.0, i.2)];
+      if context.blocks[i] == biome.stone {
+        for entry in biome.ores.iter() {
+          if i.1 >= entry.min_y && i.1 <= entry.max_y && rng.gen_range(0..entry.one_in) == 0 {
+            context.blocks[i] = entry.ore;
+            break;
+          }
+        }
+      }
+      i = match i.next(&bounds) {
+        None => break,
+        Some(i) => i,
+      }
+    }
+  }
+}
@@ -0,0 +1,96 @@
+use crate::ecs::components::blocks::block_id::BlockId;
+use crate::util::array::{Array, Array3d, DDD};
+use crate::worldgen::biomes::BiomeId;
+
+// A block template placed on top of the terrain during the decoration stage. Air cells leave the terrain untouched.
+#[derive(Clone)]
+pub struct Structure {
+  pub blocks: Array3d<BlockId>,
+  // Template cell that ends up right above the surface block the structure is anchored to
+  pub anchor: DDD,
+}
+
+pub struct FeatureRule {
+  pub structure: Structure,
+  pub biomes: Vec<BiomeId>,
+  // Placement attempts per chunk, each of them succeeding with a 1 in one_in chance
+  pub attempts: u32,
+  pub one_in: u32,
+}
+
+fn tree(height: i32) -> Structure {
+  Structure {
+    blocks: Array::new_init(((0, 0, 0), (4, height + 1, 4)), |(x, y, z)| {
+      let (dx, dz) = ((x - 2).abs(), (z - 2).abs());
+      if dx == 0 && dz == 0 && y < height {
+        BlockId::Wood
+      } else if y >= height - 2 && y < height && !(dx == 2 && dz == 2) {
+        BlockId::Leaves
+      } else if y >= height && dx <= 1 && dz <= 1 && !(y == height + 1 && dx == 1 && dz == 1) {
+        BlockId::Leaves
+      } else {
+        BlockId::Air
+      }
+    }),
+    anchor: (2, 0, 2),
+  }
+}
+
+fn boulder() -> Structure {
+  Structure {
+    blocks: Array::new_init(((0, 0, 0), (2, 2, 2)), |(x, y, z)| {
+      if (x - 1).abs() + (y - 1).abs() + (z - 1).abs() <= 2 {
+        BlockId::Cobble
+      } else {
+        BlockId::Air
+      }
+    }),
+    anchor: (1, 0, 1),
+  }
+}
+
+// Crumbling walls of a small square building
+fn ruin() -> Structure {
+  Structure {
+    blocks: Array::new_init(((0, 0, 0), (6, 2, 6)), |(x, y, z)| {
+      let wall = x == 0 || x == 6 || z == 0 || z == 6;
+      let doorway = z == 0 && x == 3 && y < 2;
+      let crumbled = y > 0 && (x * 3 + z * 5 + y) % 4 == 0;
+      if wall && !doorway && !crumbled {
+        BlockId::Cobble
+      } else {
+        BlockId::Air
+      }
+    }),
+    anchor: (3, 0, 3),
+  }
+}
+
+pub fn populate_features() -> Vec<FeatureRule> {
+  vec![
+    FeatureRule {
+      structure: tree(5),
+      biomes: vec![BiomeId::Forest],
+      attempts: 8,
+      one_in: 2,
+    },
+    FeatureRule {
+      structure: tree(4),
+      biomes: vec![BiomeId::Plains, BiomeId::Tundra],
+      attempts: 2,
+      one_in: 4,
+    },
+    FeatureRule {
+      structure: boulder(),
+      biomes: vec![BiomeId::Highlands, BiomeId::Tundra],
+      attempts: 3,
+      one_in: 3,
+    },
+    FeatureRule {
+      structure: ruin(),
+      biomes: vec![BiomeId::Plains, BiomeId::Desert],
+      attempts: 1,
+      one_in: 40,
+    },
+  ]
+}
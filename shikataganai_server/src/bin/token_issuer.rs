@@ -0,0 +1,51 @@
+use bevy_renet::renet::ConnectToken;
+use shikataganai_common::networking::PROTOCOL_ID;
+use shikataganai_server::ecs::resources::auth::{create_key, load_key, nickname_user_data};
+use std::env;
+use std::fs::File;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+// Seconds a token stays valid for when not given on the command line
+const DEFAULT_EXPIRE_SECONDS: u64 = 24 * 60 * 60;
+const TIMEOUT_SECONDS: i32 = 15;
+
+// Issues connect tokens for a server running in secure mode. The first run creates the server's key, which turns
+// secure mode on for the world the next time the server starts.
+fn main() {
+  let usage = "Usage: token_issuer <IP>:<PORT> <NICKNAME> [EXPIRE_SECONDS] [WORLD_DIR]";
+  let address = env::args().nth(1).expect(usage);
+  let nickname = env::args().nth(2).expect(usage);
+  let expire_seconds = env::args()
+    .nth(3)
+    .map(|seconds| seconds.parse().expect("Expire time must be a number of seconds"))
+    .unwrap_or(DEFAULT_EXPIRE_SECONDS);
+
+  let directory = PathBuf::from(env::args().nth(4).unwrap_or_else(|| "world".to_string()));
+  let private_key = match load_key(&directory).unwrap_or_else(|error| panic!("{}", error)) {
+    Some(private_key) => private_key,
+    None => {
+      let private_key = create_key(&directory).expect("Failed to create the server key");
+      println!("Created {:?}, the server only accepts connect tokens from now on", directory);
+      private_key
+    }
+  };
+  let user_data = nickname_user_data(&nickname).expect("Nickname is too long");
+  let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+  let token = ConnectToken::generate(
+    current_time,
+    PROTOCOL_ID,
+    expire_seconds,
+    rand::random(),
+    TIMEOUT_SECONDS,
+    vec![address.parse().expect("Address must be <IP>:<PORT>")],
+    Some(&user_data),
+    &private_key,
+  )
+  .expect("Failed to generate the connect token");
+
+  let path = format!("{}.token", nickname);
+  let mut file = File::create(&path).expect("Failed to create the token file");
+  token.write(&mut file).expect("Failed to write the token file");
+  println!("Wrote {}, it is valid for {} seconds", path, expire_seconds);
+}
@@ -0,0 +1,335 @@
+use crate::ecs::resources::access::NicknameList;
+use crate::ecs::systems::save::AUTOSAVE_INTERVAL;
+use crate::WorldOptions;
+use bevy::prelude::*;
+use serde::Deserialize;
+use shikataganai_common::networking::{DEFAULT_TICK_RATE, MAX_VIEW_DISTANCE};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+pub const CONFIG_FILE: &str = "server.toml";
+pub const MAX_PLAYERS: usize = 256;
+
+pub const USAGE: &str = "Usage: shikataganai_server [OPTIONS]
+
+Options are read from server.toml in the working directory, flags given here take precedence:
+  --config <PATH>             Configuration file to read instead of server.toml
+  --address <IP>:<PORT>       Address to listen on [default: 127.0.0.1:8181]
+  --max-players <N>           Players that can be connected at once [default: 64]
+  --seed <N>                  Seed of a newly created world [default: random]
+  --terrain <MODE>            Terrain of a newly created world, heightmap or density [default: heightmap]
+  --height <MIN_Y:MAX_Y>      Height of a newly created world [default: 0:127]
+  --world <PATH>              Directory the world is kept in [default: world]
+  --view-distance <N>         Most chunks a player may see in every direction [default: 16]
+  --tick-rate <N>             Server ticks per second [default: 60]
+  --motd <TEXT>               Message shown to players when they join
+  --whitelist <PATH>          Only let in the nicknames listed in the file, one per line
+  --ops <PATH>                Nicknames allowed to run admin commands, one per line
+  --blocks <PATH>             Block definitions to use instead of the built-in ones
+  --items <PATH>              Item definitions to use instead of the built-in ones
+  --recipes <PATH>            Recipes to use instead of the built-in ones
+  --autosave-interval <SECS>  Seconds between saves of the world [default: 60]
+  --rcon <IP>:<PORT>          Also take console commands over TCP on this address
+  --rcon-password <TEXT>      Password remote console connections have to start with
+  --help                      Print this message";
+
+// Settings the server is started with, they don't outlive the process unlike WorldOptions
+#[derive(Clone, Resource)]
+pub struct ServerSettings {
+  pub address: SocketAddr,
+  pub max_players: usize,
+  pub world: PathBuf,
+  pub view_distance: u32,
+  pub tick_rate: f64,
+  pub motd: String,
+  pub whitelist: Option<PathBuf>,
+  pub ops: Option<PathBuf>,
+  pub blocks: Option<PathBuf>,
+  pub items: Option<PathBuf>,
+  pub recipes: Option<PathBuf>,
+  pub autosave_interval: f32,
+  // Commands are read from stdin, off for the servers the client starts in the background
+  pub console: bool,
+  pub rcon: Option<SocketAddr>,
+  pub rcon_password: String,
+}
+
+impl Default for ServerSettings {
+  fn default() -> Self {
+    Self {
+      address: "127.0.0.1:8181".parse().unwrap(),
+      max_players: 64,
+      world: PathBuf::from("world"),
+      view_distance: MAX_VIEW_DISTANCE,
+      tick_rate: DEFAULT_TICK_RATE,
+      motd: String::new(),
+      whitelist: None,
+      ops: None,
+      blocks: None,
+      items: None,
+      recipes: None,
+      autosave_interval: AUTOSAVE_INTERVAL,
+      console: false,
+      rcon: None,
+      rcon_password: String::new(),
+    }
+  }
+}
+
+// Either server.toml or the command line, anything left out keeps its default
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigOverrides {
+  address: Option<String>,
+  max_players: Option<usize>,
+  seed: Option<u64>,
+  terrain: Option<String>,
+  height: Option<String>,
+  world: Option<PathBuf>,
+  view_distance: Option<u32>,
+  tick_rate: Option<f64>,
+  motd: Option<String>,
+  whitelist: Option<PathBuf>,
+  ops: Option<PathBuf>,
+  blocks: Option<PathBuf>,
+  items: Option<PathBuf>,
+  recipes: Option<PathBuf>,
+  autosave_interval: Option<f32>,
+  rcon: Option<String>,
+  rcon_password: Option<String>,
+}
+
+impl ConfigOverrides {
+  fn merge(self, other: ConfigOverrides) -> ConfigOverrides {
+    ConfigOverrides {
+      address: other.address.or(self.address),
+      max_players: other.max_players.or(self.max_players),
+      seed: other.seed.or(self.seed),
+      terrain: other.terrain.or(self.terrain),
+      height: other.height.or(self.height),
+      world: other.world.or(self.world),
+      view_distance: other.view_distance.or(self.view_distance),
+      tick_rate: other.tick_rate.or(self.tick_rate),
+      motd: other.motd.or(self.motd),
+      whitelist: other.whitelist.or(self.whitelist),
+      ops: other.ops.or(self.ops),
+      blocks: other.blocks.or(self.blocks),
+      items: other.items.or(self.items),
+      recipes: other.recipes.or(self.recipes),
+      autosave_interval: other.autosave_interval.or(self.autosave_interval),
+      rcon: other.rcon.or(self.rcon),
+      rcon_password: other.rcon_password.or(self.rcon_password),
+    }
+  }
+}
+
+fn parse_flag<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+  value
+    .parse()
+    .map_err(|_| format!("Invalid value {} for {}", value, flag))
+}
+
+// Flags and the configuration file they point to, if any
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<(ConfigOverrides, Option<PathBuf>), String> {
+  let mut overrides = ConfigOverrides::default();
+  let mut config = None;
+  while let Some(flag) = args.next() {
+    // Asking for the usage isn't an error
+    if flag == "--help" {
+      println!("{}", USAGE);
+      std::process::exit(0);
+    }
+    let value = args
+      .next()
+      .ok_or_else(|| format!("{} needs a value\n\n{}", flag, USAGE))?;
+    match flag.as_str() {
+      "--config" => config = Some(PathBuf::from(value)),
+      "--address" => overrides.address = Some(value),
+      "--max-players" => overrides.max_players = Some(parse_flag(&flag, &value)?),
+      "--seed" => overrides.seed = Some(parse_flag(&flag, &value)?),
+      "--terrain" => overrides.terrain = Some(value),
+      "--height" => overrides.height = Some(value),
+      "--world" => overrides.world = Some(PathBuf::from(value)),
+      "--view-distance" => overrides.view_distance = Some(parse_flag(&flag, &value)?),
+      "--tick-rate" => overrides.tick_rate = Some(parse_flag(&flag, &value)?),
+      "--motd" => overrides.motd = Some(value),
+      "--whitelist" => overrides.whitelist = Some(PathBuf::from(value)),
+      "--ops" => overrides.ops = Some(PathBuf::from(value)),
+      "--blocks" => overrides.blocks = Some(PathBuf::from(value)),
+      "--items" => overrides.items = Some(PathBuf::from(value)),
+      "--recipes" => overrides.recipes = Some(PathBuf::from(value)),
+      "--autosave-interval" => overrides.autosave_interval = Some(parse_flag(&flag, &value)?),
+      "--rcon" => overrides.rcon = Some(value),
+      "--rcon-password" => overrides.rcon_password = Some(value),
+      _ => return Err(format!("Unknown option {}\n\n{}", flag, USAGE)),
+    }
+  }
+  Ok((overrides, config))
+}
+
+// A configuration file that was asked for has to be there, the default one may be missing
+fn read_config_file(path: Option<PathBuf>) -> Result<ConfigOverrides, String> {
+  let (path, required) = match path {
+    Some(path) => (path, true),
+    None => (PathBuf::from(CONFIG_FILE), false),
+  };
+  match std::fs::read_to_string(&path) {
+    Ok(text) => toml::from_str(&text).map_err(|error| format!("Invalid configuration in {:?}: {}", path, error)),
+    Err(error) if !required && error.kind() == std::io::ErrorKind::NotFound => Ok(ConfigOverrides::default()),
+    Err(error) => Err(format!("Failed to read {:?}: {}", path, error)),
+  }
+}
+
+// Reads the configuration file and the command line (without the program name) into validated settings
+pub fn load_config<I: Iterator<Item = String>>(args: I) -> Result<(ServerSettings, WorldOptions), String> {
+  let (flags, config) = parse_args(args)?;
+  let config = read_config_file(config)?.merge(flags);
+  let defaults = ServerSettings::default();
+
+  let address = match config.address {
+    Some(address) => address
+      .parse()
+      .map_err(|_| format!("Invalid address {}, expected <IP>:<PORT>", address))?,
+    None => defaults.address,
+  };
+  let max_players = config.max_players.unwrap_or(defaults.max_players);
+  if max_players == 0 || max_players > MAX_PLAYERS {
+    return Err(format!("Max players has to be between 1 and {}, got {}", MAX_PLAYERS, max_players));
+  }
+  let view_distance = config.view_distance.unwrap_or(defaults.view_distance);
+  if view_distance == 0 || view_distance > MAX_VIEW_DISTANCE {
+    return Err(format!(
+      "View distance has to be between 1 and {}, got {}",
+      MAX_VIEW_DISTANCE, view_distance
+    ));
+  }
+  let tick_rate = config.tick_rate.unwrap_or(defaults.tick_rate);
+  if !(1.0..=240.0).contains(&tick_rate) {
+    return Err(format!("Tick rate has to be between 1 and 240, got {}", tick_rate));
+  }
+  let autosave_interval = config.autosave_interval.unwrap_or(defaults.autosave_interval);
+  if !(autosave_interval >= 1.0) {
+    return Err(format!("Autosave interval has to be at least a second, got {}", autosave_interval));
+  }
+  let rcon = config
+    .rcon
+    .map(|rcon| {
+      rcon
+        .parse()
+        .map_err(|_| format!("Invalid remote console address {}, expected <IP>:<PORT>", rcon))
+    })
+    .transpose()?;
+  let rcon_password = config.rcon_password.unwrap_or(defaults.rcon_password);
+  if rcon.is_some() && rcon_password.is_empty() {
+    return Err("The remote console needs a password".to_string());
+  }
+  // Read once here, so that a missing or unreadable list stops the server before it starts
+  for path in config.whitelist.iter().chain(config.ops.iter()) {
+    NicknameList::load(Some(path.clone()))?;
+  }
+
+  let settings = ServerSettings {
+    address,
+    max_players,
+    world: config.world.unwrap_or(defaults.world),
+    view_distance,
+    tick_rate,
+    motd: config.motd.unwrap_or(defaults.motd),
+    whitelist: config.whitelist,
+    ops: config.ops,
+    blocks: config.blocks,
+    items: config.items,
+    recipes: config.recipes,
+    autosave_interval,
+    console: true,
+    rcon,
+    rcon_password,
+  };
+  let options = WorldOptions {
+    seed: config.seed,
+    terrain: config.terrain.map(|terrain| terrain.parse()).transpose()?,
+    height: config.height.map(|height| height.parse()).transpose()?,
+  };
+  Ok((settings, options))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use shikataganai_common::ecs::components::chunk::WorldHeight;
+  use shikataganai_common::worldgen::TerrainMode;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  // Every test reads its own file, so that they don't depend on a server.toml lying around
+  fn load(file: &str, args: &[&str]) -> Result<(ServerSettings, WorldOptions), String> {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+      "shikataganai_config_{}_{}.toml",
+      std::process::id(),
+      NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::write(&path, file).unwrap();
+    let args = ["--config", path.to_str().unwrap()].into_iter().chain(args.iter().copied());
+    let result = load_config(args.map(str::to_string));
+    std::fs::remove_file(&path).unwrap();
+    result
+  }
+
+  #[test]
+  fn empty_configuration_keeps_the_defaults() {
+    let (settings, options) = load("", &[]).unwrap();
+    let defaults = ServerSettings::default();
+    assert_eq!(settings.address, defaults.address);
+    assert_eq!(settings.max_players, defaults.max_players);
+    assert_eq!(settings.view_distance, defaults.view_distance);
+    assert_eq!(settings.world, defaults.world);
+    assert!(settings.console);
+    assert_eq!((options.seed, options.terrain, options.height), (None, None, None));
+  }
+
+  #[test]
+  fn flags_take_precedence_over_the_file() {
+    let file = "max_players = 10\nseed = 5\nterrain = \"density\"\nmotd = \"file\"";
+    let (settings, options) = load(file, &["--max-players", "20", "--height", "-64:255"]).unwrap();
+    assert_eq!(settings.max_players, 20);
+    assert_eq!(settings.motd, "file");
+    assert_eq!(options.seed, Some(5));
+    assert_eq!(options.terrain, Some(TerrainMode::Density));
+    assert_eq!(options.height, Some(WorldHeight::new(-64, 255).unwrap()));
+  }
+
+  #[test]
+  fn invalid_settings_are_refused() {
+    let invalid: [(&str, &[&str]); 14] = [
+      ("max_players = 0", &[]),
+      ("", &["--max-players", "257"]),
+      ("", &["--max-players", "many"]),
+      ("view_distance = 0", &[]),
+      ("", &["--view-distance", "17"]),
+      ("tick_rate = 0.5", &[]),
+      ("autosave_interval = 0.0", &[]),
+      ("address = \"localhost\"", &[]),
+      ("rcon = \"127.0.0.1:8182\"", &[]),
+      ("", &["--terrain", "flat"]),
+      ("", &["--height", "0:100"]),
+      ("unknown = 1", &[]),
+      ("", &["--unknown", "1"]),
+      ("", &["--seed"]),
+    ];
+    for (file, args) in invalid {
+      assert!(load(file, args).is_err(), "{:?} {:?} was accepted", file, args);
+    }
+  }
+
+  #[test]
+  fn remote_console_needs_a_password() {
+    let (settings, _) = load("rcon = \"127.0.0.1:8182\"\nrcon_password = \"secret\"", &[]).unwrap();
+    assert_eq!(settings.rcon, Some("127.0.0.1:8182".parse().unwrap()));
+  }
+
+  #[test]
+  fn configuration_file_asked_for_has_to_exist() {
+    let missing = std::env::temp_dir().join("shikataganai_config_that_does_not_exist.toml");
+    assert!(load_config(["--config".to_string(), missing.to_str().unwrap().to_string()].into_iter()).is_err());
+  }
+}
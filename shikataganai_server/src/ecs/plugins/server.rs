@@ -1,7 +1,24 @@
-use crate::ecs::resources::world::{send_chunk_data, ServerGameWorld};
+use crate::config::ServerSettings;
+use crate::ecs::resources::access::AccessLists;
+use crate::ecs::resources::auth::{user_data_nickname, NicknameReservations, PasswordChecks, ServerKey};
+use crate::ecs::resources::chat::ChatLimiter;
+use crate::ecs::resources::chunk_stream::ChunkStreams;
+use crate::ecs::resources::console::{AdminConsole, ConsoleReply, ConsoleRequest};
+use crate::ecs::resources::frames::FrameHistory;
+use crate::ecs::resources::players::{PlayerData, PlayerDatabase};
+use crate::ecs::resources::protocol::{ProtocolError, ProtocolViolations};
+use crate::ecs::resources::storage::WorldStorage;
+use crate::ecs::resources::validation::{can_reach, eye_position, MoveVerdict, MovementValidator, Reach};
+use crate::ecs::resources::world::{ChunkGenerator, ServerGameWorld, SpawnPoint};
+use crate::ecs::systems::chunk_stream::{stream_chunks, update_views};
 use crate::ecs::systems::chunkgen::collect_async_chunks;
+use crate::ecs::systems::console::admin_console;
 use crate::ecs::systems::light::relight_system;
-use bevy::app::ScheduleRunnerSettings;
+use crate::ecs::systems::save::{
+  autosave_system, save_on_exit, save_player, shutdown_system, unload_chunks, AutosaveTimer, DirtyChunks, SaveRequest,
+  UnloadTimer,
+};
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 use bevy::utils::hashbrown::{HashMap, HashSet};
 use bevy_renet::renet::{RenetError, RenetServer, ServerAuthentication, ServerConfig, ServerEvent};
@@ -9,16 +26,18 @@ use bevy_renet::RenetServerPlugin;
 use bincode::*;
 use num_traits::float::FloatConst;
 use shikataganai_common::ecs::components::blocks::block_id::BlockId;
-use shikataganai_common::ecs::components::blocks::BlockMeta;
+use shikataganai_common::ecs::components::blocks::{BlockMeta, BlockOrItem, QuantifiedBlockOrItem};
 use shikataganai_common::ecs::components::functors::InternalInventory;
 use shikataganai_common::ecs::resources::light::{LightLevel, RelightEvent};
-use shikataganai_common::ecs::resources::player::PlayerNickname;
+use shikataganai_common::ecs::resources::player::{PlayerInventory, PlayerNickname};
+use shikataganai_common::ecs::resources::registries::Registries;
 use shikataganai_common::ecs::resources::world::GameWorld;
-use shikataganai_common::networking::{server_connection_config, BlockTransfer, FunctorType, NetworkFrame, NetworkedEntities, PlayerCommand, PolarRotation, ServerChannel, ServerMessage, PROTOCOL_ID};
+use shikataganai_common::networking::{read_auth_version, server_connection_config, BlockTransfer, ClientChannel, FrameAck, FunctorAction, FunctorType, NetworkFrame, PlayerCommand, PolarRotation, QuantizedTransform, ServerChannel, ServerMessage, Snapshot, PROTOCOL_ID, PROTOCOL_VERSION};
 use shikataganai_common::recipes::Recipes;
-use shikataganai_common::util::array::{add_ddd, sub_ddd, DD, DDD};
+use shikataganai_common::util::array::{add_ddd, sub_ddd, to_ddd, DD, DDD};
+use std::marker::PhantomData;
 use std::net::UdpSocket;
-use std::time::{Duration, SystemTime};
+use std::time::SystemTime;
 
 pub struct ShikataganaiServerPlugin;
 
@@ -38,36 +57,47 @@ pub struct UnAuthedPlayers {
   pub players: HashSet<u64>,
 }
 
+// Clients that currently have a functor's interface open, so they can be kept up to date when it changes
+#[derive(Default, Resource)]
+pub struct FunctorViewers {
+  pub viewers: HashMap<DDD, HashSet<u64>>,
+}
+
 #[derive(Component)]
 pub struct ClientId(u64);
 
-#[derive(Resource)]
-pub struct ShikataganaiServerAddress {
-  pub address: String,
+// Binds the socket up front, so that a bad address is reported before anything else starts
+pub fn bind_server(settings: &ServerSettings, server_key: &ServerKey) -> Result<RenetServer, String> {
+  let socket = UdpSocket::bind(settings.address).map_err(|error| format!("Failed to bind {}: {}", settings.address, error))?;
+  println!("{}", settings.address);
+  let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+  let authentication = match server_key.0 {
+    Some(private_key) => {
+      println!("Running in secure mode, clients need a connect token");
+      ServerAuthentication::Secure { private_key }
+    }
+    None => ServerAuthentication::Unsecure,
+  };
+  RenetServer::new(
+    current_time,
+    ServerConfig::new(settings.max_players, PROTOCOL_ID, settings.address, authentication),
+    server_connection_config(),
+    socket,
+  )
+  .map_err(|error| format!("Failed to start the server: {}", error))
 }
 
 impl Plugin for ShikataganaiServerPlugin {
   fn build(&self, app: &mut App) {
-    let address = app.world.resource::<ShikataganaiServerAddress>().address.as_str();
-    let server_addr = address.parse().unwrap();
-    let socket = UdpSocket::bind(server_addr).unwrap();
-    println!("{}", server_addr);
-    let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+    let settings = app.world.resource::<ServerSettings>().clone();
 
     // let on_fixed_step_simulation: SystemSet = ConditionSet::new()
     //   .into();
     // let on_fixed_step_simulation_stage = SystemStage::parallel().with_system_set(on_fixed_step_simulation);
 
-    let server = RenetServer::new(
-      current_time,
-      ServerConfig::new(64, PROTOCOL_ID, server_addr, ServerAuthentication::Unsecure),
-      server_connection_config(),
-      socket,
-    )
-    .unwrap();
-
     app.add_event::<RelightEvent>();
     app.add_event::<FunctorRequestEvent>();
+    app.add_event::<SaveRequest>();
 
     app
       // .add_stage_after(
@@ -75,18 +105,35 @@ impl Plugin for ShikataganaiServerPlugin {
       //   FixedUpdate,
       //   FixedTimestepStage::from_stage(Duration::from_millis(10), on_fixed_step_simulation_stage),
       // )
-      .insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_secs_f64(1.0 / 60.0)))
       .add_plugin(RenetServerPlugin { clear_events: false })
       .init_resource::<ServerTick>()
       .init_resource::<PlayerEntities>()
       .init_resource::<UnAuthedPlayers>()
-      .insert_resource(server)
+      .init_resource::<FunctorViewers>()
+      .init_resource::<DirtyChunks>()
+      .init_resource::<ChunkStreams>()
+      .init_resource::<FrameHistory>()
+      .init_resource::<MovementValidator>()
+      .init_resource::<ProtocolViolations>()
+      .init_resource::<ChatLimiter>()
+      .init_resource::<PasswordChecks>()
+      .insert_resource(AutosaveTimer(Timer::from_seconds(settings.autosave_interval, TimerMode::Repeating)))
+      .init_resource::<UnloadTimer>()
+      .add_system(poll_password_checks.before(handle_events))
       .add_system(handle_events)
       .add_system(handle_functor_requests.after(handle_events))
       .add_system(sync_frame)
       .add_system(collect_async_chunks)
+      .add_system(update_views.after(handle_events))
+      .add_system(stream_chunks.after(collect_async_chunks).after(update_views))
+      .add_system(unload_chunks.after(update_views))
       .add_system(panic_handler)
-      .add_system_to_stage(CoreStage::PostUpdate, relight_system);
+      .add_system(disconnect_kicked_clients.after(admin_console))
+      .add_system(admin_console.after(handle_events))
+      .add_system(autosave_system.after(admin_console))
+      .add_system(shutdown_system)
+      .add_system_to_stage(CoreStage::PostUpdate, relight_system)
+      .add_system_to_stage(CoreStage::Last, save_on_exit);
   }
 }
 
@@ -96,6 +143,26 @@ pub fn panic_handler(mut events: EventReader<RenetError>) {
   }
 }
 
+// Cuts kicked clients off once they have had the time to receive the reason
+pub fn disconnect_kicked_clients(mut server: ResMut<RenetServer>, violations: Res<ProtocolViolations>, time: Res<Time>) {
+  for client in violations.due(time.elapsed_seconds_f64()) {
+    server.disconnect(client);
+  }
+}
+
+// Joins with a wrong password are refused here, the others go on in handle_events
+pub fn poll_password_checks(
+  mut password_checks: ResMut<PasswordChecks>,
+  mut server: ResMut<RenetServer>,
+  mut violations: ResMut<ProtocolViolations>,
+  time: Res<Time>,
+) {
+  let now = time.elapsed_seconds_f64();
+  for (client, nickname) in password_checks.poll(now) {
+    violations.report(server.as_mut(), client, ProtocolError::WrongPassword(nickname), now);
+  }
+}
+
 #[derive(Debug)]
 pub struct FunctorRequestEvent {
   pub client: u64,
@@ -132,18 +199,91 @@ pub fn handle_functor_requests(
   }
 }
 
+// Per-client state of the connection and the protocol
+#[derive(SystemParam)]
+pub struct Connections<'w, 's> {
+  pub server: ResMut<'w, RenetServer>,
+  pub server_events: EventReader<'w, 's, ServerEvent>,
+  pub player_entities: ResMut<'w, PlayerEntities>,
+  pub unauthed_players: ResMut<'w, UnAuthedPlayers>,
+  pub chunk_streams: ResMut<'w, ChunkStreams>,
+  pub frame_history: ResMut<'w, FrameHistory>,
+  pub movement: ResMut<'w, MovementValidator>,
+  pub violations: ResMut<'w, ProtocolViolations>,
+  pub chat_limiter: ResMut<'w, ChatLimiter>,
+  pub functor_viewers: ResMut<'w, FunctorViewers>,
+  pub time: Res<'w, Time>,
+}
+
+// Who may join and run commands
+#[derive(SystemParam)]
+pub struct Admission<'w, 's> {
+  pub settings: Res<'w, ServerSettings>,
+  pub server_key: Res<'w, ServerKey>,
+  pub reservations: ResMut<'w, NicknameReservations>,
+  pub password_checks: ResMut<'w, PasswordChecks>,
+  pub access_lists: Res<'w, AccessLists>,
+  pub console: Res<'w, AdminConsole>,
+  #[system_param(ignore)]
+  marker: PhantomData<&'s ()>,
+}
+
+// The world and everything that is loaded into it or saved from it
+#[derive(SystemParam)]
+pub struct WorldAccess<'w, 's> {
+  pub game_world: ResMut<'w, GameWorld>,
+  pub storage: ResMut<'w, WorldStorage>,
+  pub generator: Res<'w, ChunkGenerator>,
+  pub dirty_chunks: ResMut<'w, DirtyChunks>,
+  pub player_database: ResMut<'w, PlayerDatabase>,
+  pub recipes: Res<'w, Recipes>,
+  pub registries: Res<'w, Registries>,
+  pub spawn_point: Res<'w, SpawnPoint>,
+  pub relight: EventWriter<'w, 's, RelightEvent>,
+}
+
 pub fn handle_events(
   mut commands: Commands,
-  mut server: ResMut<RenetServer>,
-  mut server_events: EventReader<ServerEvent>,
-  mut relight: EventWriter<RelightEvent>,
+  connections: Connections,
+  admission: Admission,
+  world_access: WorldAccess,
   mut functor_events: EventWriter<FunctorRequestEvent>,
-  mut player_entities: ResMut<PlayerEntities>,
-  mut unauthed_players: ResMut<UnAuthedPlayers>,
-  mut query: Query<(Entity, &mut Transform, &mut PolarRotation, &PlayerNickname)>,
-  mut game_world: ResMut<GameWorld>,
-  recipes: Res<Recipes>,
+  mut query: Query<(Entity, &mut Transform, &mut PolarRotation, &PlayerNickname, &mut PlayerInventory)>,
+  mut inventories: Query<&mut InternalInventory>,
 ) {
+  let Connections {
+    mut server,
+    mut server_events,
+    mut player_entities,
+    mut unauthed_players,
+    mut chunk_streams,
+    mut frame_history,
+    mut movement,
+    mut violations,
+    mut chat_limiter,
+    mut functor_viewers,
+    time,
+  } = connections;
+  let Admission {
+    settings,
+    server_key,
+    mut reservations,
+    mut password_checks,
+    access_lists,
+    console,
+    ..
+  } = admission;
+  let WorldAccess {
+    mut game_world,
+    mut storage,
+    generator,
+    mut dirty_chunks,
+    mut player_database,
+    recipes,
+    registries,
+    spawn_point,
+    mut relight,
+  } = world_access;
   for event in server_events.iter() {
     match event {
       ServerEvent::ClientConnected(client_id, _) => {
@@ -151,46 +291,199 @@ pub fn handle_events(
         println!("Client {} connected", client_id);
       }
       ServerEvent::ClientDisconnected(client_id) => {
-        println!("Client {} disconnected", client_id);
-        // let entity = player_entities.players.remove(client_id).unwrap();
-        // commands.entity(entity).despawn();
+        let client = *client_id;
+        println!("Client {} disconnected", client);
+        unauthed_players.players.remove(&client);
+        password_checks.remove(client);
+        chunk_streams.remove(client);
+        frame_history.remove(client);
+        movement.remove(client);
+        violations.remove(client);
+        chat_limiter.remove(client);
+        functor_viewers.viewers.retain(|_, viewers| {
+          viewers.remove(&client);
+          !viewers.is_empty()
+        });
+        // The player is saved and despawned, so that the nickname can be used again
+        if let Some(player_entity) = player_entities.players.remove(&client) {
+          if let Ok((_, transform, rotation, nickname, inventory)) = query.get(player_entity) {
+            save_player(player_database.as_mut(), nickname, transform, rotation, inventory);
+            player_database.save();
+            let message = format!("{} left the game", nickname.0);
+            broadcast_chat(server.as_mut(), player_entities.as_ref(), None, &message);
+          }
+          commands.entity(player_entity).despawn_recursive();
+          let message = serialize(&ServerMessage::PlayerDespawn { id: client }).unwrap();
+          for other_client in player_entities.players.keys() {
+            server.send_message(*other_client, ServerChannel::GameEvent.id(), message.clone());
+          }
+        }
       }
     }
   }
   for client in server.clients_id().into_iter() {
-    while let Some(message) = server.receive_message(client, 0) {
-      let command: PlayerCommand = deserialize(&message).unwrap();
+    // A join whose password was checked on the task pool goes on before anything else the client sent
+    let mut verified = password_checks.verified.remove(&client);
+    loop {
+      let now = time.elapsed_seconds_f64();
+      let (command, mut checked_password) = match verified.take() {
+        Some((command, checked_password)) => (command, Some(checked_password)),
+        None => {
+          let Some(message) = server.receive_message(client, ClientChannel::ClientCommand.id()) else {
+            break;
+          };
+          if violations.is_kicked(client) {
+            continue;
+          }
+          match deserialize::<PlayerCommand>(&message) {
+            Ok(command) => (command, None),
+            Err(error) => {
+              let error = match read_auth_version(&message) {
+                Some(version) if version != PROTOCOL_VERSION => ProtocolError::VersionMismatch(version),
+                _ => ProtocolError::Malformed(error.to_string()),
+              };
+              violations.report(server.as_mut(), client, error, now);
+              continue;
+            }
+          }
+        }
+      };
+      let authenticated = player_entities.players.contains_key(&client);
+      if !authenticated && !matches!(command, PlayerCommand::PlayerAuth { .. }) {
+        violations.report(server.as_mut(), client, ProtocolError::Unauthenticated(command.to_string()), now);
+        continue;
+      }
       match command {
         PlayerCommand::PlayerMove { translation } => {
-          let player_entity = *player_entities.players.get(&client).unwrap();
-          query.get_mut(player_entity).unwrap().1.translation = translation.0;
-          *query.get_mut(player_entity).unwrap().2 = translation.1;
+          let Some(player_entity) = player_entities.players.get(&client).copied() else {
+            continue;
+          };
+          let Ok((_, mut transform, mut rotation, ..)) = query.get_mut(player_entity) else {
+            continue;
+          };
+          *rotation = translation.1;
+          match movement.check(client, translation.0, now) {
+            MoveVerdict::Accept => transform.translation = translation.0,
+            MoveVerdict::Correct(translation) => server.send_message(
+              client,
+              ServerChannel::GameEvent.id(),
+              serialize(&ServerMessage::PlayerCorrection { translation }).unwrap(),
+            ),
+            MoveVerdict::Ignore => {}
+          }
         }
         PlayerCommand::BlockRemove { location } => {
-          if let Some(block) = game_world.get_mut(location) {
+          let Some(player_entity) = player_entities.players.get(&client).copied() else {
+            continue;
+          };
+          let Ok((_, transform, .., mut inventory)) = query.get_mut(player_entity) else {
+            continue;
+          };
+          if !can_reach(game_world.as_ref(), &registries.blocks, eye_position(transform.translation), location, Reach::Block) {
+            send_block_state(server.as_mut(), client, game_world.as_ref(), location);
+            send_inventory(server.as_mut(), client, &inventory);
+            continue;
+          }
+          let removed = game_world.get_mut(location).and_then(|block| {
+            if block.block == BlockId::Air {
+              return None;
+            }
+            // What a chest holds goes to the player along with the drop, it stays if that doesn't all fit
+            let contents = inventories
+              .get(block.entity)
+              .map(|internal| internal.inventory.iter().flatten().cloned().collect())
+              .unwrap_or(vec![]);
+            let mut gained = inventory.clone();
+            let fits = registries
+              .block(block.block)
+              .drops()
+              .map(|drop| QuantifiedBlockOrItem { block_or_item: drop, quant: 1 })
+              .into_iter()
+              .chain(contents)
+              .all(|item| gained.add(&registries, item.block_or_item, item.quant));
+            if !fits {
+              return None;
+            }
+            *inventory = gained;
+            if block.entity != Entity::from_bits(0) {
+              commands.entity(block.entity).despawn_recursive();
+            }
             *block = BlockId::Air.into();
+            Some(())
+          });
+          if removed.is_some() {
+            functor_viewers.viewers.remove(&location);
+            dirty_chunks.0.insert(GameWorld::get_chunk_coord(location));
             relight.send(RelightEvent::Relight(location));
-            broadcast_but(server.as_mut(), client, ServerMessage::BlockRemove { location })
+            broadcast_but(server.as_mut(), chunk_streams.as_ref(), client, location, ServerMessage::BlockRemove { location })
+          } else {
+            send_block_state(server.as_mut(), client, game_world.as_ref(), location);
           }
+          send_inventory(server.as_mut(), client, &inventory);
         }
-        PlayerCommand::BlockPlace { location, block_transfer } => {
-          if let Some(block) = game_world.get_mut(location) {
+        PlayerCommand::BlockPlace { location, block_transfer, slot } => {
+          let Some(player_entity) = player_entities.players.get(&client).copied() else {
+            continue;
+          };
+          let Ok((_, transform, .., mut inventory)) = query.get_mut(player_entity) else {
+            continue;
+          };
+          if !can_reach(game_world.as_ref(), &registries.blocks, eye_position(transform.translation), location, Reach::Placement) {
+            send_block_state(server.as_mut(), client, game_world.as_ref(), location);
+            send_inventory(server.as_mut(), client, &inventory);
+            continue;
+          }
+          let placed = game_world.get_mut(location).and_then(|block| {
+            if block.block != BlockId::Air || !inventory.take(slot, BlockOrItem::Block(block_transfer.block)) {
+              return None;
+            }
             *block = block_transfer.into();
-            if block.need_to_spawn_functors() {
-              block.block.clone().spawn_or_add_functors(block, location, &mut commands);
+            let definition = registries.block(block.block);
+            if definition.need_to_spawn_functors() {
+              definition.spawn_or_add_functors(block, location, &mut commands);
             }
+            Some(())
+          });
+          if placed.is_some() {
+            dirty_chunks.0.insert(GameWorld::get_chunk_coord(location));
             relight.send(RelightEvent::Relight(location));
             game_world.set_light_level(location, LightLevel::dark());
-            broadcast_but(server.as_mut(), client, ServerMessage::BlockPlace { location, block_transfer })
+            broadcast_but(
+              server.as_mut(),
+              chunk_streams.as_ref(),
+              client,
+              location,
+              ServerMessage::BlockPlace { location, block_transfer },
+            )
+          } else {
+            send_block_state(server.as_mut(), client, game_world.as_ref(), location);
+          }
+          send_inventory(server.as_mut(), client, &inventory);
+        }
+        PlayerCommand::InventorySwap { from, to } => {
+          if let Some(player_entity) = player_entities.players.get(&client).copied()
+            && let Ok((.., mut inventory)) = query.get_mut(player_entity)
+            && !inventory.swap(from, to)
+          {
+            send_inventory(server.as_mut(), client, &inventory);
           }
         }
         PlayerCommand::RequestChunk { chunk_coord: coord } => {
-          if let Some(chunk) = game_world.get_chunk_or_spawn(coord, &mut commands, client) {
-            send_chunk_data(server.as_mut(), chunk, client);
+          // Sent again by stream_chunks as soon as it is loaded or generated
+          if chunk_streams.request(client, coord) {
+            game_world.get_chunk_or_spawn(coord, &mut commands, storage.as_mut(), generator.as_ref(), &registries);
           }
         }
         PlayerCommand::RequestFunctor { location, functor } => {
-          if let Some(entity) = game_world.get(location).map(|block| block.entity) && entity != Entity::from_bits(0) {
+          let reachable = player_entities
+            .players
+            .get(&client)
+            .and_then(|player_entity| query.get(*player_entity).ok())
+            .map_or(false, |(_, transform, ..)| {
+              can_reach(game_world.as_ref(), &registries.blocks, eye_position(transform.translation), location, Reach::Block)
+            });
+          if reachable && let Some(entity) = game_world.get(location).map(|block| block.entity) && entity != Entity::from_bits(0) {
+            functor_viewers.viewers.entry(location).or_default().insert(client);
             functor_events.send(FunctorRequestEvent {
               client,
               location,
@@ -199,31 +492,108 @@ pub fn handle_events(
             });
           }
         }
-        PlayerCommand::PlayerAuth { nickname } => {
-          if unauthed_players.players.contains(&client) {
-            unauthed_players.players.remove(&client);
-            let (player_entity, translation, rotation) = query.iter().find(|(_, _, _, player_nickname)| player_nickname.0 == nickname).map(|(entity, transform, rotation, _)| {
-              (entity, transform.translation, *rotation)
-            }).or_else(|| {
-              let player_entity = commands
-                .spawn((
-                   Transform::from_xyz(10.1, 45.0, 10.0),
-                   PolarRotation { phi: 0.0, theta: f32::FRAC_PI_2() },
-                   ClientId(client),
-                   PlayerNickname(nickname)
-                ))
-                .id();
-              Some((player_entity, Vec3::new(10.1, 37.0, 10.0), PolarRotation { phi: 0.0, theta: f32::FRAC_PI_2() }))
-            }).unwrap();
-
-            if player_entities.players.iter().any(|(_, entity)| *entity == player_entity) {
-              println!("Client taken!");
+        PlayerCommand::FunctorAction { location, functor } => match functor {
+          FunctorAction::InventoryTransaction { _type, from, to } => {
+            let Some(player_entity) = player_entities.players.get(&client).copied() else {
+              continue;
+            };
+            let Ok((.., mut player_inventory)) = query.get_mut(player_entity) else {
+              continue;
+            };
+            let Some(entity) = game_world.get(location).map(|block| block.entity) else {
+              continue;
+            };
+            let Ok(mut inventory) = inventories.get_mut(entity) else {
+              continue;
+            };
+            let viewing = functor_viewers
+              .viewers
+              .get(&location)
+              .map(|viewers| viewers.contains(&client))
+              .unwrap_or(false);
+            if viewing && inventory.transaction(player_inventory.as_mut(), &registries, _type, from, to) {
+              dirty_chunks.0.insert(GameWorld::get_chunk_coord(location));
+              for viewer in functor_viewers.viewers.get(&location).unwrap() {
+                send_functor(server.as_mut(), *viewer, location, &inventory);
+              }
+            } else {
+              send_functor(server.as_mut(), client, location, &inventory);
+            }
+            send_inventory(server.as_mut(), client, &player_inventory);
+          }
+        },
+        PlayerCommand::CloseFunctor { location } => {
+          if let Some(viewers) = functor_viewers.viewers.get_mut(&location) {
+            viewers.remove(&client);
+            if viewers.is_empty() {
+              functor_viewers.viewers.remove(&location);
+            }
+          }
+        }
+        PlayerCommand::PlayerAuth { version, nickname, password, view_distance } => {
+          if !unauthed_players.players.contains(&client) || password_checks.is_pending(client) {
+            violations.report(server.as_mut(), client, ProtocolError::AlreadyAuthenticated, now);
+          } else {
+            // Only the entities of players that are still connected count, the ones of players who just left are on
+            // their way out and already saved
+            let online = query.iter().any(|(entity, _, _, player_nickname, _)| {
+              player_nickname.0 == nickname && player_entities.players.values().any(|other| *other == entity)
+            });
+            // In secure mode the token vouches for the nickname, otherwise a reserved nickname needs its password.
+            // Passwords are hashed on the task pool, the join comes back here once it is checked. Nothing is reserved
+            // for a client that gets refused.
+            let refused = if version != PROTOCOL_VERSION {
+              Some(ProtocolError::VersionMismatch(version))
+            } else if online {
+              Some(ProtocolError::NicknameTaken(nickname.clone()))
+            } else if access_lists.is_banned(&nickname) {
+              Some(ProtocolError::Banned(nickname.clone()))
+            } else if !access_lists.may_join(&nickname) {
+              Some(ProtocolError::NotWhitelisted(nickname.clone()))
+            } else if server_key.0.is_some() {
+              let token_nickname = server.user_data(client).and_then(|data| user_data_nickname(&data));
+              (token_nickname.as_deref() != Some(nickname.as_str())).then(|| ProtocolError::TokenMismatch(nickname.clone()))
+            } else if let Some(checked_password) = checked_password.take() {
+              reservations.apply(checked_password).err()
+            } else {
+              match reservations.check(&nickname, password.as_deref()) {
+                Ok(None) => None,
+                Ok(Some(check)) => {
+                  let command = PlayerCommand::PlayerAuth { version, nickname, password, view_distance };
+                  if let Err(error) = password_checks.start(client, check, command, now) {
+                    violations.report(server.as_mut(), client, error, now);
+                  }
+                  continue;
+                }
+                Err(error) => Some(error),
+              }
+            };
+            if let Some(error) = refused {
+              violations.report(server.as_mut(), client, error, now);
               continue;
             }
+            unauthed_players.players.remove(&client);
+            // Returning players resume from the database, new ones start at the spawn point
+            let PlayerData { translation, rotation, inventory } = player_database.players.get(&nickname).cloned().unwrap_or_else(|| PlayerData {
+              translation: spawn_point.0,
+              rotation: PolarRotation { phi: 0.0, theta: f32::FRAC_PI_2() },
+              inventory: PlayerInventory::default(),
+            });
+            let player_entity = commands
+              .spawn((
+                 Transform::from_translation(translation),
+                 rotation,
+                 ClientId(client),
+                 PlayerNickname(nickname.clone()),
+                 inventory.clone(),
+              ))
+              .id();
 
-            for other_client in player_entities.players.keys() {
-              let other_entity = *player_entities.players.get(other_client).unwrap();
-              let (_, translation, rotation, _) = query.get(other_entity).unwrap();
+            for (other_client, other_entity) in player_entities.players.iter() {
+              let other_entity = *other_entity;
+              let Ok((_, translation, rotation, _, _)) = query.get(other_entity) else {
+                continue;
+              };
               server.send_message(
                 client,
                 ServerChannel::GameEvent.id(),
@@ -244,25 +614,52 @@ pub fn handle_events(
                 }).unwrap(),
               );
             }
+            for definition in registries.items.definitions() {
+              server.send_message(
+                client,
+                ServerChannel::GameEvent.id(),
+                serialize(&ServerMessage::ItemDefinition { definition }).unwrap(),
+              );
+            }
+            for definition in registries.blocks.definitions() {
+              server.send_message(
+                client,
+                ServerChannel::GameEvent.id(),
+                serialize(&ServerMessage::BlockDefinition { definition }).unwrap(),
+              );
+            }
+            let view_distance = chunk_streams.open(client, view_distance.min(settings.view_distance));
+            movement.open(client, translation, now);
             server.send_message(
               client,
               ServerChannel::GameEvent.id(),
               serialize(&ServerMessage::AuthConfirmed {
                 translation: (translation, rotation),
+                inventory,
+                world_height: game_world.height,
+                view_distance,
+                tick_rate: settings.tick_rate,
+                motd: settings.motd.clone(),
               }).unwrap(),
             );
             player_entities.players.insert(client, player_entity);
+            let message = format!("{} joined the game", nickname);
+            broadcast_chat(server.as_mut(), player_entities.as_ref(), None, &message);
           }
         }
         PlayerCommand::AnimationStart { location, animation } => {
-          for other_client in player_entities.players.keys() {
-            if *other_client == client {
-              continue;
-            }
-            server.send_message(*other_client, ServerChannel::GameEvent.id(), serialize(&ServerMessage::AnimationStart { location, animation: animation.clone() }).unwrap())
-          }
+          broadcast_but(server.as_mut(), chunk_streams.as_ref(), client, location, ServerMessage::AnimationStart { location, animation })
         },
         PlayerCommand::InitiateInWorldCraft { location } => {
+          let Some(player_entity) = player_entities.players.get(&client).copied() else {
+            continue;
+          };
+          let Ok((_, transform, .., mut inventory)) = query.get_mut(player_entity) else {
+            continue;
+          };
+          if !can_reach(game_world.as_ref(), &registries.blocks, eye_position(transform.translation), location, Reach::Block) {
+            continue;
+          }
           if let Some(block) = game_world.get(location) {
             let mut iter = vec![];
             for r in &recipes.recipes {
@@ -279,56 +676,193 @@ pub fn handle_events(
                 flag = flag && *b == game_world.get(loc).map(|b| b.block).unwrap_or(BlockId::Air);
               });
               if flag {
+                // Only the blocks the recipe turns into something else are touched
+                let mut changes = vec![];
                 recipe.to.foreach(|c, b| {
-                  let loc = add_ddd(sub_ddd(c, origin), anchor);
-                  game_world.get_mut(loc).map(|block| {
-                    block.block = *b;
-                    server.broadcast_message(ServerChannel::GameEvent.id(), serialize(&ServerMessage::BlockPlace { location: loc, block_transfer: BlockTransfer { block: *b, meta: BlockMeta { v: 0 } } }).unwrap());
-                    relight.send(RelightEvent::Relight(loc));
-                  });
+                  if recipe.from[c] != *b {
+                    changes.push((add_ddd(sub_ddd(c, origin), anchor), *b));
+                  }
+                });
+                let holds_items = changes.iter().any(|(loc, _)| {
+                  game_world
+                    .get(*loc)
+                    .and_then(|block| inventories.get(block.entity).ok())
+                    .map_or(false, |internal| internal.inventory.iter().any(Option::is_some))
                 });
-                if let Some(item) = recipe.item {
-                  server.send_message(client, ServerChannel::GameEvent.id(), serialize(&ServerMessage::ItemAdd { item, quant: 1 }).unwrap());
+                if holds_items {
+                  send_chat(server.as_mut(), client, None, &format!("Empty the blocks used for {} first", recipe.name));
+                  break;
+                }
+                // Nothing is crafted unless the player has room for all of the output
+                let mut crafted = inventory.clone();
+                if !recipe.output.iter().all(|output| crafted.add(&registries, output.block_or_item, output.quant)) {
+                  send_chat(server.as_mut(), client, None, &format!("No room in the inventory for {}", recipe.name));
+                  break;
+                }
+                for (loc, to) in changes {
+                  let Some(block) = game_world.get_mut(loc) else {
+                    continue;
+                  };
+                  if block.entity != Entity::from_bits(0) {
+                    commands.entity(block.entity).despawn_recursive();
+                  }
+                  *block = to.into();
+                  let definition = registries.block(to);
+                  if definition.need_to_spawn_functors() {
+                    definition.spawn_or_add_functors(block, loc, &mut commands);
+                  }
+                  functor_viewers.viewers.remove(&loc);
+                  let message = serialize(&ServerMessage::BlockPlace { location: loc, block_transfer: BlockTransfer { block: to, meta: BlockMeta { v: 0 } } }).unwrap();
+                  for watcher in chunk_streams.watchers(GameWorld::get_chunk_coord(loc)) {
+                    server.send_message(watcher, ServerChannel::GameEvent.id(), message.clone());
+                  }
+                  dirty_chunks.0.insert(GameWorld::get_chunk_coord(loc));
+                  relight.send(RelightEvent::Relight(loc));
+                }
+                if !recipe.output.is_empty() {
+                  *inventory = crafted;
+                  send_inventory(server.as_mut(), client, &inventory);
                 }
                 break;
               }
             }
           }
         }
+        PlayerCommand::Chat { message } => {
+          let Some(nickname) = player_entities
+            .players
+            .get(&client)
+            .and_then(|player_entity| query.get(*player_entity).ok())
+            .map(|(_, _, _, nickname, _)| nickname.0.clone())
+          else {
+            continue;
+          };
+          // Slash-commands the client doesn't know itself are run as console commands for operators
+          match chat_limiter.check(client, &message, now) {
+            Ok(message) => match message.strip_prefix('/') {
+              Some(line) if access_lists.ops.contains(&nickname) => {
+                println!("{} ran /{}", nickname, line);
+                console.submit(ConsoleRequest {
+                  line: line.to_string(),
+                  reply: ConsoleReply::Player(client),
+                });
+              }
+              Some(_) => send_chat(server.as_mut(), client, None, "Only operators can run server commands"),
+              None => broadcast_chat(server.as_mut(), player_entities.as_ref(), Some(&nickname), &message),
+            },
+            Err(reason) => send_chat(server.as_mut(), client, None, &reason),
+          }
+        }
       }
     }
   }
 }
 
-pub fn broadcast_but(server: &mut RenetServer, client_exclude: u64, message: ServerMessage) {
-  for broadcast_client in server.clients_id().into_iter() {
+pub fn send_functor(server: &mut RenetServer, client: u64, location: DDD, inventory: &InternalInventory) {
+  server.send_message(
+    client,
+    ServerChannel::GameEvent.id(),
+    serialize(&ServerMessage::Functor {
+      location,
+      functor_type: FunctorType::InternalInventory,
+      functor: serialize(inventory).unwrap(),
+    })
+    .unwrap(),
+  );
+}
+
+pub fn send_inventory(server: &mut RenetServer, client: u64, inventory: &PlayerInventory) {
+  server.send_message(
+    client,
+    ServerChannel::GameEvent.id(),
+    serialize(&ServerMessage::Inventory {
+      inventory: inventory.clone(),
+    })
+    .unwrap(),
+  );
+}
+
+pub fn send_chat(server: &mut RenetServer, client: u64, sender: Option<&str>, message: &str) {
+  server.send_message(
+    client,
+    ServerChannel::GameEvent.id(),
+    serialize(&ServerMessage::Chat {
+      sender: sender.map(str::to_string),
+      message: message.to_string(),
+    })
+    .unwrap(),
+  );
+}
+
+// Chat reaches every player wherever they are, messages without a sender come from the server
+pub fn broadcast_chat(server: &mut RenetServer, player_entities: &PlayerEntities, sender: Option<&str>, message: &str) {
+  match sender {
+    Some(sender) => println!("<{}> {}", sender, message),
+    None => println!("[Server] {}", message),
+  }
+  for client in player_entities.players.keys() {
+    send_chat(server, *client, sender, message);
+  }
+}
+
+// Tells the client what is actually at the location, rolling back whatever it has predicted locally
+pub fn send_block_state(server: &mut RenetServer, client: u64, game_world: &GameWorld, location: DDD) {
+  if let Some(block) = game_world.get(location) {
+    let message = if block.block == BlockId::Air {
+      ServerMessage::BlockRemove { location }
+    } else {
+      ServerMessage::BlockPlace {
+        location,
+        block_transfer: (*block).into(),
+      }
+    };
+    server.send_message(client, ServerChannel::GameEvent.id(), serialize(&message).unwrap());
+  }
+}
+
+// Sends the message to every other client that sees the chunk the location is in
+pub fn broadcast_but(
+  server: &mut RenetServer,
+  chunk_streams: &ChunkStreams,
+  client_exclude: u64,
+  location: DDD,
+  message: ServerMessage,
+) {
+  let message = serialize(&message).unwrap();
+  for broadcast_client in chunk_streams.watchers(GameWorld::get_chunk_coord(location)) {
     if client_exclude != broadcast_client {
-      server.send_message(
-        broadcast_client,
-        ServerChannel::GameEvent.id(),
-        serialize(&message).unwrap(),
-      );
+      server.send_message(broadcast_client, ServerChannel::GameEvent.id(), message.clone());
     }
   }
 }
 
+// Every client gets a frame of its own, holding only the players standing in chunks it sees, encoded against the
+// last frame it has acknowledged
 pub fn sync_frame(
   mut server: ResMut<RenetServer>,
   mut tick: ResMut<ServerTick>,
+  mut frame_history: ResMut<FrameHistory>,
+  chunk_streams: Res<ChunkStreams>,
   query: Query<(&ClientId, &Transform, &PolarRotation)>,
 ) {
-  let mut players = vec![];
-  let mut translations = vec![];
-  query.iter().for_each(|(id, transform, rotation)| {
-    players.push(id.0);
-    translations.push((transform.translation, *rotation))
-  });
-  let frame = NetworkFrame {
-    tick: tick.0,
-    entities: NetworkedEntities { players, translations },
-  };
+  for client in chunk_streams.clients.keys().copied() {
+    while let Some(message) = server.receive_message(client, ClientChannel::FrameAck.id()) {
+      if let Ok(FrameAck { tick }) = deserialize(&message) {
+        frame_history.ack(client, tick);
+      }
+    }
+    let snapshot: Snapshot = query
+      .iter()
+      .filter(|(id, transform, _)| {
+        id.0 != client && chunk_streams.sees(client, GameWorld::get_chunk_coord(to_ddd(transform.translation)))
+      })
+      .map(|(id, transform, rotation)| (id.0, QuantizedTransform::new(transform.translation, *rotation)))
+      .collect();
+    let frame = NetworkFrame::encode(tick.0, &snapshot, frame_history.baseline(client, tick.0));
+    server.send_message(client, ServerChannel::GameFrame.id(), serialize(&frame).unwrap());
+    frame_history.push(client, tick.0, snapshot);
+  }
   tick.0 += 1;
-  server.broadcast_message(ServerChannel::GameFrame.id(), serialize(&frame).unwrap())
 }
 
 pub fn get_chunk_coord(mut coord: DDD) -> DD {
@@ -0,0 +1,86 @@
+use bevy::prelude::*;
+use bevy::utils::hashbrown::HashSet;
+use std::path::PathBuf;
+
+// Nicknames kept in a text file, one per line. Lines starting with # are comments.
+#[derive(Default)]
+pub struct NicknameList {
+  pub path: Option<PathBuf>,
+  pub nicknames: HashSet<String>,
+}
+
+impl NicknameList {
+  pub fn load(path: Option<PathBuf>) -> Result<Self, String> {
+    let nicknames = match &path {
+      Some(path) => std::fs::read_to_string(path)
+        .map_err(|error| format!("Failed to read {:?}: {}", path, error))?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect(),
+      None => HashSet::new(),
+    };
+    Ok(Self { path, nicknames })
+  }
+
+  // Like load, but a missing file is an empty list, written out the first time it is saved
+  pub fn open(path: PathBuf) -> Result<Self, String> {
+    if path.exists() {
+      Self::load(Some(path))
+    } else {
+      Ok(Self {
+        path: Some(path),
+        nicknames: HashSet::new(),
+      })
+    }
+  }
+
+  pub fn contains(&self, nickname: &str) -> bool {
+    self.nicknames.contains(nickname)
+  }
+
+  pub fn insert(&mut self, nickname: &str) -> bool {
+    self.nicknames.insert(nickname.to_string())
+  }
+
+  pub fn remove(&mut self, nickname: &str) -> bool {
+    self.nicknames.remove(nickname)
+  }
+
+  // Comments in the file don't survive this
+  pub fn save(&self) -> Result<(), String> {
+    let Some(path) = &self.path else {
+      return Ok(());
+    };
+    let mut nicknames: Vec<&String> = self.nicknames.iter().collect();
+    nicknames.sort();
+    let text: String = nicknames.into_iter().map(|nickname| format!("{}\n", nickname)).collect();
+    std::fs::write(path, text).map_err(|error| format!("Failed to write {:?}: {}", path, error))
+  }
+}
+
+pub const BANNED_FILE: &str = "banned.txt";
+
+// Who may join and who may run admin commands
+#[derive(Default, Resource)]
+pub struct AccessLists {
+  // Everybody may join when there is no whitelist
+  pub whitelist: Option<NicknameList>,
+  pub ops: NicknameList,
+  // Kept in the world directory, managed with the ban and unban console commands
+  pub banned: NicknameList,
+}
+
+impl AccessLists {
+  pub fn is_banned(&self, nickname: &str) -> bool {
+    self.banned.contains(nickname)
+  }
+
+  pub fn may_join(&self, nickname: &str) -> bool {
+    self
+      .whitelist
+      .as_ref()
+      .map_or(true, |whitelist| whitelist.contains(nickname))
+  }
+}
@@ -0,0 +1,265 @@
+use crate::ecs::resources::protocol::ProtocolError;
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use bevy::utils::hashbrown::HashMap;
+use bevy_renet::renet::{NETCODE_KEY_BYTES, NETCODE_USER_DATA_BYTES};
+use futures_lite::future::{block_on, poll_once};
+use serde::{Deserialize, Serialize};
+use shikataganai_common::networking::PlayerCommand;
+use hmac::Hmac;
+use pbkdf2::pbkdf2;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+// Lives in the world directory. As long as it is there, only clients holding a token made with it get in.
+pub const KEY_FILE: &str = "server.key";
+
+// Private key of a server running in secure mode
+#[derive(Resource)]
+pub struct ServerKey(pub Option<[u8; NETCODE_KEY_BYTES]>);
+
+// None without a key file. A key file that can't be used is an error, running unsecured instead would let anyone in.
+pub fn load_key(directory: &Path) -> Result<Option<[u8; NETCODE_KEY_BYTES]>, String> {
+  let path = directory.join(KEY_FILE);
+  match std::fs::read(&path) {
+    Ok(data) => data
+      .try_into()
+      .map(Some)
+      .map_err(|_| format!("Invalid key in {:?}, a key is exactly {} bytes long", path, NETCODE_KEY_BYTES)),
+    Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+    Err(error) => Err(format!("Failed to read {:?}: {}", path, error)),
+  }
+}
+
+pub fn create_key(directory: &Path) -> std::io::Result<[u8; NETCODE_KEY_BYTES]> {
+  let key: [u8; NETCODE_KEY_BYTES] = rand::random();
+  std::fs::create_dir_all(directory)?;
+  std::fs::write(directory.join(KEY_FILE), key)?;
+  Ok(key)
+}
+
+// Tokens carry the nickname they were issued for in their user data, length first
+pub fn nickname_user_data(nickname: &str) -> Option<[u8; NETCODE_USER_DATA_BYTES]> {
+  let bytes = nickname.as_bytes();
+  if bytes.len() >= NETCODE_USER_DATA_BYTES {
+    return None;
+  }
+  let mut data = [0; NETCODE_USER_DATA_BYTES];
+  data[0] = bytes.len() as u8;
+  data[1..=bytes.len()].copy_from_slice(bytes);
+  Some(data)
+}
+
+pub fn user_data_nickname(data: &[u8; NETCODE_USER_DATA_BYTES]) -> Option<String> {
+  String::from_utf8(data[1..=data[0] as usize].to_vec()).ok()
+}
+
+// PBKDF2-HMAC-SHA256 rounds for new reservations. Slow enough that a leaked nicknames.bin doesn't give the passwords
+// away, which is why passwords are checked on the task pool and not on the tick.
+const PASSWORD_ROUNDS: u32 = 100_000;
+// Seconds a nickname can't be tried again after a wrong password, so that reconnecting over and over doesn't keep
+// the task pool hashing
+const PASSWORD_RETRY_DELAY: f64 = 2.0;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Reservation {
+  salt: [u8; 16],
+  hash: [u8; 32],
+  // 0 for reservations made when the password went through a single SHA-256, they are upgraded on the next join
+  rounds: u32,
+}
+
+impl Reservation {
+  fn new(password: &str) -> Self {
+    let salt = rand::random();
+    Self {
+      salt,
+      hash: hash_password(&salt, password, PASSWORD_ROUNDS),
+      rounds: PASSWORD_ROUNDS,
+    }
+  }
+
+  fn accepts(&self, password: &str) -> bool {
+    hash_password(&self.salt, password, self.rounds) == self.hash
+  }
+}
+
+// The layout of nicknames.bin before reservations had rounds
+#[derive(Deserialize)]
+struct LegacyReservation {
+  salt: [u8; 16],
+  hash: [u8; 32],
+}
+
+fn hash_password(salt: &[u8; 16], password: &str, rounds: u32) -> [u8; 32] {
+  let mut hash = [0; 32];
+  if rounds == 0 {
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update(password.as_bytes());
+    hash = hasher.finalize().into();
+  } else {
+    pbkdf2::<Hmac<Sha256>>(password.as_bytes(), salt, rounds, &mut hash);
+  }
+  hash
+}
+
+// Nicknames claimed with a password, only used when the server isn't running in secure mode
+#[derive(Resource)]
+pub struct NicknameReservations {
+  pub path: PathBuf,
+  pub reserved: HashMap<String, Reservation>,
+}
+
+impl NicknameReservations {
+  // Only a missing file means nothing is reserved, anything else would hand the reserved nicknames to whoever asks
+  pub fn load<P: Into<PathBuf>>(path: P) -> Result<Self, String> {
+    let path = path.into();
+    let mut data = vec![];
+    let reserved = match File::open(&path).and_then(|mut file| file.read_to_end(&mut data)) {
+      Ok(_) => bincode::deserialize(&data)
+        .or_else(|_| {
+          bincode::deserialize::<HashMap<String, LegacyReservation>>(&data).map(|reserved| {
+            reserved
+              .into_iter()
+              .map(|(nickname, LegacyReservation { salt, hash })| (nickname, Reservation { salt, hash, rounds: 0 }))
+              .collect()
+          })
+        })
+        .map_err(|error| format!("Failed to read nickname reservations {:?}: {}", path, error))?,
+      Err(error) if error.kind() == ErrorKind::NotFound => HashMap::new(),
+      Err(error) => return Err(format!("Failed to read nickname reservations {:?}: {}", path, error)),
+    };
+    Ok(Self { path, reserved })
+  }
+
+  pub fn save(&self) {
+    if let Some(directory) = self.path.parent() && let Err(error) = std::fs::create_dir_all(directory) {
+      println!("Failed to create {:?}: {}", directory, error);
+      return;
+    }
+    let data = bincode::serialize(&self.reserved).unwrap();
+    let temporary = self.path.with_extension("tmp");
+    let result = File::create(&temporary)
+      .and_then(|mut file| file.write_all(&data).and_then(|_| file.sync_all()))
+      .and_then(|_| std::fs::rename(&temporary, &self.path));
+    if let Err(error) = result {
+      println!("Failed to write nickname reservations {:?}: {}", self.path, error);
+    }
+  }
+
+  // A free nickname given with a password becomes reserved, a reserved one takes nothing but its password. None if
+  // there is no password to hash.
+  pub fn check(&self, nickname: &str, password: Option<&str>) -> Result<Option<PasswordCheck>, ProtocolError> {
+    match (self.reserved.get(nickname), password) {
+      (Some(_), None) => Err(ProtocolError::WrongPassword(nickname.to_string())),
+      (None, None) => Ok(None),
+      (existing, Some(password)) => Ok(Some(PasswordCheck {
+        nickname: nickname.to_string(),
+        existing: existing.cloned(),
+        password: password.to_string(),
+      })),
+    }
+  }
+
+  // Stores what a PasswordCheck came up with, unless the reservation changed while it ran
+  pub fn apply(&mut self, verified: VerifiedPassword) -> Result<(), ProtocolError> {
+    if self.reserved.get(&verified.nickname).map(|reservation| reservation.hash) != verified.against {
+      return Err(ProtocolError::WrongPassword(verified.nickname));
+    }
+    if let Some(reservation) = verified.store {
+      if self.reserved.insert(verified.nickname.clone(), reservation).is_none() {
+        println!("Nickname {} is now reserved", verified.nickname);
+      }
+      self.save();
+    }
+    Ok(())
+  }
+}
+
+pub struct PasswordCheck {
+  nickname: String,
+  existing: Option<Reservation>,
+  password: String,
+}
+
+pub struct VerifiedPassword {
+  nickname: String,
+  // Hash of the reservation the password was checked against
+  against: Option<[u8; 32]>,
+  // New or upgraded reservation
+  store: Option<Reservation>,
+}
+
+impl PasswordCheck {
+  // The nickname back if the password is wrong
+  fn run(self) -> Result<VerifiedPassword, String> {
+    let against = self.existing.as_ref().map(|reservation| reservation.hash);
+    let store = match self.existing {
+      Some(reservation) if !reservation.accepts(&self.password) => return Err(self.nickname),
+      Some(reservation) if reservation.rounds == PASSWORD_ROUNDS => None,
+      _ => Some(Reservation::new(&self.password)),
+    };
+    Ok(VerifiedPassword {
+      nickname: self.nickname,
+      against,
+      store,
+    })
+  }
+}
+
+// Joins waiting for their password to be hashed, and the ones handle_events can let through now, by client
+#[derive(Default, Resource)]
+pub struct PasswordChecks {
+  pending: HashMap<u64, (Task<Result<VerifiedPassword, String>>, PlayerCommand)>,
+  pub verified: HashMap<u64, (PlayerCommand, VerifiedPassword)>,
+  // When each nickname last had a wrong password
+  failures: HashMap<String, f64>,
+}
+
+impl PasswordChecks {
+  pub fn is_pending(&self, client: u64) -> bool {
+    self.pending.contains_key(&client) || self.verified.contains_key(&client)
+  }
+
+  // The PlayerAuth comes back through verified once the password turns out right
+  pub fn start(&mut self, client: u64, check: PasswordCheck, command: PlayerCommand, now: f64) -> Result<(), ProtocolError> {
+    if let Some(failed) = self.failures.get(&check.nickname) && now - failed < PASSWORD_RETRY_DELAY {
+      return Err(ProtocolError::WrongPassword(check.nickname));
+    }
+    let task = AsyncComputeTaskPool::get().spawn(async move { check.run() });
+    self.pending.insert(client, (task, command));
+    Ok(())
+  }
+
+  // Clients and nicknames of the checks that failed
+  pub fn poll(&mut self, now: f64) -> Vec<(u64, String)> {
+    let finished: Vec<_> = self
+      .pending
+      .iter_mut()
+      .filter_map(|(client, (task, _))| block_on(poll_once(task)).map(|result| (*client, result)))
+      .collect();
+    let mut refused = vec![];
+    for (client, result) in finished {
+      let (_, command) = self.pending.remove(&client).unwrap();
+      match result {
+        Ok(verified) => {
+          self.verified.insert(client, (command, verified));
+        }
+        Err(nickname) => {
+          self.failures.insert(nickname.clone(), now);
+          refused.push((client, nickname));
+        }
+      }
+    }
+    self.failures.retain(|_, failed| now - *failed < PASSWORD_RETRY_DELAY);
+    refused
+  }
+
+  pub fn remove(&mut self, client: u64) {
+    self.pending.remove(&client);
+    self.verified.remove(&client);
+  }
+}
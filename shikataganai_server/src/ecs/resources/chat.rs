@@ -0,0 +1,46 @@
+use bevy::prelude::*;
+use bevy::utils::hashbrown::HashMap;
+use shikataganai_common::networking::MAX_CHAT_LENGTH;
+
+// Messages a player may send in a row, and how many per second come back after that
+pub const CHAT_BURST: f32 = 5.0;
+pub const CHAT_RATE: f32 = 1.0;
+
+struct ChatAllowance {
+  messages: f32,
+  last_message: f64,
+}
+
+#[derive(Default, Resource)]
+pub struct ChatLimiter {
+  clients: HashMap<u64, ChatAllowance>,
+}
+
+impl ChatLimiter {
+  // The message the way it is passed on, or why it isn't
+  pub fn check(&mut self, client: u64, message: &str, now: f64) -> Result<String, String> {
+    let message: String = message.chars().filter(|c| !c.is_control()).collect();
+    let message = message.trim();
+    if message.is_empty() {
+      return Err("Empty message".to_string());
+    }
+    if message.chars().count() > MAX_CHAT_LENGTH {
+      return Err(format!("Messages are at most {} characters long", MAX_CHAT_LENGTH));
+    }
+    let allowance = self.clients.entry(client).or_insert(ChatAllowance {
+      messages: CHAT_BURST,
+      last_message: now,
+    });
+    allowance.messages = (allowance.messages + (now - allowance.last_message) as f32 * CHAT_RATE).min(CHAT_BURST);
+    allowance.last_message = now;
+    if allowance.messages < 1.0 {
+      return Err("You are sending messages too fast".to_string());
+    }
+    allowance.messages -= 1.0;
+    Ok(message.to_string())
+  }
+
+  pub fn remove(&mut self, client: u64) {
+    self.clients.remove(&client);
+  }
+}
@@ -0,0 +1,91 @@
+use bevy::prelude::*;
+use bevy::utils::hashbrown::{HashMap, HashSet};
+use shikataganai_common::networking::{ChunkFragment, MAX_VIEW_DISTANCE};
+use shikataganai_common::util::array::DD;
+use std::collections::VecDeque;
+
+// Bytes of chunk data a client gets per second. Unused allowance piles up to a second worth of it.
+pub const CHUNK_BANDWIDTH: usize = 256 * 1024;
+
+pub struct ClientStream {
+  pub view_distance: i32,
+  // Chunk the player stood in when the view was last updated
+  pub center: Option<DD>,
+  // Chunks the client has or is about to get
+  pub visible: HashSet<DD>,
+  // Chunks the client got all of, until they are unloaded
+  pub delivered: HashSet<DD>,
+  // Chunks the client is waiting for, they may still be generating
+  pub requested: Vec<DD>,
+  // What is left of the chunk being sent right now
+  pub sending: VecDeque<ChunkFragment>,
+  pub allowance: usize,
+}
+
+impl ClientStream {
+  // The chunk being sent was encoded already, the client holds on to updates for it until it arrives
+  fn has(&self, chunk_coord: DD) -> bool {
+    self.delivered.contains(&chunk_coord)
+      || self.sending.front().map_or(false, |fragment| fragment.chunk_coord == chunk_coord)
+  }
+}
+
+#[derive(Default, Resource)]
+pub struct ChunkStreams {
+  pub clients: HashMap<u64, ClientStream>,
+}
+
+impl ChunkStreams {
+  // Starts streaming to an authenticated client, returns the view distance it has been granted
+  pub fn open(&mut self, client: u64, view_distance: u32) -> u32 {
+    let view_distance = view_distance.clamp(1, MAX_VIEW_DISTANCE);
+    self.clients.insert(
+      client,
+      ClientStream {
+        view_distance: view_distance as i32,
+        center: None,
+        visible: HashSet::new(),
+        delivered: HashSet::new(),
+        requested: vec![],
+        sending: VecDeque::new(),
+        allowance: 0,
+      },
+    );
+    view_distance
+  }
+
+  // Chunks outside of the client's view are not sent
+  pub fn request(&mut self, client: u64, chunk_coord: DD) -> bool {
+    match self.clients.get_mut(&client) {
+      Some(stream) if stream.visible.contains(&chunk_coord) => {
+        if !stream.requested.contains(&chunk_coord) {
+          stream.requested.push(chunk_coord);
+        }
+        true
+      }
+      _ => false,
+    }
+  }
+
+  pub fn is_watched(&self, chunk_coord: DD) -> bool {
+    self.clients.values().any(|stream| stream.visible.contains(&chunk_coord))
+  }
+
+  // Whether the client has the chunk, updates for it are of no use to anyone else. Queued chunks are encoded when
+  // their turn comes and carry the updates already.
+  pub fn sees(&self, client: u64, chunk_coord: DD) -> bool {
+    self.clients.get(&client).map_or(false, |stream| stream.has(chunk_coord))
+  }
+
+  pub fn watchers(&self, chunk_coord: DD) -> impl Iterator<Item = u64> + '_ {
+    self
+      .clients
+      .iter()
+      .filter(move |(_, stream)| stream.has(chunk_coord))
+      .map(|(client, _)| *client)
+  }
+
+  pub fn remove(&mut self, client: u64) {
+    self.clients.remove(&client);
+  }
+}
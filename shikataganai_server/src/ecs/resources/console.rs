@@ -0,0 +1,242 @@
+use bevy::prelude::*;
+use shikataganai_common::ecs::components::blocks::block_id::BlockId;
+use shikataganai_common::ecs::components::blocks::BlockOrItem;
+use shikataganai_common::ecs::resources::registries::Registries;
+use shikataganai_common::util::array::DDD;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::str::FromStr;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+
+pub const CONSOLE_HELP: &str = "list                              Connected players: client, nickname, x, y, z separated by tabs
+kick <NICKNAME> [REASON]          Disconnect a player
+ban <NICKNAME> [REASON]           Disconnect a player and keep the nickname from joining again
+unban <NICKNAME>                  Let a banned nickname join again
+tp <NICKNAME> <X> <Y> <Z>         Move a player to a position
+tp <NICKNAME> <OTHER_NICKNAME>    Move a player to another one
+give <NICKNAME> <NAME> [COUNT]    Put blocks or items into a player's inventory
+setblock <X> <Y> <Z> <BLOCK>      Replace the block at a location, the chunk has to be loaded and a chest there empty
+save                              Write the world and the players to disk
+reload                            Read the recipes again, the current ones stay if the file has errors
+stop                              Save and shut the server down
+say <MESSAGE>                     Show a message to every player
+help                              Print this message";
+
+pub enum Destination {
+  Position(Vec3),
+  Player(String),
+}
+
+pub enum AdminCommand {
+  Help,
+  List,
+  Kick { nickname: String, reason: Option<String> },
+  Ban { nickname: String, reason: Option<String> },
+  Unban { nickname: String },
+  Teleport { nickname: String, destination: Destination },
+  Give { nickname: String, block_or_item: BlockOrItem, quant: u32 },
+  SetBlock { location: DDD, block: BlockId },
+  Save,
+  Reload,
+  Stop,
+  Say { message: String },
+}
+
+fn parse_arg<T: FromStr>(name: &str, word: Option<&str>) -> Result<T, String> {
+  let word = word.ok_or_else(|| format!("Missing {}", name))?;
+  word.parse().map_err(|_| format!("Invalid {} {}", name, word))
+}
+
+// Whatever is left of the line, None if that is nothing
+fn rest<'a>(words: impl Iterator<Item = &'a str>) -> Option<String> {
+  let rest = words.collect::<Vec<_>>().join(" ");
+  (!rest.is_empty()).then_some(rest)
+}
+
+impl AdminCommand {
+  // Block and item names are looked up in the server's registries
+  pub fn parse(line: &str, registries: &Registries) -> Result<Self, String> {
+    let mut words = line.split_whitespace();
+    let command = match words.next().unwrap_or("") {
+      "help" => AdminCommand::Help,
+      "list" => AdminCommand::List,
+      "kick" => AdminCommand::Kick {
+        nickname: parse_arg("nickname", words.next())?,
+        reason: rest(words.by_ref()),
+      },
+      "ban" => AdminCommand::Ban {
+        nickname: parse_arg("nickname", words.next())?,
+        reason: rest(words.by_ref()),
+      },
+      "unban" => AdminCommand::Unban {
+        nickname: parse_arg("nickname", words.next())?,
+      },
+      "tp" => {
+        let nickname = parse_arg("nickname", words.next())?;
+        let target: String = parse_arg("destination", words.next())?;
+        let destination = match target.parse::<f32>() {
+          Ok(x) => Destination::Position(Vec3::new(
+            x,
+            parse_arg("y", words.next())?,
+            parse_arg("z", words.next())?,
+          )),
+          Err(_) => Destination::Player(target),
+        };
+        AdminCommand::Teleport { nickname, destination }
+      }
+      "give" => {
+        let nickname = parse_arg("nickname", words.next())?;
+        let name: String = parse_arg("block or item", words.next())?;
+        let block_or_item = registries
+          .by_name(&name.to_lowercase())
+          .ok_or_else(|| format!("Unknown block or item {}", name))?;
+        let quant = match words.next() {
+          Some(count) => parse_arg("count", Some(count))?,
+          None => 1,
+        };
+        if quant == 0 {
+          return Err("Count has to be at least 1".to_string());
+        }
+        AdminCommand::Give {
+          nickname,
+          block_or_item,
+          quant,
+        }
+      }
+      "setblock" => {
+        let location = (
+          parse_arg("x", words.next())?,
+          parse_arg("y", words.next())?,
+          parse_arg("z", words.next())?,
+        );
+        let name: String = parse_arg("block", words.next())?;
+        let block = registries
+          .blocks
+          .by_name(&name.to_lowercase())
+          .ok_or_else(|| format!("Unknown block {}", name))?;
+        AdminCommand::SetBlock { location, block }
+      }
+      "save" => AdminCommand::Save,
+      "reload" => AdminCommand::Reload,
+      "stop" => AdminCommand::Stop,
+      "say" => AdminCommand::Say {
+        message: rest(words.by_ref()).ok_or_else(|| "Missing message".to_string())?,
+      },
+      "" => return Err("Empty command".to_string()),
+      command => return Err(format!("Unknown command {}, try help", command)),
+    };
+    match words.next() {
+      Some(word) => Err(format!("Unexpected {}", word)),
+      None => Ok(command),
+    }
+  }
+}
+
+// Where the answer to a command goes
+pub enum ConsoleReply {
+  Stdout,
+  Remote(Sender<String>),
+  // An operator's slash-command, answered in chat
+  Player(u64),
+}
+
+pub struct ConsoleRequest {
+  pub line: String,
+  pub reply: ConsoleReply,
+}
+
+// Lines are read on threads of their own and picked up by admin_console every tick
+#[derive(Resource)]
+pub struct AdminConsole {
+  requests: Mutex<Receiver<ConsoleRequest>>,
+  sender: Mutex<Sender<ConsoleRequest>>,
+}
+
+impl AdminConsole {
+  pub fn spawn(stdin: bool, rcon: Option<SocketAddr>, password: String) -> Result<Self, String> {
+    let (sender, receiver) = channel();
+    let console = Self {
+      requests: Mutex::new(receiver),
+      sender: Mutex::new(sender.clone()),
+    };
+    if let Some(address) = rcon {
+      let listener =
+        TcpListener::bind(address).map_err(|error| format!("Failed to bind the remote console to {}: {}", address, error))?;
+      println!("Remote console listening on {}", address);
+      let sender = sender.clone();
+      std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+          let sender = sender.clone();
+          let password = password.clone();
+          std::thread::spawn(move || {
+            if let Err(error) = serve_rcon(stream, sender, &password) {
+              println!("Remote console: {}", error);
+            }
+          });
+        }
+      });
+    }
+    if stdin {
+      std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+          let Ok(line) = line else {
+            break;
+          };
+          let request = ConsoleRequest {
+            line,
+            reply: ConsoleReply::Stdout,
+          };
+          if sender.send(request).is_err() {
+            break;
+          }
+        }
+      });
+    }
+    Ok(console)
+  }
+
+  pub fn submit(&self, request: ConsoleRequest) {
+    let _ = self.sender.lock().unwrap().send(request);
+  }
+
+  pub fn drain(&self) -> Vec<ConsoleRequest> {
+    self.requests.lock().unwrap().try_iter().collect()
+  }
+}
+
+// The first line is the password, every line after it a command. Answers are followed by an empty line, so that
+// scripts can tell where they end.
+fn serve_rcon(stream: TcpStream, requests: Sender<ConsoleRequest>, password: &str) -> std::io::Result<()> {
+  let peer = stream.peer_addr()?;
+  let mut writer = stream.try_clone()?;
+  let mut lines = BufReader::new(stream).lines();
+  if lines.next().transpose()?.as_deref() != Some(password) {
+    println!("Remote console {}: wrong password", peer);
+    return write!(writer, "error: wrong password\n\n");
+  }
+  println!("Remote console {} connected", peer);
+  write!(writer, "ok\n\n")?;
+  for line in lines {
+    let line = line?;
+    println!("Remote console {}: {}", peer, line);
+    let (reply, answer) = channel();
+    let request = ConsoleRequest {
+      line,
+      reply: ConsoleReply::Remote(reply),
+    };
+    if requests.send(request).is_err() {
+      break;
+    }
+    let Ok(answer) = answer.recv() else {
+      break;
+    };
+    if answer.is_empty() {
+      writeln!(writer)?;
+    } else {
+      write!(writer, "{}\n\n", answer)?;
+    }
+  }
+  println!("Remote console {} disconnected", peer);
+  Ok(())
+}
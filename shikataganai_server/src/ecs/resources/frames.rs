@@ -0,0 +1,55 @@
+use bevy::prelude::*;
+use bevy::utils::hashbrown::HashMap;
+use shikataganai_common::networking::{Snapshot, FRAME_HISTORY};
+use std::collections::VecDeque;
+
+#[derive(Default)]
+pub struct ClientFrames {
+  // Newest frame the client confirmed to have
+  acked: Option<u32>,
+  // Frames sent within the last FRAME_HISTORY ticks, oldest first
+  sent: VecDeque<(u32, Snapshot)>,
+}
+
+// What every client has been sent, so that frames can be encoded against something the client is known to have
+#[derive(Default, Resource)]
+pub struct FrameHistory {
+  pub clients: HashMap<u64, ClientFrames>,
+}
+
+impl FrameHistory {
+  pub fn ack(&mut self, client: u64, tick: u32) {
+    if let Some(frames) = self.clients.get_mut(&client)
+      && frames.acked.map_or(true, |acked| acked < tick)
+      && frames.sent.iter().any(|(sent, _)| *sent == tick)
+    {
+      frames.acked = Some(tick);
+    }
+  }
+
+  // The baseline for a frame sent on the given tick, if there is one recent enough
+  pub fn baseline(&self, client: u64, tick: u32) -> Option<(u32, &Snapshot)> {
+    let frames = self.clients.get(&client)?;
+    let acked = frames.acked?;
+    if tick - acked >= FRAME_HISTORY {
+      return None;
+    }
+    frames
+      .sent
+      .iter()
+      .find(|(sent, _)| *sent == acked)
+      .map(|(sent, snapshot)| (*sent, snapshot))
+  }
+
+  pub fn push(&mut self, client: u64, tick: u32, snapshot: Snapshot) {
+    let frames = self.clients.entry(client).or_default();
+    while let Some((oldest, _)) = frames.sent.front() && tick - oldest >= FRAME_HISTORY {
+      frames.sent.pop_front();
+    }
+    frames.sent.push_back((tick, snapshot));
+  }
+
+  pub fn remove(&mut self, client: u64) {
+    self.clients.remove(&client);
+  }
+}
@@ -1 +1,11 @@
+pub mod access;
+pub mod auth;
+pub mod chat;
+pub mod chunk_stream;
+pub mod console;
+pub mod frames;
+pub mod players;
+pub mod protocol;
+pub mod storage;
+pub mod validation;
 pub mod world;
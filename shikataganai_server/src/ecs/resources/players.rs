@@ -0,0 +1,52 @@
+use bevy::prelude::*;
+use bevy::utils::hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use shikataganai_common::ecs::resources::player::PlayerInventory;
+use shikataganai_common::networking::PolarRotation;
+use std::fs::File;
+use std::io::{ErrorKind, Read, Write};
+use std::path::PathBuf;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PlayerData {
+  pub translation: Vec3,
+  pub rotation: PolarRotation,
+  pub inventory: PlayerInventory,
+}
+
+// Everything the server remembers about players between sessions, keyed by nickname
+#[derive(Resource)]
+pub struct PlayerDatabase {
+  pub path: PathBuf,
+  pub players: HashMap<String, PlayerData>,
+}
+
+impl PlayerDatabase {
+  // A database that is there but can't be read stops the server, the next save would overwrite it otherwise
+  pub fn load<P: Into<PathBuf>>(path: P) -> Result<Self, String> {
+    let path = path.into();
+    let mut data = vec![];
+    let players = match File::open(&path).and_then(|mut file| file.read_to_end(&mut data)) {
+      Ok(_) => bincode::deserialize(&data)
+        .map_err(|error| format!("Failed to read player database {:?}: {}", path, error))?,
+      Err(error) if error.kind() == ErrorKind::NotFound => HashMap::new(),
+      Err(error) => return Err(format!("Failed to read player database {:?}: {}", path, error)),
+    };
+    Ok(Self { path, players })
+  }
+
+  pub fn save(&self) {
+    if let Some(directory) = self.path.parent() && let Err(error) = std::fs::create_dir_all(directory) {
+      println!("Failed to create {:?}: {}", directory, error);
+      return;
+    }
+    let data = bincode::serialize(&self.players).unwrap();
+    let temporary = self.path.with_extension("tmp");
+    let result = File::create(&temporary)
+      .and_then(|mut file| file.write_all(&data).and_then(|_| file.sync_all()))
+      .and_then(|_| std::fs::rename(&temporary, &self.path));
+    if let Err(error) = result {
+      println!("Failed to write player database {:?}: {}", self.path, error);
+    }
+  }
+}
@@ -0,0 +1,120 @@
+use bevy::prelude::*;
+use bevy::utils::hashbrown::HashMap;
+use bevy_renet::renet::RenetServer;
+use bincode::serialize;
+use shikataganai_common::networking::{ServerChannel, ServerMessage, PROTOCOL_VERSION};
+use std::fmt::{Display, Formatter};
+
+// Violations a client gets away with before it is dropped
+pub const MAX_PROTOCOL_VIOLATIONS: u32 = 16;
+// Seconds a kicked client is given to receive the reason before the connection is cut
+pub const DISCONNECT_GRACE: f64 = 0.5;
+
+pub enum ProtocolError {
+  // The message doesn't decode as a command
+  Malformed(String),
+  // A command that needs a player arrived before PlayerAuth
+  Unauthenticated(String),
+  // PlayerAuth from a client that already has a player
+  AlreadyAuthenticated,
+  // PlayerAuth as somebody who is already playing
+  NicknameTaken(String),
+  // PlayerAuth from a build speaking another version of the protocol
+  VersionMismatch(u32),
+  // PlayerAuth for a reserved nickname without its password
+  WrongPassword(String),
+  // PlayerAuth for another nickname than the connect token was issued for
+  TokenMismatch(String),
+  // PlayerAuth for a nickname missing from the whitelist
+  NotWhitelisted(String),
+  // PlayerAuth for a banned nickname
+  Banned(String),
+}
+
+impl ProtocolError {
+  // Errors the client can't recover from drop it straight away
+  pub fn is_fatal(&self) -> bool {
+    matches!(
+      self,
+      ProtocolError::NicknameTaken(_)
+        | ProtocolError::VersionMismatch(_)
+        | ProtocolError::WrongPassword(_)
+        | ProtocolError::TokenMismatch(_)
+        | ProtocolError::NotWhitelisted(_)
+        | ProtocolError::Banned(_)
+    )
+  }
+}
+
+impl Display for ProtocolError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ProtocolError::Malformed(error) => write!(f, "Malformed message: {}", error),
+      ProtocolError::Unauthenticated(command) => write!(f, "{} before authentication", command),
+      ProtocolError::AlreadyAuthenticated => f.write_str("Already authenticated"),
+      ProtocolError::NicknameTaken(nickname) => write!(f, "{} is already playing", nickname),
+      ProtocolError::VersionMismatch(version) => write!(
+        f,
+        "Protocol version {} is not supported, the server runs version {}",
+        version, PROTOCOL_VERSION
+      ),
+      ProtocolError::WrongPassword(nickname) => write!(f, "Wrong password for {}", nickname),
+      ProtocolError::TokenMismatch(nickname) => write!(f, "The connect token wasn't issued for {}", nickname),
+      ProtocolError::NotWhitelisted(nickname) => write!(f, "{} is not whitelisted", nickname),
+      ProtocolError::Banned(nickname) => write!(f, "{} is banned from this server", nickname),
+    }
+  }
+}
+
+#[derive(Default, Resource)]
+pub struct ProtocolViolations {
+  counts: HashMap<u64, u32>,
+  // Clients that have been told why they are dropped, with the time they are actually disconnected at
+  kicked: HashMap<u64, f64>,
+}
+
+impl ProtocolViolations {
+  pub fn report(&mut self, server: &mut RenetServer, client: u64, error: ProtocolError, now: f64) {
+    println!("Client {}: {}", client, error);
+    let count = self.counts.entry(client).or_default();
+    *count += 1;
+    if error.is_fatal() {
+      self.kick(server, client, error.to_string(), now);
+    } else if *count > MAX_PROTOCOL_VIOLATIONS {
+      self.kick(server, client, format!("Too many protocol errors, last one: {}", error), now);
+    }
+  }
+
+  pub fn kick(&mut self, server: &mut RenetServer, client: u64, reason: String, now: f64) {
+    if self.kicked.contains_key(&client) {
+      return;
+    }
+    println!("Kicking client {}: {}", client, reason);
+    server.send_message(
+      client,
+      ServerChannel::GameEvent.id(),
+      serialize(&ServerMessage::Disconnect { reason }).unwrap(),
+    );
+    self.kicked.insert(client, now + DISCONNECT_GRACE);
+  }
+
+  // Nothing a kicked client sends is looked at anymore
+  pub fn is_kicked(&self, client: u64) -> bool {
+    self.kicked.contains_key(&client)
+  }
+
+  // Clients whose grace period is over
+  pub fn due(&self, now: f64) -> Vec<u64> {
+    self
+      .kicked
+      .iter()
+      .filter(|(_, at)| **at <= now)
+      .map(|(client, _)| *client)
+      .collect()
+  }
+
+  pub fn remove(&mut self, client: u64) {
+    self.counts.remove(&client);
+    self.kicked.remove(&client);
+  }
+}
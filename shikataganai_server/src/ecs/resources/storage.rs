@@ -0,0 +1,270 @@
+use bevy::prelude::*;
+use bevy::utils::hashbrown::HashMap;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use shikataganai_common::ecs::components::chunk::{Chunk, WorldHeight};
+use shikataganai_common::ecs::components::functors::InternalInventory;
+use shikataganai_common::util::array::{DD, DDD};
+use shikataganai_common::worldgen::TerrainMode;
+use std::fs::File;
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+// Region files group REGION_WIDTH x REGION_WIDTH chunks. A region file starts with a header of
+// (offset: u32, length: u32) little-endian pairs, one per chunk, followed by zlib-compressed chunk blobs.
+// A length of 0 means the chunk has never been saved.
+pub const REGION_WIDTH: i32 = 32;
+const REGION_CHUNKS: usize = (REGION_WIDTH * REGION_WIDTH) as usize;
+const REGION_HEADER_SIZE: usize = REGION_CHUNKS * 8;
+
+// Settings fixed at world creation, kept in level.bin next to the regions
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WorldMetadata {
+  pub seed: u64,
+  pub terrain: TerrainMode,
+  pub height: WorldHeight,
+}
+
+// level.bin is LEVEL_MAGIC followed by (version, WorldMetadata)
+const LEVEL_MAGIC: [u8; 4] = *b"SKLV";
+const LEVEL_VERSION: u32 = 1;
+
+fn decode_metadata(data: &[u8]) -> Result<WorldMetadata, String> {
+  let data = data
+    .strip_prefix(&LEVEL_MAGIC)
+    .ok_or_else(|| "Not a world settings file".to_string())?;
+  let (version, metadata): (u32, WorldMetadata) = bincode::deserialize(data).map_err(|error| error.to_string())?;
+  if version != LEVEL_VERSION {
+    return Err(format!("Version {} is not supported, expected {}", version, LEVEL_VERSION));
+  }
+  Ok(metadata)
+}
+
+#[derive(Deserialize)]
+pub struct SavedChunk {
+  pub chunk: Chunk,
+  pub inventories: Vec<(DDD, InternalInventory)>,
+}
+
+// Borrowed twin of SavedChunk so that saving doesn't need to clone the chunk. Both serialize identically.
+#[derive(Serialize)]
+struct SavedChunkRef<'a> {
+  chunk: &'a Chunk,
+  inventories: Vec<(DDD, &'a InternalInventory)>,
+}
+
+struct Region {
+  chunks: Vec<Option<Vec<u8>>>,
+  dirty: bool,
+}
+
+impl Region {
+  fn empty() -> Self {
+    Self {
+      chunks: vec![None; REGION_CHUNKS],
+      dirty: false,
+    }
+  }
+
+  fn read(path: &Path) -> std::io::Result<Self> {
+    let mut data = vec![];
+    File::open(path)?.read_to_end(&mut data)?;
+    if data.len() < REGION_HEADER_SIZE {
+      return Err(std::io::Error::new(ErrorKind::InvalidData, "Region header is truncated"));
+    }
+    let mut chunks = Vec::with_capacity(REGION_CHUNKS);
+    for entry in data[..REGION_HEADER_SIZE].chunks_exact(8) {
+      let offset = u32::from_le_bytes(entry[0..4].try_into().unwrap()) as usize;
+      let length = u32::from_le_bytes(entry[4..8].try_into().unwrap()) as usize;
+      if length == 0 {
+        chunks.push(None);
+        continue;
+      }
+      let blob = data
+        .get(offset..offset + length)
+        .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidData, "Region entry points outside of the file"))?;
+      chunks.push(Some(blob.to_vec()));
+    }
+    Ok(Self { chunks, dirty: false })
+  }
+
+  fn write(&self, path: &Path) -> std::io::Result<()> {
+    let mut header = Vec::with_capacity(REGION_HEADER_SIZE);
+    let mut body = vec![];
+    for chunk in self.chunks.iter() {
+      match chunk {
+        None => header.extend_from_slice(&[0; 8]),
+        Some(blob) => {
+          header.extend_from_slice(&((REGION_HEADER_SIZE + body.len()) as u32).to_le_bytes());
+          header.extend_from_slice(&(blob.len() as u32).to_le_bytes());
+          body.extend_from_slice(blob);
+        }
+      }
+    }
+    // Write next to the original and swap it in, so a crash mid-save never leaves a half written region behind
+    let temporary = path.with_extension("tmp");
+    let mut file = File::create(&temporary)?;
+    file.write_all(&header)?;
+    file.write_all(&body)?;
+    file.sync_all()?;
+    std::fs::rename(temporary, path)
+  }
+}
+
+#[derive(Resource)]
+pub struct WorldStorage {
+  pub directory: PathBuf,
+  regions: HashMap<DD, Region>,
+}
+
+impl WorldStorage {
+  pub fn new<P: Into<PathBuf>>(directory: P) -> Self {
+    Self {
+      directory: directory.into(),
+      regions: HashMap::new(),
+    }
+  }
+
+  // None for a world that hasn't been created yet. Anything else that goes wrong keeps the server from starting,
+  // a new seed would change how the world generates from here on.
+  pub fn load_metadata(&self) -> Result<Option<WorldMetadata>, String> {
+    let path = self.directory.join("level.bin");
+    let mut data = vec![];
+    match File::open(&path).and_then(|mut file| file.read_to_end(&mut data)) {
+      Ok(_) => decode_metadata(&data)
+        .map(Some)
+        .map_err(|error| format!("Failed to read world settings from {:?}: {}", path, error)),
+      Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+      Err(error) => Err(format!("Failed to read {:?}: {}", path, error)),
+    }
+  }
+
+  pub fn store_metadata(&self, metadata: &WorldMetadata) -> Result<(), String> {
+    let path = self.directory.join("level.bin");
+    let mut data = LEVEL_MAGIC.to_vec();
+    data.extend(bincode::serialize(&(LEVEL_VERSION, metadata)).unwrap());
+    let temporary = path.with_extension("tmp");
+    std::fs::create_dir_all(&self.directory)
+      .and_then(|_| File::create(&temporary))
+      .and_then(|mut file| file.write_all(&data).and_then(|_| file.sync_all()))
+      .and_then(|_| std::fs::rename(&temporary, &path))
+      .map_err(|error| format!("Failed to write {:?}: {}", path, error))
+  }
+
+  // Block or item names in id order, empty for worlds that only ever had the built-in ones. A file that can't be read
+  // is an error, starting over would give the ids in the saved chunks and inventories to other blocks and items.
+  pub fn load_ids(&self, file: &str) -> Result<Vec<String>, String> {
+    let path = self.directory.join(file);
+    let mut data = vec![];
+    match File::open(&path).and_then(|mut file| file.read_to_end(&mut data)) {
+      Ok(_) => bincode::deserialize(&data).map_err(|error| format!("Failed to deserialize {:?}: {}", path, error)),
+      Err(error) if error.kind() == ErrorKind::NotFound => Ok(vec![]),
+      Err(error) => Err(format!("Failed to read {:?}: {}", path, error)),
+    }
+  }
+
+  pub fn store_ids(&self, file: &str, names: &[String]) -> Result<(), String> {
+    let path = self.directory.join(file);
+    let data = bincode::serialize(names).unwrap();
+    let temporary = path.with_extension("tmp");
+    std::fs::create_dir_all(&self.directory)
+      .and_then(|_| File::create(&temporary))
+      .and_then(|mut file| file.write_all(&data).and_then(|_| file.sync_all()))
+      .and_then(|_| std::fs::rename(&temporary, &path))
+      .map_err(|error| format!("Failed to write {:?}: {}", path, error))
+  }
+
+  pub fn region_coord(chunk_coord: DD) -> (DD, usize) {
+    let region = (chunk_coord.0.div_euclid(REGION_WIDTH), chunk_coord.1.div_euclid(REGION_WIDTH));
+    let index = chunk_coord.1.rem_euclid(REGION_WIDTH) * REGION_WIDTH + chunk_coord.0.rem_euclid(REGION_WIDTH);
+    (region, index as usize)
+  }
+
+  fn region_path(&self, region: DD) -> PathBuf {
+    self
+      .directory
+      .join("region")
+      .join(format!("r.{}.{}.region", region.0, region.1))
+  }
+
+  fn region_mut(&mut self, region: DD) -> &mut Region {
+    let path = self.region_path(region);
+    self.regions.entry(region).or_insert_with(|| match Region::read(&path) {
+      Ok(region) => region,
+      Err(error) if error.kind() == ErrorKind::NotFound => Region::empty(),
+      Err(error) => {
+        // Keep the broken file around instead of silently overwriting it on the next flush
+        println!("Failed to read region {:?}: {}. Moving it aside.", path, error);
+        let _ = std::fs::rename(&path, path.with_extension("corrupt"));
+        Region::empty()
+      }
+    })
+  }
+
+  pub fn load_chunk(&mut self, chunk_coord: DD) -> Option<SavedChunk> {
+    let (region, index) = Self::region_coord(chunk_coord);
+    let blob = self.region_mut(region).chunks[index].as_ref()?;
+    let mut data = vec![];
+    let saved_chunk = ZlibDecoder::new(blob.as_slice())
+      .read_to_end(&mut data)
+      .map_err(|error| format!("Failed to decompress saved chunk {:?}: {}", chunk_coord, error))
+      .and_then(|_| {
+        bincode::deserialize(&data).map_err(|error| format!("Failed to deserialize saved chunk {:?}: {}", chunk_coord, error))
+      });
+    match saved_chunk {
+      Ok(saved_chunk) => Some(saved_chunk),
+      Err(error) => {
+        println!("{}", error);
+        let blob = blob.clone();
+        self.keep_corrupt(chunk_coord, &blob);
+        None
+      }
+    }
+  }
+
+  // The chunk is generated again and its blob would be overwritten the next time it is saved, so it is kept aside
+  fn keep_corrupt(&self, chunk_coord: DD, blob: &[u8]) {
+    let path = self
+      .directory
+      .join("region")
+      .join(format!("c.{}.{}.corrupt", chunk_coord.0, chunk_coord.1));
+    match std::fs::write(&path, blob) {
+      Ok(()) => println!("Kept it in {:?}", path),
+      Err(error) => println!("Failed to keep it in {:?}: {}", path, error),
+    }
+  }
+
+  pub fn store_chunk(&mut self, chunk_coord: DD, chunk: &Chunk, inventories: Vec<(DDD, &InternalInventory)>) {
+    let data = bincode::serialize(&SavedChunkRef { chunk, inventories }).unwrap();
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&data).unwrap();
+    let blob = encoder.finish().unwrap();
+    let (region, index) = Self::region_coord(chunk_coord);
+    let region = self.region_mut(region);
+    region.chunks[index] = Some(blob);
+    region.dirty = true;
+  }
+
+  pub fn flush(&mut self) {
+    let region_directory = self.directory.join("region");
+    if let Err(error) = std::fs::create_dir_all(&region_directory) {
+      println!("Failed to create {:?}: {}", region_directory, error);
+      return;
+    }
+    let paths = self
+      .regions
+      .iter()
+      .filter(|(_, region)| region.dirty)
+      .map(|(coord, _)| (*coord, self.region_path(*coord)))
+      .collect::<Vec<_>>();
+    for (coord, path) in paths {
+      let region = self.regions.get_mut(&coord).unwrap();
+      match region.write(&path) {
+        Ok(()) => region.dirty = false,
+        Err(error) => println!("Failed to write region {:?}: {}", path, error),
+      }
+    }
+  }
+}
@@ -0,0 +1,133 @@
+use bevy::prelude::*;
+use bevy::utils::hashbrown::HashMap;
+use shikataganai_common::ecs::components::blocks::registry::BlockRegistry;
+use shikataganai_common::ecs::components::blocks::BlockTrait;
+use shikataganai_common::ecs::resources::world::GameWorld;
+use shikataganai_common::networking::{EYE_HEIGHT, MAX_PLAYER_SPEED, REACH_DISTANCE};
+use shikataganai_common::util::array::{to_ddd, DDD};
+
+// Blocks per second, with some slack over what the client actually does
+pub const MAX_HORIZONTAL_SPEED: f32 = MAX_PLAYER_SPEED + 0.5;
+pub const MAX_RISE_SPEED: f32 = 8.0;
+// Seconds of movement a player may bank, so that moves arriving in bursts are not refused
+pub const MOVEMENT_BURST: f32 = 0.5;
+// A single move longer than this is refused whatever the player has banked
+pub const TELEPORT_DISTANCE: f32 = 10.0;
+// Blocks, a corrected player is believed again once it reports a position this close to the correction
+pub const CORRECTION_TOLERANCE: f32 = 1.0;
+// Blocks, how finely line of sight is sampled
+const SIGHT_STEP: f32 = 0.05;
+
+pub struct ClientMovement {
+  // Last position the server accepted
+  pub position: Vec3,
+  horizontal: f32,
+  rise: f32,
+  last_move: f64,
+  correcting: bool,
+}
+
+pub enum MoveVerdict {
+  Accept,
+  // The move is refused and the client has to be told where it actually is
+  Correct(Vec3),
+  // The move is refused, but a correction is already on its way
+  Ignore,
+}
+
+#[derive(Default, Resource)]
+pub struct MovementValidator {
+  pub clients: HashMap<u64, ClientMovement>,
+}
+
+impl MovementValidator {
+  pub fn open(&mut self, client: u64, position: Vec3, now: f64) {
+    self.clients.insert(
+      client,
+      ClientMovement {
+        position,
+        horizontal: MAX_HORIZONTAL_SPEED * MOVEMENT_BURST,
+        rise: MAX_RISE_SPEED * MOVEMENT_BURST,
+        last_move: now,
+        correcting: false,
+      },
+    );
+  }
+
+  pub fn check(&mut self, client: u64, to: Vec3, now: f64) -> MoveVerdict {
+    let Some(movement) = self.clients.get_mut(&client) else {
+      return MoveVerdict::Ignore;
+    };
+    let elapsed = (now - movement.last_move) as f32;
+    movement.last_move = now;
+    movement.horizontal = (movement.horizontal + elapsed * MAX_HORIZONTAL_SPEED).min(MAX_HORIZONTAL_SPEED * MOVEMENT_BURST);
+    movement.rise = (movement.rise + elapsed * MAX_RISE_SPEED).min(MAX_RISE_SPEED * MOVEMENT_BURST);
+
+    if movement.correcting {
+      if to.distance(movement.position) > CORRECTION_TOLERANCE {
+        return MoveVerdict::Ignore;
+      }
+      movement.correcting = false;
+      movement.position = to;
+      return MoveVerdict::Accept;
+    }
+
+    let delta = to - movement.position;
+    let horizontal = Vec2::new(delta.x, delta.z).length();
+    let rise = delta.y.max(0.0);
+    if delta.length() > TELEPORT_DISTANCE || horizontal > movement.horizontal || rise > movement.rise {
+      movement.correcting = true;
+      return MoveVerdict::Correct(movement.position);
+    }
+    movement.horizontal -= horizontal;
+    movement.rise -= rise;
+    movement.position = to;
+    MoveVerdict::Accept
+  }
+
+  pub fn remove(&mut self, client: u64) {
+    self.clients.remove(&client);
+  }
+}
+
+pub fn eye_position(translation: Vec3) -> Vec3 {
+  translation + Vec3::new(0.0, EYE_HEIGHT, 0.0)
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Reach {
+  // Removing a block or using it, the block itself has to be in sight
+  Block,
+  // Placing one, the player points at a neighbour of the location
+  Placement,
+}
+
+// Whether a player with the given eyes can reach the block and nothing solid stands in between
+pub fn can_reach(game_world: &GameWorld, blocks: &BlockRegistry, eye: Vec3, location: DDD, reach: Reach) -> bool {
+  let center = Vec3::new(location.0 as f32, location.1 as f32, location.2 as f32) + Vec3::splat(0.5);
+  // A block's center can be this much further away than the face the player is actually pointing at
+  if eye.distance(center) > REACH_DISTANCE + 0.87 {
+    return false;
+  }
+  // The center can hide behind a neighbour while one of the faces is in sight
+  let faces = [Vec3::X, Vec3::NEG_X, Vec3::Y, Vec3::NEG_Y, Vec3::Z, Vec3::NEG_Z];
+  std::iter::once(center)
+    .chain(faces.iter().map(|face| center + *face * 0.45))
+    .any(|target| clear_line(game_world, blocks, eye, target, location, reach))
+}
+
+fn clear_line(game_world: &GameWorld, blocks: &BlockRegistry, eye: Vec3, target: Vec3, location: DDD, reach: Reach) -> bool {
+  let start = to_ddd(eye);
+  let steps = (eye.distance(target) / SIGHT_STEP).ceil().max(1.0) as i32;
+  for step in 0..=steps {
+    let c = to_ddd(eye.lerp(target, step as f32 / steps as f32));
+    let distance = (c.0 - location.0).abs() + (c.1 - location.1).abs() + (c.2 - location.2).abs();
+    if distance == 0 || (reach == Reach::Placement && distance == 1) {
+      return true;
+    }
+    if c != start && game_world.get(c).map_or(false, |block| !blocks.block(block.block).passable()) {
+      return false;
+    }
+  }
+  false
+}
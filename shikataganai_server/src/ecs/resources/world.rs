@@ -1,51 +1,103 @@
+use crate::ecs::resources::storage::{SavedChunk, WorldStorage};
 use crate::ecs::systems::chunkgen::ChunkTask;
 use bevy::prelude::*;
 use bevy::tasks::AsyncComputeTaskPool;
-use bevy_renet::renet::RenetServer;
+use bevy::utils::hashbrown::HashMap;
 use flate2::write::ZlibEncoder;
 use flate2::Compression;
-use shikataganai_common::ecs::components::chunk::Chunk;
+use shikataganai_common::ecs::components::chunk::{Chunk, WorldHeight};
+use shikataganai_common::ecs::components::functors::InternalInventory;
+use shikataganai_common::ecs::resources::registries::Registries;
 use shikataganai_common::ecs::resources::world::GameWorld;
-use shikataganai_common::networking::{ServerChannel, ServerMessage, RELIABLE_CHANNEL_MAX_LENGTH};
-use shikataganai_common::util::array::DD;
+use shikataganai_common::util::array::{DD, DDD};
+use shikataganai_common::worldgen::WorldGenerator;
 use std::io::Write;
+use std::sync::Arc;
+
+#[derive(Resource, Clone)]
+pub struct ChunkGenerator(pub Arc<dyn WorldGenerator>);
+
+pub const SPAWN_COLUMN: DD = (10, 10);
+
+// Where players without a saved position appear
+#[derive(Resource, Clone, Copy)]
+pub struct SpawnPoint(pub Vec3);
+
+impl SpawnPoint {
+  // On top of the spawn column, or at the top of the world when the column is all air
+  pub fn find(generator: &dyn WorldGenerator, height: WorldHeight) -> Self {
+    let y = generator
+      .surface(SPAWN_COLUMN)
+      .map(|(y, _, _)| y + 1)
+      .unwrap_or(height.max_y)
+      .min(height.max_y);
+    Self(Vec3::new(SPAWN_COLUMN.0 as f32 + 0.5, y as f32, SPAWN_COLUMN.1 as f32 + 0.5))
+  }
+}
 
 pub trait ServerGameWorld {
-  fn get_chunk_or_spawn(&mut self, chunk_coord: DD, commands: &mut Commands, client: u64) -> Option<&Chunk>;
+  fn get_chunk_or_spawn(
+    &mut self,
+    chunk_coord: DD,
+    commands: &mut Commands,
+    storage: &mut WorldStorage,
+    generator: &ChunkGenerator,
+    registries: &Registries,
+  ) -> Option<&Chunk>;
 }
 
 impl ServerGameWorld for GameWorld {
-  fn get_chunk_or_spawn(&mut self, chunk_coord: DD, commands: &mut Commands, client: u64) -> Option<&Chunk> {
-    match self.chunks.get(&chunk_coord) {
-      None => {
-        if !self.generating.contains(&chunk_coord) {
-          self.generating.push(chunk_coord);
-          let dispatcher = AsyncComputeTaskPool::get();
-          commands.spawn(ChunkTask {
-            task: dispatcher.spawn(Chunk::generate(chunk_coord)),
-            coord: chunk_coord,
-            client,
-          });
+  fn get_chunk_or_spawn(
+    &mut self,
+    chunk_coord: DD,
+    commands: &mut Commands,
+    storage: &mut WorldStorage,
+    generator: &ChunkGenerator,
+    registries: &Registries,
+  ) -> Option<&Chunk> {
+    if !self.chunks.contains_key(&chunk_coord) {
+      match storage.load_chunk(chunk_coord) {
+        Some(saved_chunk) => {
+          self.chunks.insert(chunk_coord, restore_chunk(saved_chunk, commands, registries));
+        }
+        None => {
+          if !self.generating.contains(&chunk_coord) {
+            self.generating.push(chunk_coord);
+            let dispatcher = AsyncComputeTaskPool::get();
+            let generator = generator.0.clone();
+            commands.spawn(ChunkTask {
+              task: dispatcher.spawn(async move { generator.generate(chunk_coord) }),
+              coord: chunk_coord,
+            });
+          }
+          return None;
         }
-        None
       }
-      Some(chunk) => Some(chunk),
     }
+    self.chunks.get(&chunk_coord)
   }
 }
 
-pub fn send_chunk_data(server: &mut RenetServer, chunk: &Chunk, client: u64) {
+// Entities stored in a saved chunk are stale, functors have to be respawned and filled with the saved state
+fn restore_chunk(SavedChunk { mut chunk, inventories }: SavedChunk, commands: &mut Commands, registries: &Registries) -> Chunk {
+  let mut inventories: HashMap<DDD, InternalInventory> = inventories.into_iter().collect();
+  chunk.foreach_mut(|i, block| {
+    block.entity = Entity::from_bits(0);
+    let definition = registries.block(block.block);
+    if definition.need_to_spawn_functors() {
+      definition.spawn_or_add_functors(block, i, commands);
+      if let Some(inventory) = inventories.remove(&i) {
+        commands.entity(block.entity).insert(inventory);
+      }
+    }
+  });
+  chunk
+}
+
+// Compressed the same way the client expects chunk streams to be, see stream_chunks
+pub fn encode_chunk(chunk: &Chunk) -> Vec<u8> {
   let data = bincode::serialize(&chunk).unwrap();
   let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
   encoder.write_all(&data).unwrap();
-  let message = encoder.finish().unwrap();
-  assert!(
-    message.len() <= RELIABLE_CHANNEL_MAX_LENGTH as usize,
-    "Chunk packet size limit reached. Stopgap has been used up. Good luck fixing that."
-  );
-  server.send_message(
-    client,
-    ServerChannel::GameEvent.id(),
-    bincode::serialize(&ServerMessage::ChunkData { chunk: message }).unwrap(),
-  );
+  encoder.finish().unwrap()
 }
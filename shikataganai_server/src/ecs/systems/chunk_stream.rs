@@ -0,0 +1,120 @@
+use crate::ecs::plugins::server::PlayerEntities;
+use crate::ecs::resources::chunk_stream::{ChunkStreams, CHUNK_BANDWIDTH};
+use crate::ecs::resources::storage::WorldStorage;
+use crate::ecs::resources::world::{encode_chunk, ChunkGenerator, ServerGameWorld};
+use bevy::prelude::*;
+use bevy::utils::hashbrown::HashSet;
+use bevy_renet::renet::RenetServer;
+use bincode::serialize;
+use shikataganai_common::ecs::resources::registries::Registries;
+use shikataganai_common::ecs::resources::world::GameWorld;
+use shikataganai_common::networking::{fragment_chunk, ChunkStreamMessage, ServerChannel};
+use shikataganai_common::util::array::{to_ddd, DD};
+
+// Sends requested chunks closest to the player first, one at a time and within the client's bandwidth
+pub fn stream_chunks(
+  mut server: ResMut<RenetServer>,
+  mut streams: ResMut<ChunkStreams>,
+  game_world: Res<GameWorld>,
+  player_entities: Res<PlayerEntities>,
+  transforms: Query<&Transform>,
+  time: Res<Time>,
+) {
+  let refill = (CHUNK_BANDWIDTH as f32 * time.delta_seconds()) as usize;
+  for (client, stream) in streams.clients.iter_mut() {
+    stream.allowance = (stream.allowance + refill).min(CHUNK_BANDWIDTH);
+    let center = player_entities
+      .players
+      .get(client)
+      .and_then(|entity| transforms.get(*entity).ok())
+      .map(|transform| GameWorld::get_chunk_coord(to_ddd(transform.translation)));
+    loop {
+      if stream.sending.is_empty() {
+        let next = stream
+          .requested
+          .iter()
+          .enumerate()
+          .filter(|(_, chunk_coord)| game_world.chunks.contains_key(*chunk_coord))
+          .min_by_key(|(_, chunk_coord)| {
+            center.map_or(0, |center| {
+              (chunk_coord.0 - center.0).pow(2) + (chunk_coord.1 - center.1).pow(2)
+            })
+          })
+          .map(|(index, _)| index);
+        let Some(next) = next else {
+          break;
+        };
+        let chunk_coord = stream.requested.remove(next);
+        stream.sending = fragment_chunk(chunk_coord, &encode_chunk(&game_world.chunks[&chunk_coord])).into();
+      }
+      let Some(fragment) = stream.sending.front() else {
+        break;
+      };
+      if fragment.data.len() > stream.allowance {
+        break;
+      }
+      stream.allowance -= fragment.data.len();
+      let fragment = stream.sending.pop_front().unwrap();
+      if stream.sending.is_empty() {
+        stream.delivered.insert(fragment.chunk_coord);
+      }
+      server.send_message(
+        *client,
+        ServerChannel::ChunkStream.id(),
+        serialize(&ChunkStreamMessage::Fragment(fragment)).unwrap(),
+      );
+    }
+  }
+}
+
+// Follows players around: chunks that come into view are loaded and queued, the ones that left it are dropped
+pub fn update_views(
+  mut commands: Commands,
+  mut server: ResMut<RenetServer>,
+  mut streams: ResMut<ChunkStreams>,
+  mut game_world: ResMut<GameWorld>,
+  mut storage: ResMut<WorldStorage>,
+  generator: Res<ChunkGenerator>,
+  registries: Res<Registries>,
+  player_entities: Res<PlayerEntities>,
+  transforms: Query<&Transform>,
+) {
+  for (client, entity) in player_entities.players.iter() {
+    let (Some(stream), Ok(transform)) = (streams.clients.get_mut(client), transforms.get(*entity)) else {
+      continue;
+    };
+    let center = GameWorld::get_chunk_coord(to_ddd(transform.translation));
+    if stream.center == Some(center) {
+      continue;
+    }
+    stream.center = Some(center);
+    let radius = stream.view_distance;
+    let mut visible = HashSet::new();
+    for x in -radius..=radius {
+      for z in -radius..=radius {
+        if x * x + z * z <= radius * radius {
+          visible.insert((center.0 + x, center.1 + z));
+        }
+      }
+    }
+    let load: Vec<DD> = visible.difference(&stream.visible).copied().collect();
+    let unload: Vec<DD> = stream.visible.difference(&visible).copied().collect();
+    stream.requested.retain(|chunk_coord| visible.contains(chunk_coord));
+    if let Some(fragment) = stream.sending.front() && !visible.contains(&fragment.chunk_coord) {
+      stream.sending.clear();
+    }
+    stream.visible = visible;
+    for chunk_coord in load {
+      game_world.get_chunk_or_spawn(chunk_coord, &mut commands, storage.as_mut(), generator.as_ref(), &registries);
+      stream.requested.push(chunk_coord);
+    }
+    for chunk_coord in unload {
+      stream.delivered.remove(&chunk_coord);
+      server.send_message(
+        *client,
+        ServerChannel::ChunkStream.id(),
+        serialize(&ChunkStreamMessage::Unload { chunk_coord }).unwrap(),
+      );
+    }
+  }
+}
@@ -1,7 +1,5 @@
-use crate::ecs::resources::world::send_chunk_data;
 use bevy::prelude::*;
 use bevy::tasks::Task;
-use bevy_renet::renet::RenetServer;
 use shikataganai_common::ecs::components::chunk::Chunk;
 use shikataganai_common::ecs::resources::world::GameWorld;
 use shikataganai_common::util::array::DD;
@@ -10,18 +8,15 @@ use shikataganai_common::util::array::DD;
 pub struct ChunkTask {
   pub task: Task<Chunk>,
   pub coord: DD,
-  pub client: u64,
 }
 
 pub fn collect_async_chunks(
   mut query: Query<(Entity, &mut ChunkTask)>,
   mut commands: Commands,
-  mut server: ResMut<RenetServer>,
   mut world: ResMut<GameWorld>,
 ) {
   for (e, mut task) in query.iter_mut() {
     if let Some(chunk) = futures_lite::future::block_on(futures_lite::future::poll_once(&mut task.task)) {
-      send_chunk_data(server.as_mut(), &chunk, task.client);
       world.chunks.insert(task.coord, chunk);
       world.remove_from_generating(task.coord);
       commands.entity(e).remove::<ChunkTask>();
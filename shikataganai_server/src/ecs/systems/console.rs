@@ -0,0 +1,199 @@
+use crate::ecs::plugins::server::{
+  broadcast_chat, send_block_state, send_chat, send_inventory, FunctorViewers, PlayerEntities,
+};
+use crate::ecs::resources::access::AccessLists;
+use crate::ecs::resources::chunk_stream::ChunkStreams;
+use crate::ecs::resources::console::{AdminCommand, AdminConsole, ConsoleReply, Destination, CONSOLE_HELP};
+use crate::ecs::resources::protocol::ProtocolViolations;
+use crate::ecs::resources::validation::MovementValidator;
+use crate::config::ServerSettings;
+use crate::ecs::systems::save::{DirtyChunks, SaveRequest};
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy_renet::renet::RenetServer;
+use bincode::serialize;
+use shikataganai_common::ecs::components::blocks::block_id::BlockId;
+use shikataganai_common::ecs::components::functors::InternalInventory;
+use shikataganai_common::ecs::resources::light::{LightLevel, RelightEvent};
+use shikataganai_common::ecs::resources::player::{PlayerInventory, PlayerNickname};
+use shikataganai_common::ecs::resources::registries::Registries;
+use shikataganai_common::ecs::resources::world::GameWorld;
+use shikataganai_common::networking::{ServerChannel, ServerMessage};
+use shikataganai_common::recipes::Recipes;
+
+type Players<'w, 's> = Query<'w, 's, (&'static mut Transform, &'static PlayerNickname, &'static mut PlayerInventory)>;
+
+// Client, entity and position of a connected player
+fn find_player(player_entities: &PlayerEntities, players: &Players, nickname: &str) -> Result<(u64, Entity, Vec3), String> {
+  player_entities
+    .players
+    .iter()
+    .find_map(|(client, entity)| {
+      let (transform, player_nickname, _) = players.get(*entity).ok()?;
+      (player_nickname.0 == nickname).then_some((*client, *entity, transform.translation))
+    })
+    .ok_or_else(|| format!("{} is not online", nickname))
+}
+
+// Answers are plain lines for scripts to read, failures start with "error: "
+pub fn admin_console(
+  mut commands: Commands,
+  console: Res<AdminConsole>,
+  mut server: ResMut<RenetServer>,
+  player_entities: Res<PlayerEntities>,
+  mut players: Players,
+  (mut game_world, mut dirty_chunks, mut relight, chunk_streams, mut functor_viewers, inventories): (
+    ResMut<GameWorld>,
+    ResMut<DirtyChunks>,
+    EventWriter<RelightEvent>,
+    Res<ChunkStreams>,
+    ResMut<FunctorViewers>,
+    Query<&InternalInventory>,
+  ),
+  mut violations: ResMut<ProtocolViolations>,
+  mut movement: ResMut<MovementValidator>,
+  mut access_lists: ResMut<AccessLists>,
+  mut save_requests: EventWriter<SaveRequest>,
+  mut exit: EventWriter<AppExit>,
+  time: Res<Time>,
+  mut recipes: ResMut<Recipes>,
+  settings: Res<ServerSettings>,
+  registries: Res<Registries>,
+) {
+  for request in console.drain() {
+    let now = time.elapsed_seconds_f64();
+    let answer = AdminCommand::parse(&request.line, &registries).and_then(|command| match command {
+      AdminCommand::Help => Ok(CONSOLE_HELP.to_string()),
+      AdminCommand::List => Ok(
+        player_entities
+          .players
+          .iter()
+          .filter_map(|(client, entity)| {
+            let (transform, nickname, _) = players.get(*entity).ok()?;
+            let Vec3 { x, y, z } = transform.translation;
+            Some(format!("{}\t{}\t{:.2}\t{:.2}\t{:.2}", client, nickname.0, x, y, z))
+          })
+          .collect::<Vec<_>>()
+          .join("\n"),
+      ),
+      AdminCommand::Kick { nickname, reason } => {
+        let (client, ..) = find_player(&player_entities, &players, &nickname)?;
+        let reason = reason.unwrap_or_else(|| "Kicked by an operator".to_string());
+        violations.kick(server.as_mut(), client, reason, now);
+        Ok(format!("Kicked {}", nickname))
+      }
+      AdminCommand::Ban { nickname, reason } => {
+        if !access_lists.banned.insert(&nickname) {
+          return Err(format!("{} is already banned", nickname));
+        }
+        access_lists.banned.save()?;
+        if let Ok((client, ..)) = find_player(&player_entities, &players, &nickname) {
+          let reason = reason.unwrap_or_else(|| "Banned by an operator".to_string());
+          violations.kick(server.as_mut(), client, reason, now);
+        }
+        Ok(format!("Banned {}", nickname))
+      }
+      AdminCommand::Unban { nickname } => {
+        if !access_lists.banned.remove(&nickname) {
+          return Err(format!("{} is not banned", nickname));
+        }
+        access_lists.banned.save()?;
+        Ok(format!("Unbanned {}", nickname))
+      }
+      AdminCommand::Teleport { nickname, destination } => {
+        let (client, entity, _) = find_player(&player_entities, &players, &nickname)?;
+        let translation = match destination {
+          Destination::Position(translation) => translation,
+          Destination::Player(other) => find_player(&player_entities, &players, &other)?.2,
+        };
+        if let Ok((mut transform, ..)) = players.get_mut(entity) {
+          transform.translation = translation;
+        }
+        // The move would be refused otherwise
+        movement.open(client, translation, now);
+        server.send_message(
+          client,
+          ServerChannel::GameEvent.id(),
+          serialize(&ServerMessage::PlayerCorrection { translation }).unwrap(),
+        );
+        Ok(format!(
+          "Teleported {} to {:.2} {:.2} {:.2}",
+          nickname, translation.x, translation.y, translation.z
+        ))
+      }
+      AdminCommand::Give {
+        nickname,
+        block_or_item,
+        quant,
+      } => {
+        let (client, entity, _) = find_player(&player_entities, &players, &nickname)?;
+        let Ok((_, _, mut inventory)) = players.get_mut(entity) else {
+          return Err(format!("{} is not online", nickname));
+        };
+        let name = registries.name(block_or_item);
+        if !inventory.add(&registries, block_or_item, quant) {
+          return Err(format!("{} has no room for {}", nickname, name));
+        }
+        send_inventory(server.as_mut(), client, &inventory);
+        Ok(format!("Gave {} {} to {}", quant, name, nickname))
+      }
+      AdminCommand::SetBlock { location, block } => {
+        let Some(current) = game_world.get_mut(location) else {
+          return Err(format!("The chunk holding {:?} is not loaded", location));
+        };
+        // Nobody would get what is in it back
+        if let Ok(inventory) = inventories.get(current.entity) && inventory.inventory.iter().any(Option::is_some) {
+          return Err(format!("{:?} holds items, empty it first", location));
+        }
+        if current.entity != Entity::from_bits(0) {
+          commands.entity(current.entity).despawn_recursive();
+        }
+        *current = block.into();
+        let definition = registries.block(block);
+        if definition.need_to_spawn_functors() {
+          definition.spawn_or_add_functors(current, location, &mut commands);
+        }
+        functor_viewers.viewers.remove(&location);
+        dirty_chunks.0.insert(GameWorld::get_chunk_coord(location));
+        relight.send(RelightEvent::Relight(location));
+        if block != BlockId::Air {
+          game_world.set_light_level(location, LightLevel::dark());
+        }
+        for watcher in chunk_streams.watchers(GameWorld::get_chunk_coord(location)) {
+          send_block_state(server.as_mut(), watcher, game_world.as_ref(), location);
+        }
+        Ok(format!("Set {:?} to {}", location, registries.block(block).name()))
+      }
+      AdminCommand::Save => {
+        save_requests.send(SaveRequest);
+        Ok("Saving the world".to_string())
+      }
+      AdminCommand::Reload => {
+        *recipes = Recipes::load(settings.recipes.as_deref(), &registries)?;
+        Ok(format!("Loaded {} recipes", recipes.recipes.len()))
+      }
+      AdminCommand::Stop => {
+        // The world is saved by save_on_exit
+        for client in player_entities.players.keys() {
+          violations.kick(server.as_mut(), *client, "The server is shutting down".to_string(), now);
+        }
+        exit.send(AppExit);
+        Ok("Stopping the server".to_string())
+      }
+      AdminCommand::Say { message } => {
+        // Already printed by broadcast_chat
+        broadcast_chat(server.as_mut(), player_entities.as_ref(), None, &message);
+        Ok(String::new())
+      }
+    });
+    let answer = answer.unwrap_or_else(|error| format!("error: {}", error));
+    match request.reply {
+      ConsoleReply::Remote(reply) => {
+        let _ = reply.send(answer);
+      }
+      _ if answer.is_empty() => {}
+      ConsoleReply::Player(client) => send_chat(server.as_mut(), client, None, &answer),
+      ConsoleReply::Stdout => println!("{}", answer),
+    }
+  }
+}
@@ -1,7 +1,11 @@
+use crate::ecs::resources::chunk_stream::ChunkStreams;
+use crate::ecs::systems::save::DirtyChunks;
 use bevy::prelude::*;
+use bevy::utils::hashbrown::HashMap;
 use bevy_renet::renet::RenetServer;
 use bincode::serialize;
 use shikataganai_common::ecs::resources::light::{relight_helper, RelightEvent};
+use shikataganai_common::ecs::resources::registries::Registries;
 use shikataganai_common::ecs::resources::world::GameWorld;
 use shikataganai_common::networking::{ServerChannel, ServerMessage};
 
@@ -9,16 +13,25 @@ pub fn relight_system(
   mut relight: EventReader<RelightEvent>,
   mut game_world: ResMut<GameWorld>,
   mut server: ResMut<RenetServer>,
+  mut dirty_chunks: ResMut<DirtyChunks>,
+  chunk_streams: Res<ChunkStreams>,
+  registries: Res<Registries>,
 ) {
-  let mut relights = vec![];
-  for coord in relight_helper(&mut relight, game_world.as_mut()).iter() {
-    relights.push((*coord, game_world.get_light_level(*coord).unwrap()))
+  // Every client only hears about the light in chunks it sees
+  let mut relights: HashMap<u64, Vec<_>> = HashMap::new();
+  for coord in relight_helper(&mut relight, game_world.as_mut(), &registries.blocks).iter() {
+    let chunk_coord = GameWorld::get_chunk_coord(*coord);
+    dirty_chunks.0.insert(chunk_coord);
+    let light_level = game_world.get_light_level(*coord).unwrap();
+    for client in chunk_streams.watchers(chunk_coord) {
+      relights.entry(client).or_default().push((*coord, light_level));
+    }
   }
-  if !relights.is_empty() {
+  for (client, relights) in relights {
     let message = serialize(&ServerMessage::Relight { relights }).unwrap();
     // TODO: idk
     if message.len() < 2000 {
-      server.broadcast_message(ServerChannel::GameEvent.id(), message)
+      server.send_message(client, ServerChannel::GameEvent.id(), message)
     }
   }
 }
@@ -1,2 +1,5 @@
+pub mod chunk_stream;
 pub mod chunkgen;
+pub mod console;
 pub mod light;
+pub mod save;
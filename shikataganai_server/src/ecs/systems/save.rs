@@ -0,0 +1,188 @@
+use crate::ecs::plugins::server::FunctorViewers;
+use crate::ecs::resources::chunk_stream::ChunkStreams;
+use crate::ecs::resources::players::{PlayerData, PlayerDatabase};
+use crate::ecs::resources::storage::WorldStorage;
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use shikataganai_common::ecs::components::functors::InternalInventory;
+use shikataganai_common::ecs::resources::player::{PlayerInventory, PlayerNickname};
+use shikataganai_common::ecs::resources::world::GameWorld;
+use shikataganai_common::networking::PolarRotation;
+use shikataganai_common::util::array::DD;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+pub const AUTOSAVE_INTERVAL: f32 = 60.0;
+pub const UNLOAD_INTERVAL: f32 = 5.0;
+
+// Chunks that have been changed since the last flush to disk
+#[derive(Default, Resource)]
+pub struct DirtyChunks(pub HashSet<DD>);
+
+#[derive(Resource)]
+pub struct AutosaveTimer(pub Timer);
+
+// Saves right away instead of waiting for the timer
+pub struct SaveRequest;
+
+#[derive(Resource)]
+pub struct UnloadTimer(pub Timer);
+
+// Raised from outside the app to stop the server the same way AppExit does, saving on the way out
+#[derive(Clone, Default, Resource)]
+pub struct ShutdownSignal(pub Arc<AtomicBool>);
+
+impl ShutdownSignal {
+  // Ctrl-C and SIGTERM, for the dedicated server. There is one handler per process, so an embedded server doesn't
+  // take it from the game.
+  pub fn on_ctrl_c() -> Result<Self, String> {
+    let signal = Self::default();
+    let raised = signal.0.clone();
+    ctrlc::set_handler(move || raised.store(true, Ordering::SeqCst))
+      .map_err(|error| format!("Failed to install the Ctrl-C handler: {}", error))?;
+    Ok(signal)
+  }
+}
+
+impl Default for UnloadTimer {
+  fn default() -> Self {
+    Self(Timer::from_seconds(UNLOAD_INTERVAL, TimerMode::Repeating))
+  }
+}
+
+pub fn save_world(
+  dirty_chunks: &mut DirtyChunks,
+  game_world: &GameWorld,
+  storage: &mut WorldStorage,
+  inventories: &Query<&InternalInventory>,
+) {
+  for chunk_coord in dirty_chunks.0.drain() {
+    if let Some(chunk) = game_world.chunks.get(&chunk_coord) {
+      let mut chunk_inventories = vec![];
+      chunk.foreach(|location, block| {
+        if block.entity != Entity::from_bits(0) && let Ok(inventory) = inventories.get(block.entity) {
+          chunk_inventories.push((location, inventory));
+        }
+      });
+      storage.store_chunk(chunk_coord, chunk, chunk_inventories);
+    }
+  }
+  storage.flush();
+}
+
+// Only updates the database in memory, it is written out by the caller
+pub fn save_player(
+  player_database: &mut PlayerDatabase,
+  nickname: &PlayerNickname,
+  transform: &Transform,
+  rotation: &PolarRotation,
+  inventory: &PlayerInventory,
+) {
+  player_database.players.insert(
+    nickname.0.clone(),
+    PlayerData {
+      translation: transform.translation,
+      rotation: *rotation,
+      inventory: inventory.clone(),
+    },
+  );
+}
+
+pub fn save_players(
+  player_database: &mut PlayerDatabase,
+  players: &Query<(&PlayerNickname, &Transform, &PolarRotation, &PlayerInventory)>,
+) {
+  for (nickname, transform, rotation, inventory) in players.iter() {
+    save_player(player_database, nickname, transform, rotation, inventory);
+  }
+  player_database.save();
+}
+
+// Chunks out of every player's view are saved if they have changed and dropped along with their functors
+pub fn unload_chunks(
+  mut commands: Commands,
+  time: Res<Time>,
+  mut timer: ResMut<UnloadTimer>,
+  mut dirty_chunks: ResMut<DirtyChunks>,
+  mut game_world: ResMut<GameWorld>,
+  mut storage: ResMut<WorldStorage>,
+  inventories: Query<&InternalInventory>,
+  chunk_streams: Res<ChunkStreams>,
+  mut functor_viewers: ResMut<FunctorViewers>,
+) {
+  if !timer.0.tick(time.delta()).just_finished() {
+    return;
+  }
+  let unwatched: Vec<DD> = game_world
+    .chunks
+    .keys()
+    .copied()
+    .filter(|chunk_coord| !chunk_streams.is_watched(*chunk_coord))
+    .collect();
+  if unwatched.is_empty() {
+    return;
+  }
+  for chunk_coord in unwatched {
+    let chunk = game_world.chunks.remove(&chunk_coord).unwrap();
+    let mut entities = vec![];
+    chunk.foreach(|location, block| {
+      if block.entity != Entity::from_bits(0) {
+        entities.push((location, block.entity));
+      }
+    });
+    if dirty_chunks.0.remove(&chunk_coord) {
+      let chunk_inventories = entities
+        .iter()
+        .filter_map(|(location, entity)| inventories.get(*entity).ok().map(|inventory| (*location, inventory)))
+        .collect();
+      storage.store_chunk(chunk_coord, &chunk, chunk_inventories);
+    }
+    for (location, entity) in entities {
+      functor_viewers.viewers.remove(&location);
+      commands.entity(entity).despawn_recursive();
+    }
+  }
+  storage.flush();
+}
+
+pub fn autosave_system(
+  time: Res<Time>,
+  mut timer: ResMut<AutosaveTimer>,
+  mut save_requests: EventReader<SaveRequest>,
+  mut dirty_chunks: ResMut<DirtyChunks>,
+  game_world: Res<GameWorld>,
+  mut storage: ResMut<WorldStorage>,
+  inventories: Query<&InternalInventory>,
+  mut player_database: ResMut<PlayerDatabase>,
+  players: Query<(&PlayerNickname, &Transform, &PolarRotation, &PlayerInventory)>,
+) {
+  let requested = save_requests.iter().count() > 0;
+  if timer.0.tick(time.delta()).just_finished() || requested {
+    save_world(dirty_chunks.as_mut(), game_world.as_ref(), storage.as_mut(), &inventories);
+    save_players(player_database.as_mut(), &players);
+  }
+}
+
+pub fn shutdown_system(signal: Res<ShutdownSignal>, mut exit_events: EventWriter<AppExit>) {
+  if signal.0.swap(false, Ordering::SeqCst) {
+    println!("Shutting down");
+    exit_events.send(AppExit);
+  }
+}
+
+pub fn save_on_exit(
+  mut exit_events: EventReader<AppExit>,
+  mut dirty_chunks: ResMut<DirtyChunks>,
+  game_world: Res<GameWorld>,
+  mut storage: ResMut<WorldStorage>,
+  inventories: Query<&InternalInventory>,
+  mut player_database: ResMut<PlayerDatabase>,
+  players: Query<(&PlayerNickname, &Transform, &PolarRotation, &PlayerInventory)>,
+) {
+  if exit_events.iter().next().is_some() {
+    println!("Saving world to {:?}", storage.directory);
+    save_world(dirty_chunks.as_mut(), game_world.as_ref(), storage.as_mut(), &inventories);
+    save_players(player_database.as_mut(), &players);
+  }
+}
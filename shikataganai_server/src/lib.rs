@@ -1,21 +1,122 @@
 #![feature(let_chains)]
 use bevy::app::ScheduleRunnerSettings;
 use bevy::prelude::*;
+use shikataganai_common::ecs::components::blocks::registry::BlockRegistry;
+use shikataganai_common::ecs::components::item::registry::ItemRegistry;
+use shikataganai_common::ecs::components::chunk::WorldHeight;
+use shikataganai_common::ecs::resources::registries::Registries;
 use shikataganai_common::ecs::resources::world::GameWorld;
 use shikataganai_common::recipes::Recipes;
 use std::time::Duration;
 
-use crate::ecs::plugins::server::{ShikataganaiServerAddress, ShikataganaiServerPlugin};
+use crate::config::ServerSettings;
+use crate::ecs::plugins::server::{bind_server, ShikataganaiServerPlugin};
+use crate::ecs::resources::access::{AccessLists, NicknameList, BANNED_FILE};
+use crate::ecs::resources::auth::{load_key, NicknameReservations, ServerKey};
+use crate::ecs::resources::console::AdminConsole;
+use crate::ecs::resources::players::PlayerDatabase;
+use crate::ecs::resources::storage::{WorldMetadata, WorldStorage};
+use crate::ecs::resources::world::{ChunkGenerator, SpawnPoint};
+use crate::ecs::systems::save::ShutdownSignal;
+use shikataganai_common::worldgen::{PassGenerator, TerrainMode};
+use std::sync::Arc;
 
+pub mod config;
 pub mod ecs;
 
-pub fn spawn_server(address: ShikataganaiServerAddress) {
+// Only used when the world is created, an existing world keeps generating the way it started
+#[derive(Default)]
+pub struct WorldOptions {
+  pub seed: Option<u64>,
+  pub terrain: Option<TerrainMode>,
+  pub height: Option<WorldHeight>,
+}
+
+// Runs until the server is stopped, errors are the ones that keep it from starting
+pub fn spawn_server(settings: ServerSettings, options: WorldOptions, shutdown: ShutdownSignal) -> Result<(), String> {
+  let storage = WorldStorage::new(settings.world.clone());
+  let player_database = PlayerDatabase::load(storage.directory.join("players.bin"))?;
+  let reservations = NicknameReservations::load(storage.directory.join("nicknames.bin"))?;
+  let server_key = ServerKey(load_key(&storage.directory)?);
+  let access_lists = AccessLists {
+    whitelist: settings
+      .whitelist
+      .clone()
+      .map(|path| NicknameList::load(Some(path)))
+      .transpose()?,
+    ops: NicknameList::load(settings.ops.clone())?,
+    banned: NicknameList::open(storage.directory.join(BANNED_FILE))?,
+  };
+  // Ids the world already uses stay with their items and blocks, the files are rewritten with the ones added now
+  let item_ids = storage.load_ids("item_ids.bin")?;
+  let items = ItemRegistry::read_definitions(settings.items.as_deref())
+    .and_then(|definitions| ItemRegistry::assign_ids(definitions, &item_ids))?;
+  let block_ids = storage.load_ids("block_ids.bin")?;
+  let blocks = BlockRegistry::read_definitions(settings.blocks.as_deref())
+    .and_then(|definitions| BlockRegistry::assign_ids(definitions, &block_ids))?;
+  let registries = Registries::new(items, blocks)?;
+  storage.store_ids("item_ids.bin", &registries.items.names())?;
+  storage.store_ids("block_ids.bin", &registries.blocks.names())?;
+  let recipes = Recipes::load(settings.recipes.as_deref(), &registries)?;
+  let server = bind_server(&settings, &server_key)?;
+  let console = AdminConsole::spawn(settings.console, settings.rcon, settings.rcon_password.clone())?;
+  let metadata = match storage.load_metadata()? {
+    Some(metadata) => {
+      if let Some(seed) = options.seed && seed != metadata.seed {
+        println!("Ignoring seed {}, the world has already been created with seed {}", seed, metadata.seed);
+      }
+      if let Some(terrain) = options.terrain && terrain != metadata.terrain {
+        println!("Ignoring terrain mode {:?}, the world has already been created with {:?}", terrain, metadata.terrain);
+      }
+      if let Some(height) = options.height && height != metadata.height {
+        println!(
+          "Ignoring world height {}:{}, the world has already been created with {}:{}",
+          height.min_y, height.max_y, metadata.height.min_y, metadata.height.max_y
+        );
+      }
+      metadata
+    }
+    None => {
+      let metadata = WorldMetadata {
+        seed: options.seed.unwrap_or_else(rand::random),
+        terrain: options.terrain.unwrap_or_default(),
+        height: options.height.unwrap_or_default(),
+      };
+      storage.store_metadata(&metadata)?;
+      metadata
+    }
+  };
+  println!(
+    "World seed: {}, terrain: {:?}, height: {}:{}",
+    metadata.seed, metadata.terrain, metadata.height.min_y, metadata.height.max_y
+  );
+  let generator = Arc::new(PassGenerator::standard(
+    metadata.seed,
+    metadata.terrain,
+    metadata.height,
+    registries.blocks.clone(),
+  ));
   App::new()
-    .insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_secs_f64(1.0 / 60.0)))
+    .insert_resource(ScheduleRunnerSettings::run_loop(Duration::from_secs_f64(1.0 / settings.tick_rate)))
     .add_plugins(MinimalPlugins)
-    .init_resource::<GameWorld>()
-    .init_resource::<Recipes>()
-    .insert_resource(address)
+    .insert_resource(GameWorld {
+      height: metadata.height,
+      ..default()
+    })
+    .insert_resource(recipes)
+    .insert_resource(settings)
+    .insert_resource(server)
+    .insert_resource(access_lists)
+    .insert_resource(console)
+    .insert_resource(storage)
+    .insert_resource(player_database)
+    .insert_resource(reservations)
+    .insert_resource(server_key)
+    .insert_resource(SpawnPoint::find(generator.as_ref(), metadata.height))
+    .insert_resource(ChunkGenerator(generator))
+    .insert_resource(registries)
+    .insert_resource(shutdown)
     .add_plugin(ShikataganaiServerPlugin)
     .run();
+  Ok(())
 }
@@ -1,17 +1,14 @@
 #![feature(let_chains)]
-use shikataganai_server::ecs::plugins::server::ShikataganaiServerAddress;
+use shikataganai_server::config::load_config;
+use shikataganai_server::ecs::systems::save::ShutdownSignal;
 use shikataganai_server::spawn_server;
 use std::env;
 
 fn main() {
-  let address: Option<String> = env::args().into_iter().nth(1);
-
-  let address = match address {
-    None => ShikataganaiServerAddress {
-      address: "127.0.0.1:8181".to_string(),
-    },
-    Some(address) => ShikataganaiServerAddress { address },
-  };
-
-  spawn_server(address);
+  let result = load_config(env::args().skip(1))
+    .and_then(|(settings, options)| spawn_server(settings, options, ShutdownSignal::on_ctrl_c()?));
+  if let Err(error) = result {
+    eprintln!("{}", error);
+    std::process::exit(1);
+  }
 }